@@ -0,0 +1,746 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::{AsmError, Instruction, Mode, START_ADDR};
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MNEMONICS: [&str; 33] = [
+    "NOP", "CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB",
+    "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH",
+    "SCU", "PLANE", "AUDIO", "PITCH", "DB", "DW",
+];
+
+/// Turns CHIP-8 assembly into a program image loaded at `START_ADDR`.
+///
+/// The source uses the mnemonics listed in `todo.md`, one statement per line:
+///
+/// ```text
+/// ; comments run to the end of the line
+/// SPEED equ 2               ; constants
+/// loop:                     ; labels
+///     LD V0, SPEED
+///     LD I, sprite
+///     DRW V0, V1, 3
+///     JP loop
+/// sprite:
+///     db 0b11100000, 0xA0, %11100000
+///     dw 0x1234, loop + 2
+/// include "font.asm"        ; relative to the including file
+/// ```
+///
+/// Numbers are decimal, hex (`0x1F`, `#1F`, `$1F`) or binary (`0b101`,
+/// `%101`); operands may add and subtract numbers, labels and constants.
+/// Instructions the selected `Mode` lacks are rejected.
+pub struct Assembler {
+    mode: Mode,
+    sources: HashMap<String, String>,
+}
+
+/// Assembles `source` with no include files other than those on disk.
+pub fn assemble(source: &str, mode: Mode) -> Result<Vec<u8>, AsmError> {
+    Assembler::new(mode).assemble(source)
+}
+
+impl Assembler {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Makes `include "name"` resolve to `text` instead of reading a file.
+    pub fn with_source(mut self, name: impl Into<String>, text: impl Into<String>) -> Self {
+        self.sources.insert(name.into(), text.into());
+        self
+    }
+
+    /// Assembles source that did not come from a file; includes are looked
+    /// up relative to the working directory.
+    pub fn assemble(&self, source: &str) -> Result<Vec<u8>, AsmError> {
+        let mut program = Program::default();
+        self.parse(&mut program, "<input>".into(), source, None, 0)?;
+        program.encode(self.mode)
+    }
+
+    pub fn assemble_file(&self, path: &Path) -> Result<Vec<u8>, AsmError> {
+        let source = fs::read_to_string(path).map_err(|err| AsmError {
+            file: path.display().to_string(),
+            line: 0,
+            message: err.to_string(),
+        })?;
+        let mut program = Program::default();
+        self.parse(
+            &mut program,
+            path.display().to_string().into(),
+            &source,
+            path.parent(),
+            0,
+        )?;
+        program.encode(self.mode)
+    }
+
+    fn parse(
+        &self,
+        program: &mut Program,
+        file: Rc<str>,
+        source: &str,
+        dir: Option<&Path>,
+        depth: usize,
+    ) -> Result<(), AsmError> {
+        for (index, raw) in source.lines().enumerate() {
+            let loc = Loc {
+                file: file.clone(),
+                line: index + 1,
+            };
+            let mut line = strip_comment(raw).trim();
+
+            if let Some((label, rest)) = line.split_once(':')
+                && is_identifier(label.trim())
+            {
+                program.define(label.trim(), program.addr as i64, &loc)?;
+                line = rest.trim();
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let (head, rest) = split_word(line);
+            let (second, value) = split_word(rest);
+            if second.eq_ignore_ascii_case("equ") {
+                if !is_identifier(head) {
+                    return Err(loc.error(format!("invalid constant name `{}`", head)));
+                }
+                let value = eval(value, &program.symbols).map_err(|msg| loc.error(msg))?;
+                program.define(head, value, &loc)?;
+                continue;
+            }
+
+            let mnemonic = head.to_ascii_uppercase();
+            let operands: Vec<String> = if rest.is_empty() {
+                Vec::new()
+            } else {
+                rest.split(',').map(|op| op.trim().to_string()).collect()
+            };
+            let size = match mnemonic.as_str() {
+                "INCLUDE" => {
+                    let name = rest
+                        .strip_prefix('"')
+                        .and_then(|r| r.strip_suffix('"'))
+                        .ok_or_else(|| loc.error("include needs a quoted file name".into()))?;
+                    self.include(program, name, dir, depth, &loc)?;
+                    continue;
+                }
+                "DB" => operands.len(),
+                "DW" => operands.len() * 2,
+                _ if operands.iter().any(|op| long_operand(op).is_some()) => 4,
+                _ => 2,
+            };
+            program.items.push(Item {
+                loc: loc.clone(),
+                addr: program.addr as u16,
+                mnemonic,
+                operands,
+            });
+            program.addr += size as u32;
+            if program.addr > 0x1_0000 {
+                return Err(loc.error("program does not fit in memory".into()));
+            }
+        }
+        Ok(())
+    }
+
+    fn include(
+        &self,
+        program: &mut Program,
+        name: &str,
+        dir: Option<&Path>,
+        depth: usize,
+        loc: &Loc,
+    ) -> Result<(), AsmError> {
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(loc.error(format!("includes nested deeper than {}", MAX_INCLUDE_DEPTH)));
+        }
+        if let Some(text) = self.sources.get(name) {
+            return self.parse(program, name.into(), text, dir, depth + 1);
+        }
+        let path: PathBuf = dir.map_or_else(|| PathBuf::from(name), |dir| dir.join(name));
+        let text = fs::read_to_string(&path)
+            .map_err(|err| loc.error(format!("cannot include `{}`: {}", name, err)))?;
+        self.parse(
+            program,
+            path.display().to_string().into(),
+            &text,
+            path.parent(),
+            depth + 1,
+        )
+    }
+}
+
+#[derive(Clone)]
+struct Loc {
+    file: Rc<str>,
+    line: usize,
+}
+
+impl Loc {
+    fn error(&self, message: String) -> AsmError {
+        AsmError {
+            file: self.file.to_string(),
+            line: self.line,
+            message,
+        }
+    }
+}
+
+struct Item {
+    loc: Loc,
+    addr: u16,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+struct Program {
+    items: Vec<Item>,
+    symbols: HashMap<String, i64>,
+    addr: u32,
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Program {
+            items: Vec::new(),
+            symbols: HashMap::new(),
+            addr: START_ADDR as u32,
+        }
+    }
+}
+
+impl Program {
+    fn define(&mut self, name: &str, value: i64, loc: &Loc) -> Result<(), AsmError> {
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(loc.error(format!("`{}` is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn encode(&self, mode: Mode) -> Result<Vec<u8>, AsmError> {
+        let mut out = Vec::new();
+        for item in &self.items {
+            debug_assert_eq!(item.addr as usize, START_ADDR as usize + out.len());
+            self.encode_item(item, mode, &mut out)
+                .map_err(|msg| item.loc.error(msg))?;
+        }
+        Ok(out)
+    }
+
+    fn encode_item(&self, item: &Item, mode: Mode, out: &mut Vec<u8>) -> Result<(), String> {
+        match item.mnemonic.as_str() {
+            "DB" => {
+                for op in &item.operands {
+                    out.push(byte(eval(op, &self.symbols)?)?);
+                }
+            }
+            "DW" => {
+                for op in &item.operands {
+                    out.extend_from_slice(&word(eval(op, &self.symbols)?)?.to_be_bytes());
+                }
+            }
+            mnemonic => {
+                let operands = item
+                    .operands
+                    .iter()
+                    .map(|op| Operand::parse(op, &self.symbols))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let ins = instruction(mnemonic, &operands)?;
+                check_mode(ins, mode)?;
+                out.extend_from_slice(&ins.to_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Reg(u8),
+    RegRange(u8, u8),
+    I,
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    Hf,
+    R,
+    Long(i64),
+    Value(i64),
+}
+
+impl Operand {
+    fn parse(text: &str, symbols: &HashMap<String, i64>) -> Result<Operand, String> {
+        let upper = text.to_ascii_uppercase();
+        let op = match upper.as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::IndirectI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "B" => Operand::B,
+            "HF" => Operand::Hf,
+            "R" => Operand::R,
+            _ => {
+                if let Some(x) = register(&upper) {
+                    Operand::Reg(x)
+                } else if let Some((x, y)) = upper
+                    .split_once('-')
+                    .and_then(|(x, y)| Some((register(x.trim())?, register(y.trim())?)))
+                {
+                    Operand::RegRange(x, y)
+                } else if let Some(expr) = long_operand(text) {
+                    Operand::Long(eval(expr, symbols)?)
+                } else {
+                    Operand::Value(eval(text, symbols)?)
+                }
+            }
+        };
+        Ok(op)
+    }
+}
+
+fn instruction(mnemonic: &str, operands: &[Operand]) -> Result<Instruction, String> {
+    use Instruction as Ins;
+    use Operand::*;
+
+    let ins = match (mnemonic, operands) {
+        ("NOP", []) => Ins::Nop,
+        ("CLS", []) => Ins::Cls,
+        ("RET", []) => Ins::Ret,
+        ("SYS", &[Value(a)]) => Ins::Sys(addr(a)?),
+        ("JP", &[Value(a)]) => Ins::Jp(addr(a)?),
+        ("JP", &[Reg(0), Value(a)]) => Ins::JpV0(addr(a)?),
+        ("CALL", &[Value(a)]) => Ins::Call(addr(a)?),
+        ("SE", &[Reg(x), Value(kk)]) => Ins::Se { x, kk: byte(kk)? },
+        ("SE", &[Reg(x), Reg(y)]) => Ins::SeReg { x, y },
+        ("SNE", &[Reg(x), Value(kk)]) => Ins::Sne { x, kk: byte(kk)? },
+        ("SNE", &[Reg(x), Reg(y)]) => Ins::SneReg { x, y },
+        ("LD", &[Reg(x), Value(kk)]) => Ins::Ld { x, kk: byte(kk)? },
+        ("LD", &[Reg(x), Reg(y)]) => Ins::LdReg { x, y },
+        ("LD", &[I, Value(a)]) => Ins::LdI(addr(a)?),
+        ("LD", &[I, Long(a)]) => Ins::LdILong(word(a)?),
+        ("LD", &[Reg(x), Dt]) => Ins::LdVxDt { x },
+        ("LD", &[Reg(x), K]) => Ins::LdVxK { x },
+        ("LD", &[Dt, Reg(x)]) => Ins::LdDtVx { x },
+        ("LD", &[St, Reg(x)]) => Ins::LdStVx { x },
+        ("LD", &[F, Reg(x)]) => Ins::LdF { x },
+        ("LD", &[B, Reg(x)]) => Ins::LdB { x },
+        ("LD", &[IndirectI, Reg(x)]) => Ins::LdIVx { x },
+        ("LD", &[Reg(x), IndirectI]) => Ins::LdVxI { x },
+        ("LD", &[Hf, Reg(x)]) => Ins::LdHf { x },
+        ("LD", &[R, Reg(x)]) => Ins::LdRVx { x },
+        ("LD", &[Reg(x), R]) => Ins::LdVxR { x },
+        ("LD", &[IndirectI, RegRange(x, y)]) => Ins::LdIVxVy { x, y },
+        ("LD", &[RegRange(x, y), IndirectI]) => Ins::LdVxVyI { x, y },
+        ("ADD", &[Reg(x), Value(kk)]) => Ins::Add { x, kk: byte(kk)? },
+        ("ADD", &[Reg(x), Reg(y)]) => Ins::AddReg { x, y },
+        ("ADD", &[I, Reg(x)]) => Ins::AddI { x },
+        ("OR", &[Reg(x), Reg(y)]) => Ins::Or { x, y },
+        ("AND", &[Reg(x), Reg(y)]) => Ins::And { x, y },
+        ("XOR", &[Reg(x), Reg(y)]) => Ins::Xor { x, y },
+        ("SUB", &[Reg(x), Reg(y)]) => Ins::Sub { x, y },
+        ("SUBN", &[Reg(x), Reg(y)]) => Ins::Subn { x, y },
+        ("SHR", &[Reg(x)]) => Ins::Shr { x, y: x },
+        ("SHR", &[Reg(x), Reg(y)]) => Ins::Shr { x, y },
+        ("SHL", &[Reg(x)]) => Ins::Shl { x, y: x },
+        ("SHL", &[Reg(x), Reg(y)]) => Ins::Shl { x, y },
+        ("RND", &[Reg(x), Value(kk)]) => Ins::Rnd { x, kk: byte(kk)? },
+        ("DRW", &[Reg(x), Reg(y), Value(n)]) => Ins::Drw {
+            x,
+            y,
+            n: nibble(n)?,
+        },
+        ("SKP", &[Reg(x)]) => Ins::Skp { x },
+        ("SKNP", &[Reg(x)]) => Ins::Sknp { x },
+        ("SCD", &[Value(n)]) => Ins::Scd(nibble(n)?),
+        ("SCR", []) => Ins::Scr,
+        ("SCL", []) => Ins::Scl,
+        ("EXIT", []) => Ins::Exit,
+        ("LOW", []) => Ins::Low,
+        ("HIGH", []) => Ins::High,
+        ("SCU", &[Value(n)]) => Ins::Scu(nibble(n)?),
+        ("PLANE", &[Value(n)]) => Ins::Plane(nibble(n)?),
+        ("AUDIO", []) => Ins::Audio,
+        ("PITCH", &[Reg(x)]) => Ins::Pitch { x },
+        _ if MNEMONICS.contains(&mnemonic) => {
+            return Err(format!("invalid operands for `{}`", mnemonic));
+        }
+        _ => return Err(format!("unknown instruction `{}`", mnemonic)),
+    };
+    Ok(ins)
+}
+
+/// Rejects instructions the target platform does not have.
+fn check_mode(ins: Instruction, mode: Mode) -> Result<(), String> {
+    let available = |mode| match ins {
+        Instruction::LdILong(_) => mode == Mode::XoChip,
+        _ => Instruction::decode(ins.encode(), mode) == Some(ins),
+    };
+    if available(mode) {
+        return Ok(());
+    }
+    let platform = if available(Mode::SuperChip) {
+        "SUPER-CHIP"
+    } else {
+        "XO-CHIP"
+    };
+    Err(format!("`{}` needs {}", ins, platform))
+}
+
+/// Evaluates a sum of numbers and symbols such as `sprite + 2` or `-1`.
+fn eval(expr: &str, symbols: &HashMap<String, i64>) -> Result<i64, String> {
+    let expr = expr.trim();
+    if expr.is_empty() {
+        return Err("missing value".into());
+    }
+    let mut total = 0i64;
+    let mut sign = 1;
+    let mut rest = expr;
+    loop {
+        rest = rest.trim_start();
+        if let Some(r) = rest.strip_prefix('-') {
+            sign = -sign;
+            rest = r;
+            continue;
+        }
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        let term = rest[..end].trim();
+        let value = number(term)
+            .or_else(|| symbols.get(term).copied())
+            .ok_or_else(|| {
+                if is_identifier(term) {
+                    format!("undefined symbol `{}`", term)
+                } else {
+                    format!("invalid value `{}`", term)
+                }
+            })?;
+        total = value
+            .checked_mul(sign)
+            .and_then(|value| total.checked_add(value))
+            .ok_or_else(|| format!("value `{}` out of range", expr))?;
+        rest = &rest[end..];
+        match rest.chars().next() {
+            None => return Ok(total),
+            Some('+') => sign = 1,
+            Some(_) => sign = -1,
+        }
+        rest = &rest[1..];
+    }
+}
+
+fn number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    let (digits, radix) = if let Some(hex) = lower
+        .strip_prefix("0x")
+        .or_else(|| lower.strip_prefix('#'))
+        .or_else(|| lower.strip_prefix('$'))
+    {
+        (hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b").or_else(|| lower.strip_prefix('%')) {
+        (bin, 2)
+    } else {
+        (lower.as_str(), 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix) || c == '_') {
+        return None;
+    }
+    i64::from_str_radix(&digits.replace('_', ""), radix).ok()
+}
+
+fn register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('V')?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// The address expression of a `long addr` operand.
+fn long_operand(text: &str) -> Option<&str> {
+    let (word, rest) = split_word(text);
+    word.eq_ignore_ascii_case("long").then_some(rest)
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Cuts `text` at a `;` that is not inside double quotes.
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (at, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..at],
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Splits off the first whitespace-separated word.
+fn split_word(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+fn addr(value: i64) -> Result<u16, String> {
+    if !(0..=0xFFF).contains(&value) {
+        return Err(format!("address {:#X} does not fit in 12 bits", value));
+    }
+    Ok(value as u16)
+}
+
+fn word(value: i64) -> Result<u16, String> {
+    if !(-0x8000..=0xFFFF).contains(&value) {
+        return Err(format!("{} does not fit in 16 bits", value));
+    }
+    Ok(value as u16)
+}
+
+fn byte(value: i64) -> Result<u8, String> {
+    if !(-0x80..=0xFF).contains(&value) {
+        return Err(format!("{} does not fit in a byte", value));
+    }
+    Ok(value as u8)
+}
+
+fn nibble(value: i64) -> Result<u8, String> {
+    if !(0..=0xF).contains(&value) {
+        return Err(format!("{} does not fit in a nibble", value));
+    }
+    Ok(value as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Emu;
+
+    fn error(source: &str, mode: Mode) -> (usize, String) {
+        let err = assemble(source, mode).unwrap_err();
+        (err.line, err.message)
+    }
+
+    #[test]
+    fn test_basic_instructions() {
+        let rom = assemble(
+            "CLS\nLD V1, 0x2A\nLD I, 0x300\nDRW V1, V2, 5\nSHR V3\nLD [I], VF\nRET",
+            Mode::Chip8,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rom,
+            [
+                0x00, 0xE0, 0x61, 0x2A, 0xA3, 0x00, 0xD1, 0x25, 0x83, 0x36, 0xFF, 0x55, 0x00, 0xEE
+            ]
+        );
+    }
+
+    #[test]
+    fn test_every_instruction_round_trips_through_display() {
+        for op in 0..=u16::MAX {
+            let Some(ins) = Instruction::decode(op, Mode::XoChip) else {
+                continue;
+            };
+            let rom = assemble(&ins.to_string(), Mode::XoChip).unwrap();
+
+            assert_eq!(rom, ins.to_bytes(), "{}", ins);
+        }
+        assert_eq!(
+            assemble("LD I, long 0xBEEF", Mode::XoChip).unwrap(),
+            [0xF0, 0x00, 0xBE, 0xEF]
+        );
+    }
+
+    #[test]
+    fn test_labels_and_constants() {
+        let source = "\
+            COUNT equ 3 ; loop count
+            start:
+                LD V0, COUNT
+            loop: ADD V0, -1
+                SE V0, 0
+                JP loop
+                LD I, data + 1
+                JP start
+            data:
+                db 1, 0b10, %11, $F0, #0F
+                dw 0x1234, loop";
+        let rom = assemble(source, Mode::Chip8).unwrap();
+
+        assert_eq!(
+            rom,
+            [
+                0x60, 0x03, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0xA2, 0x0D, 0x12, 0x00, //
+                0x01, 0x02, 0x03, 0xF0, 0x0F, 0x12, 0x34, 0x02, 0x02,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward_references() {
+        let rom = assemble("CALL sub\nJP 0x200\nsub: RET", Mode::Chip8).unwrap();
+
+        assert_eq!(rom, [0x22, 0x04, 0x12, 0x00, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn test_include_from_registered_source() {
+        let rom = Assembler::new(Mode::Chip8)
+            .with_source("lib.asm", "helper: RET\nSIZE equ 5")
+            .assemble("CALL helper\nLD V0, SIZE\ninclude \"lib.asm\"")
+            .unwrap();
+
+        assert_eq!(rom, [0x22, 0x04, 0x60, 0x05, 0x00, 0xEE]);
+    }
+
+    #[test]
+    fn test_include_name_with_semicolon() {
+        let rom = Assembler::new(Mode::Chip8)
+            .with_source("a;b.asm", "db 0xFF ; not part of the name")
+            .assemble("include \"a;b.asm\" ; comment")
+            .unwrap();
+
+        assert_eq!(rom, [0xFF]);
+    }
+
+    #[test]
+    fn test_include_from_disk() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("main.asm"), "include \"sprite.asm\"\n").unwrap();
+        fs::write(dir.join("sprite.asm"), "db 0xFF\nLD V0, nowhere\n").unwrap();
+
+        let err = Assembler::new(Mode::Chip8)
+            .assemble_file(&dir.join("main.asm"))
+            .unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.file.ends_with("sprite.asm"), "{}", err.file);
+        assert_eq!(err.line, 2);
+        assert_eq!(err.message, "undefined symbol `nowhere`");
+    }
+
+    #[test]
+    fn test_recursive_include_is_reported() {
+        let err = Assembler::new(Mode::Chip8)
+            .with_source("a.asm", "include \"a.asm\"")
+            .assemble("include \"a.asm\"")
+            .unwrap_err();
+
+        assert_eq!(err.file, "a.asm");
+        assert!(err.message.contains("nested"));
+    }
+
+    #[test]
+    fn test_line_accurate_errors() {
+        assert_eq!(
+            error("CLS\n\nFOO V1", Mode::Chip8),
+            (3, "unknown instruction `FOO`".into())
+        );
+        assert_eq!(
+            error("LD V1, 256", Mode::Chip8),
+            (1, "256 does not fit in a byte".into())
+        );
+        assert_eq!(
+            error("CLS\nLD V1", Mode::Chip8),
+            (2, "invalid operands for `LD`".into())
+        );
+        assert_eq!(
+            error("a: CLS\na: CLS", Mode::Chip8),
+            (2, "`a` is already defined".into())
+        );
+        assert_eq!(
+            error("JP 0x1000", Mode::Chip8),
+            (1, "address 0x1000 does not fit in 12 bits".into())
+        );
+        assert_eq!(
+            error("DRW V0, V1, 16", Mode::Chip8),
+            (1, "16 does not fit in a nibble".into())
+        );
+        assert_eq!(
+            error("LD V0, 1x", Mode::Chip8),
+            (1, "invalid value `1x`".into())
+        );
+        assert_eq!(
+            error("db 0x7FFFFFFFFFFFFFFF + 1", Mode::Chip8),
+            (1, "value `0x7FFFFFFFFFFFFFFF + 1` out of range".into())
+        );
+        assert_eq!(
+            error("db -0x7FFFFFFFFFFFFFFF - 2", Mode::Chip8),
+            (1, "value `-0x7FFFFFFFFFFFFFFF - 2` out of range".into())
+        );
+        assert_eq!(
+            assemble("CLS", Mode::Chip8).map_err(|e| e.to_string()),
+            Ok(vec![0x00, 0xE0])
+        );
+        assert_eq!(
+            assemble("JP", Mode::Chip8).unwrap_err().to_string(),
+            "<input>:1: invalid operands for `JP`"
+        );
+    }
+
+    #[test]
+    fn test_mode_specific_instructions() {
+        assert_eq!(
+            error("HIGH", Mode::Chip8),
+            (1, "`HIGH` needs SUPER-CHIP".into())
+        );
+        assert_eq!(
+            error("SCU 2", Mode::SuperChip),
+            (1, "`SCU 2` needs XO-CHIP".into())
+        );
+        assert_eq!(
+            error("LD R, V9", Mode::SuperChip),
+            (1, "`LD R, V9` needs XO-CHIP".into())
+        );
+        assert!(assemble("HIGH\nSCD 4", Mode::SuperChip).is_ok());
+        assert!(assemble("PLANE 3\nLD V1 - V4, [I]", Mode::XoChip).is_ok());
+    }
+
+    #[test]
+    fn test_assembled_program_runs() {
+        let rom = assemble(
+            "   LD V0, 0
+                LD V1, 10
+            loop:
+                ADD V0, 3
+                ADD V1, -1
+                SE V1, 0
+                JP loop
+            done:
+                JP done",
+            Mode::Chip8,
+        )
+        .unwrap();
+        let mut emu = Emu::new();
+        emu.load_rom(&rom).unwrap();
+
+        for _ in 0..100 {
+            emu.step().unwrap();
+        }
+
+        assert_eq!(emu.v_reg[0], 30);
+    }
+}
@@ -0,0 +1,319 @@
+//! Beeper synthesis: the sound timer as PCM samples.
+//!
+//! A `Beeper` is pulled once per 60 Hz frame for that frame's samples,
+//! taking whether the machine is beeping from `Emu::is_beeping`. The tone is
+//! a square wave with PolyBLEP corrections at its edges, which removes most
+//! of the aliasing a naive square wave has at audio sample rates. Starts and
+//! stops fade over a short ramp so they do not click.
+//!
+//! XO-CHIP audio patterns are not played; the beeper always sounds its tone.
+//!
+//! `WavWriter` stores samples as 16-bit mono PCM, e.g. to record a session
+//! without a sound card.
+
+use std::io::{self, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+use crate::{Emu, TIMER_HZ};
+
+const DEFAULT_TONE: f32 = 440.0;
+const DEFAULT_VOLUME: f32 = 0.25;
+const DEFAULT_RAMP: Duration = Duration::from_millis(5);
+
+/// Square-wave tone generator driven by the sound timer.
+///
+/// ```
+/// use chip8_core::{Beeper, Emu};
+///
+/// let mut emu = Emu::new();
+/// let mut beeper = Beeper::new(48_000).tone(880.0);
+/// let mut samples = Vec::new();
+/// emu.run_frame()?;
+/// beeper.frame(&emu, &mut samples);
+/// assert_eq!(samples.len(), 800);
+/// # Ok::<(), chip8_core::EmuError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Beeper {
+    sample_rate: u32,
+    tone: f32,
+    volume: f32,
+    ramp: Duration,
+    /// Position within the current period, from 0 to 1.
+    phase: f32,
+    /// Current level of the start/stop ramp, from 0 to 1.
+    gain: f32,
+    /// Sample periods owed to the next frame, in 1/`TIMER_HZ` of a sample.
+    frame_acc: u32,
+}
+
+impl Beeper {
+    /// A 440 Hz beeper at a quarter of full scale with 5 ms ramps.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1),
+            tone: DEFAULT_TONE,
+            volume: DEFAULT_VOLUME,
+            ramp: DEFAULT_RAMP,
+            phase: 0.0,
+            gain: 0.0,
+            frame_acc: 0,
+        }
+    }
+
+    /// Sets the pitch in Hz, kept below the Nyquist frequency (but at least
+    /// 1 Hz, even at sample rates too low for that).
+    pub fn tone(mut self, hz: f32) -> Self {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        self.tone = hz.clamp(1.0, nyquist.max(1.0));
+        self
+    }
+
+    /// Sets the peak amplitude, from 0 (silent) to 1 (full scale).
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = volume.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets how long starting and stopping fade in and out.
+    pub fn ramp(mut self, ramp: Duration) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Appends one frame of samples for the current state of `emu`. Frames
+    /// alternate between slightly different lengths when the sample rate is
+    /// not a multiple of `TIMER_HZ`, so that every second has exactly
+    /// `sample_rate` samples.
+    pub fn frame(&mut self, emu: &Emu, out: &mut Vec<f32>) {
+        self.frame_acc += self.sample_rate;
+        let count = (self.frame_acc / TIMER_HZ) as usize;
+        self.frame_acc %= TIMER_HZ;
+
+        let start = out.len();
+        out.resize(start + count, 0.0);
+        self.fill(emu.is_beeping(), &mut out[start..]);
+    }
+
+    /// Fills `out` with samples, sounding the tone if `beeping`.
+    pub fn fill(&mut self, beeping: bool, out: &mut [f32]) {
+        let ramp_samples = self.ramp.as_secs_f32() * self.sample_rate as f32;
+        let step = if ramp_samples >= 1.0 {
+            1.0 / ramp_samples
+        } else {
+            1.0
+        };
+        let dt = self.tone / self.sample_rate as f32;
+
+        for sample in out {
+            self.gain = if beeping {
+                (self.gain + step).min(1.0)
+            } else {
+                (self.gain - step).max(0.0)
+            };
+            if self.gain == 0.0 {
+                // Each beep starts at the same point of the wave.
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+            *sample = square(self.phase, dt) * self.gain * self.volume;
+            self.phase = (self.phase + dt).fract();
+        }
+    }
+}
+
+/// A band-limited square wave at `phase`, advancing `dt` per sample.
+fn square(phase: f32, dt: f32) -> f32 {
+    let naive = if phase < 0.5 { 1.0 } else { -1.0 };
+    naive + poly_blep(phase, dt) - poly_blep((phase + 0.5).fract(), dt)
+}
+
+/// The PolyBLEP residual smoothing a unit step at phase 0.
+fn poly_blep(phase: f32, dt: f32) -> f32 {
+    if phase < dt {
+        let t = phase / dt;
+        2.0 * t - t * t - 1.0
+    } else if phase > 1.0 - dt {
+        let t = (phase - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Writes samples to a 16-bit mono PCM WAV file.
+///
+/// The header is written first with empty sizes, which `finish` fills in.
+#[derive(Debug)]
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32) -> io::Result<Self> {
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, one channel.
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&sample_rate.to_le_bytes());
+        header.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        // Two bytes per frame, 16 bits per sample.
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(Self { out, samples: 0 })
+    }
+
+    /// Appends samples, clipping them to -1..=1.
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|&sample| {
+                let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                pcm.to_le_bytes()
+            })
+            .collect();
+        self.out.write_all(&bytes)?;
+        self.samples += samples.len() as u32;
+        Ok(())
+    }
+
+    /// Number of samples written so far.
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Fills in the sizes in the header and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let data = self.samples * 2;
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out.write_all(&(36 + data).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(40))?;
+        self.out.write_all(&data.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_frames_add_up_to_sample_rate() {
+        let emu = Emu::new();
+        let mut beeper = Beeper::new(44_100);
+        let mut samples = Vec::new();
+        beeper.frame(&emu, &mut samples);
+        assert_eq!(samples.len(), 735);
+
+        let mut beeper = Beeper::new(22_050 + 1);
+        let mut samples = Vec::new();
+        for _ in 0..TIMER_HZ {
+            beeper.frame(&emu, &mut samples);
+        }
+        assert_eq!(samples.len(), 22_051);
+        assert!(samples.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_tone() {
+        let mut beeper = Beeper::new(48_000).tone(1_000.0).volume(0.5);
+        let mut samples = vec![0.0; 4_800];
+        beeper.fill(true, &mut samples);
+        let steady = &samples[480..];
+
+        // Band-limited edges never overshoot, and the wave crosses zero
+        // twice in each of its 90 periods.
+        assert!(steady.iter().all(|sample| sample.abs() <= 0.5));
+        let crossings = steady
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+            .count();
+        assert_eq!(crossings, 2 * 90);
+        assert!(steady.iter().sum::<f32>().abs() < 1.0);
+        // An edge falls between two samples rather than jumping in one.
+        assert!(steady.iter().any(|sample| sample.abs() < 0.45));
+    }
+
+    #[test]
+    fn test_tone_at_tiny_sample_rates() {
+        for rate in [0, 1, 2, 3] {
+            let mut beeper = Beeper::new(rate).tone(440.0);
+            let mut samples = vec![0.0; 8];
+            beeper.fill(true, &mut samples);
+
+            assert_eq!(beeper.tone, beeper.sample_rate().max(2) as f32 / 2.0);
+            assert!(samples.iter().all(|sample| sample.is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_ramps_avoid_clicks() {
+        // A 5 ms ramp at 8 kHz takes 40 samples.
+        let mut beeper = Beeper::new(8_000).tone(100.0).volume(1.0);
+        let mut start = vec![0.0; 100];
+        beeper.fill(true, &mut start);
+        assert!(
+            start[..40]
+                .iter()
+                .enumerate()
+                .all(|(i, sample)| sample.abs() <= (i + 1) as f32 / 40.0 + 1e-6)
+        );
+        assert_eq!(start[60], -1.0);
+
+        let mut stop = vec![0.0; 80];
+        beeper.fill(false, &mut stop);
+        assert!(stop[0].abs() > 0.9);
+        assert!(stop[41..].iter().all(|&sample| sample == 0.0));
+        assert_eq!(beeper.phase, 0.0);
+    }
+
+    #[test]
+    fn test_session_to_wav() {
+        let mut emu = Emu::new();
+        // LD V0, 30 / LD ST, V0 / JP 204: beep for half a second.
+        emu.load_rom(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        let mut beeper = Beeper::new(8_000);
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), beeper.sample_rate()).unwrap();
+        let mut samples = Vec::new();
+        for _ in 0..TIMER_HZ {
+            emu.run_frame().unwrap();
+            samples.clear();
+            beeper.frame(&emu, &mut samples);
+            wav.write_samples(&samples).unwrap();
+        }
+        assert_eq!(wav.samples(), 8_000);
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 16_000);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(bytes[4..8], (36 + 16_000u32).to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(bytes[24..28], 8_000u32.to_le_bytes());
+        assert_eq!(bytes[40..44], 16_000u32.to_le_bytes());
+
+        let pcm: Vec<i16> = bytes[44..]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let loud = |range: std::ops::Range<usize>| pcm[range].iter().any(|&s| s.abs() > 8_000);
+        assert!(loud(0..3_900));
+        assert!(!loud(4_100..8_000));
+        assert!(pcm[4_100..].iter().all(|&s| s == 0));
+    }
+}
@@ -0,0 +1,801 @@
+//! Image capture: PNG screenshots and animated GIF recordings.
+//!
+//! Both encoders are self-contained. PNG data is compressed with a small
+//! DEFLATE encoder (fixed Huffman codes and LZ77 matches), which suits the
+//! large flat areas of a CHIP-8 display well. GIF frames are LZW-compressed
+//! with a local colour table each, so anything a `Renderer` produces can be
+//! recorded, including phosphor fades.
+//!
+//! `GifRecorder` takes one frame per 60 Hz tick. Identical frames are merged
+//! into one longer frame, and GIF delays are in hundredths of a second, so
+//! frame times are rounded to keep the clip in sync over its length.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::state::crc32;
+use crate::{Emu, Image, Renderer, Rgba, TIMER_HZ};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Viewers stretch GIF delays below 2/100 s, so shorter frames are dropped.
+const MIN_DELAY: u32 = 2;
+const MAX_LZW_CODES: u16 = 4096;
+
+impl Image {
+    /// Encodes the image as an 8-bit RGBA PNG. PNG has no empty images, so
+    /// one without pixels, or whose data does not match its size, is
+    /// `InvalidInput`.
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        let stride = self.width * 4;
+        if self.width == 0 || self.height == 0 || self.data.len() != stride * self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot encode a {}x{} image", self.width, self.height),
+            ));
+        }
+        let mut raw = Vec::with_capacity((stride + 1) * self.height);
+        for row in self.data.chunks_exact(stride) {
+            // Filter type 0 (None) on every scanline.
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth 8, colour type 6 (RGBA), default compression, filter and interlace.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+        Ok(png)
+    }
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Records frames into an animated GIF that loops forever.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use chip8_core::{Emu, GifRecorder, Renderer};
+///
+/// let mut emu = Emu::new();
+/// emu.load_rom_file("roms/PONG2")?;
+/// let mut recorder = GifRecorder::new(File::create("pong.gif")?, Renderer::default().scale(4));
+/// for _ in 0..600 {
+///     emu.run_frame()?;
+///     recorder.capture(&emu)?;
+/// }
+/// recorder.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct GifRecorder<W: Write> {
+    out: W,
+    renderer: Renderer,
+    /// Size of the animation, set by the first frame.
+    size: Option<(usize, usize)>,
+    /// The latest distinct frame, written once its length is known.
+    pending: Option<Image>,
+    /// Ticks captured so far.
+    ticks: u32,
+    /// Total delay of the frames written so far, in hundredths of a second.
+    written: u32,
+    frames: usize,
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Records to `out`, rendering machines with `renderer`.
+    pub fn new(out: W, renderer: Renderer) -> Self {
+        Self {
+            out,
+            renderer,
+            size: None,
+            pending: None,
+            ticks: 0,
+            written: 0,
+            frames: 0,
+        }
+    }
+
+    /// Captures the display of `emu` as the next frame.
+    pub fn capture(&mut self, emu: &Emu) -> io::Result<()> {
+        let image = self.renderer.render(emu);
+        self.capture_image(image)
+    }
+
+    /// Captures `image` as the next frame. The first frame sets the size of
+    /// the animation; later frames of another size, e.g. after a switch to
+    /// SUPER-CHIP hires, are scaled to fit.
+    pub fn capture_image(&mut self, image: Image) -> io::Result<()> {
+        let image = match self.size {
+            None => {
+                self.write_header(image.width, image.height)?;
+                self.size = Some((image.width, image.height));
+                image
+            }
+            Some((width, height)) if (image.width, image.height) != (width, height) => {
+                resize(&image, width, height)
+            }
+            Some(_) => image,
+        };
+
+        let now = self.ticks;
+        self.ticks += 1;
+        match self.pending.take() {
+            Some(pending) if pending == image => self.pending = Some(pending),
+            Some(pending) if centis(now) - self.written >= MIN_DELAY => {
+                self.write_frame(&pending, now)?;
+                self.pending = Some(image);
+            }
+            _ => self.pending = Some(image),
+        }
+        Ok(())
+    }
+
+    /// Number of frames in the file so far. Frames still being timed and
+    /// duplicates do not count.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Writes the last frame and the trailer, returning the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let Some(pending) = self.pending.take() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no frames were captured",
+            ));
+        };
+        self.write_frame(&pending, self.ticks)?;
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_header(&mut self, width: usize, height: usize) -> io::Result<()> {
+        let mut header = b"GIF89a".to_vec();
+        header.extend_from_slice(&gif_size(width)?.to_le_bytes());
+        header.extend_from_slice(&gif_size(height)?.to_le_bytes());
+        // No global colour table, background colour 0, square pixels.
+        header.extend_from_slice(&[0x00, 0x00, 0x00]);
+        // NETSCAPE2.0 application extension: loop forever.
+        header.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        header.extend_from_slice(b"NETSCAPE2.0");
+        header.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+        self.out.write_all(&header)
+    }
+
+    /// Writes `image` as a frame lasting until tick `end`.
+    fn write_frame(&mut self, image: &Image, end: u32) -> io::Result<()> {
+        let delay = centis(end) - self.written;
+        self.written += delay;
+        self.frames += 1;
+
+        let (table, indices) = quantize(image);
+        let bits = (usize::BITS - (table.len() - 1).leading_zeros()).max(1);
+        let mut frame = Vec::new();
+        // Graphic control extension: leave the frame in place, then wait.
+        frame.extend_from_slice(&[0x21, 0xF9, 0x04, 0x04]);
+        frame.extend_from_slice(&(delay.min(u16::MAX as u32) as u16).to_le_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        // Image descriptor covering the whole canvas, with a local colour table.
+        frame.push(0x2C);
+        frame.extend_from_slice(&[0, 0, 0, 0]);
+        frame.extend_from_slice(&gif_size(image.width)?.to_le_bytes());
+        frame.extend_from_slice(&gif_size(image.height)?.to_le_bytes());
+        frame.push(0x80 | (bits - 1) as u8);
+        for i in 0..1 << bits {
+            let [r, g, b, _] = table.get(i).copied().unwrap_or_default();
+            frame.extend_from_slice(&[r, g, b]);
+        }
+
+        let min_code_size = bits.max(2) as u8;
+        frame.push(min_code_size);
+        for block in lzw(&indices, min_code_size).chunks(255) {
+            frame.push(block.len() as u8);
+            frame.extend_from_slice(block);
+        }
+        frame.push(0);
+        self.out.write_all(&frame)
+    }
+}
+
+/// A width or height as GIF stores it, in 16 bits.
+fn gif_size(pixels: usize) -> io::Result<u16> {
+    u16::try_from(pixels).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} pixels is too large for a GIF", pixels),
+        )
+    })
+}
+
+/// Time at the start of tick `ticks` in hundredths of a second, rounded.
+fn centis(ticks: u32) -> u32 {
+    (ticks * 100 + TIMER_HZ / 2) / TIMER_HZ
+}
+
+/// Nearest-neighbour scaling of `image` to `width` x `height`.
+fn resize(image: &Image, width: usize, height: usize) -> Image {
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = image.pixel(x * image.width / width, y * image.height / height);
+            data.extend_from_slice(&pixel);
+        }
+    }
+    Image {
+        width,
+        height,
+        data,
+    }
+}
+
+/// The colours of `image` in order of appearance and each pixel's index
+/// among them. Past 256 colours, pixels take the closest one already seen.
+fn quantize(image: &Image) -> (Vec<Rgba>, Vec<u8>) {
+    let mut table: Vec<Rgba> = Vec::new();
+    let mut index: HashMap<Rgba, u8> = HashMap::new();
+    let indices = image
+        .data
+        .chunks_exact(4)
+        .map(|rgba| {
+            let color = [rgba[0], rgba[1], rgba[2], rgba[3]];
+            if let Some(&i) = index.get(&color) {
+                return i;
+            }
+            let i = if table.len() < 256 {
+                table.push(color);
+                (table.len() - 1) as u8
+            } else {
+                closest(&table, color)
+            };
+            index.insert(color, i);
+            i
+        })
+        .collect();
+    if table.is_empty() {
+        table.push([0, 0, 0, 0xFF]);
+    }
+    (table, indices)
+}
+
+fn closest(table: &[Rgba], color: Rgba) -> u8 {
+    let distance = |other: &Rgba| -> u32 {
+        (0..3)
+            .map(|i| (color[i] as i32 - other[i] as i32).pow(2) as u32)
+            .sum()
+    };
+    (0..table.len())
+        .min_by_key(|&i| distance(&table[i]))
+        .unwrap_or(0) as u8
+}
+
+/// Packs codes least significant bit first, as both DEFLATE and GIF do.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.acc |= value << self.len;
+        self.len += bits;
+        while self.len >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which DEFLATE stores most significant bit first.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF's variable-width LZW for colour indices below `1 << min_code_size`.
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut bits = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = min_code_size as u32 + 1;
+
+    bits.write(clear as u32, size);
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let Some(code) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&longer) = table.get(&(code, index)) {
+            prefix = Some(longer);
+            continue;
+        }
+        bits.write(code as u32, size);
+        table.insert((code, index), next);
+        next += 1;
+        // The decoder adds each entry one code later, so widen once it has
+        // room for the code just added.
+        if next > 1 << size && size < 12 {
+            size += 1;
+        }
+        if next == MAX_LZW_CODES {
+            bits.write(clear as u32, size);
+            table.clear();
+            next = end + 1;
+            size = min_code_size as u32 + 1;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(code) = prefix {
+        bits.write(code as u32, size);
+        // The decoder adds an entry for this code too, which may widen the
+        // end code.
+        next += 1;
+        if next > 1 << size && size < 12 {
+            size += 1;
+        }
+    }
+    bits.write(end as u32, size);
+    bits.finish()
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash to try for a match.
+const MAX_CHAIN: usize = 64;
+const HASH_SIZE: usize = 1 << 15;
+
+/// `data` as a zlib stream.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, no preset dictionary, fastest-level hint.
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// `data` as a single final DEFLATE block with the fixed Huffman codes.
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman).
+    bits.write(0b011, 3);
+
+    let mut chains = Chains {
+        head: vec![usize::MAX; HASH_SIZE],
+        prev: vec![usize::MAX; data.len()],
+    };
+    let mut at = 0;
+    while at < data.len() {
+        let (length, distance) = chains.longest_match(data, at);
+        let length = if length >= MIN_MATCH {
+            write_length(&mut bits, length);
+            write_distance(&mut bits, distance);
+            length
+        } else {
+            write_literal(&mut bits, data[at] as u32);
+            1
+        };
+        for pos in at..at + length {
+            chains.insert(data, pos);
+        }
+        at += length;
+    }
+    write_literal(&mut bits, 256);
+    bits.finish()
+}
+
+/// Earlier positions in the data by the hash of the three bytes there.
+struct Chains {
+    /// The latest position for each hash.
+    head: Vec<usize>,
+    /// The position before each one with the same hash.
+    prev: Vec<usize>,
+}
+
+impl Chains {
+    fn hash(data: &[u8], at: usize) -> usize {
+        let key = (data[at] as usize) << 16 | (data[at + 1] as usize) << 8 | data[at + 2] as usize;
+        key.wrapping_mul(2_654_435_761) >> 17 & (HASH_SIZE - 1)
+    }
+
+    fn insert(&mut self, data: &[u8], at: usize) {
+        if at + MIN_MATCH <= data.len() {
+            let hash = Self::hash(data, at);
+            self.prev[at] = self.head[hash];
+            self.head[hash] = at;
+        }
+    }
+
+    /// The longest earlier match for the data at `at` as (length, distance).
+    fn longest_match(&self, data: &[u8], at: usize) -> (usize, usize) {
+        if at + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let limit = MAX_MATCH.min(data.len() - at);
+        let mut best = (0, 0);
+        let mut candidate = self.head[Self::hash(data, at)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || at - candidate > WINDOW {
+                break;
+            }
+            let length = (0..limit)
+                .take_while(|&i| data[candidate + i] == data[at + i])
+                .count();
+            if length > best.0 {
+                best = (length, at - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        best
+    }
+}
+
+fn write_literal(bits: &mut BitWriter, value: u32) {
+    match value {
+        0..=143 => bits.write_code(0x30 + value, 8),
+        144..=255 => bits.write_code(0x190 + value - 144, 9),
+        256..=279 => bits.write_code(value - 256, 7),
+        _ => bits.write_code(0xC0 + value - 280, 8),
+    }
+}
+
+fn write_length(bits: &mut BitWriter, length: usize) {
+    let slot = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+    write_literal(bits, 257 + slot as u32);
+    let extra = LENGTH_EXTRA[slot] as u32;
+    bits.write((length - LENGTH_BASE[slot] as usize) as u32, extra);
+}
+
+fn write_distance(bits: &mut BitWriter, distance: usize) {
+    let slot = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+    bits.write_code(slot as u32, 5);
+    let extra = DISTANCE_EXTRA[slot] as u32;
+    bits.write((distance - DISTANCE_BASE[slot] as usize) as u32, extra);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Palette;
+
+    /// Inflates a zlib stream of fixed-Huffman blocks, enough to check `zlib`.
+    fn inflate(stream: &[u8]) -> Vec<u8> {
+        let mut pos = 16; // skip the zlib header
+        let mut bit = |n: u32| -> u32 {
+            let mut value = 0;
+            for i in 0..n {
+                value |= ((stream[pos / 8] >> (pos % 8)) as u32 & 1) << i;
+                pos += 1;
+            }
+            value
+        };
+        assert_eq!(bit(3), 0b011);
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            // Read a fixed Huffman code most significant bit first.
+            let mut code = 0;
+            let mut len = 0;
+            let symbol = loop {
+                code = code << 1 | bit(1);
+                len += 1;
+                match (len, code) {
+                    (7, 0..=0x17) => break code + 256,
+                    (8, 0x30..=0xBF) => break code - 0x30,
+                    (8, 0xC0..=0xC7) => break code - 0xC0 + 280,
+                    (9, 0x190..=0x1FF) => break code - 0x190 + 144,
+                    _ => {}
+                }
+            };
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => break,
+                _ => {
+                    let slot = symbol as usize - 257;
+                    let length =
+                        LENGTH_BASE[slot] as usize + bit(LENGTH_EXTRA[slot] as u32) as usize;
+                    let slot = (0..5).fold(0, |acc, _| acc << 1 | bit(1)) as usize;
+                    let distance =
+                        DISTANCE_BASE[slot] as usize + bit(DISTANCE_EXTRA[slot] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Decodes GIF LZW data back into colour indices.
+    fn unlzw(data: &[u8], min_code_size: u8) -> Option<Vec<u8>> {
+        let clear = 1u16 << min_code_size;
+        let mut pos = 0;
+        let mut read = |size: u32| -> Option<u16> {
+            let mut value = 0;
+            for i in 0..size {
+                value |= ((data.get(pos / 8)? >> (pos % 8)) as u16 & 1) << i;
+                pos += 1;
+            }
+            Some(value)
+        };
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = 0;
+        let mut last: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            let code = read(size.max(min_code_size as u32 + 1))?;
+            if code == clear {
+                table = (0..clear).map(|i| vec![i as u8]).collect();
+                table.extend([Vec::new(), Vec::new()]);
+                size = min_code_size as u32 + 1;
+                last = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return Some(out);
+            }
+            let entry = match table.get(code as usize) {
+                Some(entry) => entry.clone(),
+                None if code as usize == table.len() => {
+                    let last = last.clone()?;
+                    [last.clone(), vec![last[0]]].concat()
+                }
+                None => return None,
+            };
+            if let Some(last) = last {
+                table.push([last, vec![entry[0]]].concat());
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+            out.extend_from_slice(&entry);
+            last = Some(entry);
+        }
+    }
+
+    fn checkerboard(width: usize, height: usize, phase: usize) -> Image {
+        let pixels: Vec<u8> = (0..width * height)
+            .map(|i| ((i % width + i / width + phase) % 2) as u8)
+            .collect();
+        Renderer::default().render_pixels(&pixels, width)
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_deflate_round_trip() {
+        let mut data = b"CHIP-8 CHIP-8 CHIP-8 ".repeat(50);
+        data.extend(
+            (0..=255)
+                .cycle()
+                .take(70_000)
+                .map(|i: u32| (i * 7 % 251) as u8),
+        );
+        data.extend([0; 1000]);
+        let stream = zlib(&data);
+
+        assert_eq!(inflate(&stream), data);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+        assert!(zlib(&[0; 10_000]).len() < 100);
+    }
+
+    #[test]
+    fn test_png_structure() {
+        let image = checkerboard(3, 2, 0);
+        let png = image.to_png().unwrap();
+
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 3, 0, 0, 0, 2]);
+        assert_eq!(crc32(&png[12..29]).to_be_bytes(), png[29..33]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let raw = inflate(&png[41..41 + idat_len]);
+        assert_eq!(raw.len(), 2 * (1 + 3 * 4));
+        assert_eq!(raw[..5], [0, 0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(raw[13..18], [0, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_png_rejects_empty_image() {
+        let empty = Image {
+            width: 0,
+            height: 4,
+            data: Vec::new(),
+        };
+        let short = Image {
+            width: 2,
+            height: 2,
+            data: vec![0; 4],
+        };
+
+        assert_eq!(
+            empty.to_png().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            short.to_png().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let indices: Vec<u8> = (0..20_000u32).map(|i| (i * i / 7 % 4) as u8).collect();
+
+        assert_eq!(unlzw(&lzw(&indices, 2), 2), Some(indices));
+        let noise: Vec<u8> = (0..9_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        assert_eq!(unlzw(&lzw(&noise, 8), 8), Some(noise));
+        assert_eq!(unlzw(&lzw(&[], 2), 2), Some(vec![]));
+    }
+
+    #[test]
+    fn test_lzw_end_code_after_table_fills_a_width() {
+        // The decoder's table reaches 8 entries on the last code, so the end
+        // code takes 4 bits.
+        assert_eq!(unlzw(&lzw(&[0, 1, 2], 2), 2), Some(vec![0, 1, 2]));
+
+        let indices: Vec<u8> = (0..600u32).map(|i| (i * 7 % 13 % 4) as u8).collect();
+        for len in 0..indices.len() {
+            assert_eq!(
+                unlzw(&lzw(&indices[..len], 2), 2).as_deref(),
+                Some(&indices[..len])
+            );
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let image = Renderer::new(Palette::XO_CHIP).render_pixels(&[2, 0, 2, 3], 4);
+        let (table, indices) = quantize(&image);
+
+        assert_eq!(
+            table,
+            [
+                Palette::XO_CHIP.0[2],
+                Palette::XO_CHIP.0[0],
+                Palette::XO_CHIP.0[3]
+            ]
+        );
+        assert_eq!(indices, [0, 1, 0, 2]);
+    }
+
+    #[test]
+    fn test_gif_deduplicates_and_times_frames() {
+        let mut recorder = GifRecorder::new(Vec::new(), Renderer::default());
+        // Ten ticks of one frame, then five of another: 17 and 8 hundredths.
+        for _ in 0..10 {
+            recorder.capture_image(checkerboard(4, 2, 0)).unwrap();
+        }
+        for _ in 0..5 {
+            recorder.capture_image(checkerboard(4, 2, 1)).unwrap();
+        }
+        assert_eq!(recorder.frames(), 1);
+        let gif = recorder.finish().unwrap();
+
+        assert_eq!(&gif[..6], b"GIF89a");
+        assert_eq!(gif[6..10], [4, 0, 2, 0]);
+        assert_eq!(gif.last(), Some(&0x3B));
+        let delays: Vec<u16> = gif
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| w[..3] == [0x21, 0xF9, 0x04])
+            .map(|(i, _)| u16::from_le_bytes([gif[i + 4], gif[i + 5]]))
+            .collect();
+        assert_eq!(delays, [17, 8]);
+    }
+
+    #[test]
+    fn test_gif_rejects_oversized_frames() {
+        let mut recorder = GifRecorder::new(Vec::new(), Renderer::default());
+        let wide = Image {
+            width: 70_000,
+            height: 1,
+            data: vec![0; 70_000 * 4],
+        };
+
+        let err = recorder.capture_image(wide).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "70000 pixels is too large for a GIF");
+    }
+
+    #[test]
+    fn test_gif_frame_contents() {
+        let mut recorder = GifRecorder::new(Vec::new(), Renderer::default());
+        recorder.capture_image(checkerboard(4, 2, 0)).unwrap();
+        let gif = recorder.finish().unwrap();
+
+        // Header and loop extension, then the control extension and descriptor.
+        let frame = &gif[13 + 19 + 8..];
+        assert_eq!(frame[0], 0x2C);
+        assert_eq!(frame[9], 0x80);
+        // Two-entry table, black first since the top left pixel is off.
+        assert_eq!(frame[10..16], [0, 0, 0, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(frame[16], 2);
+        let len = frame[17] as usize;
+        assert_eq!(
+            unlzw(&frame[18..18 + len], 2),
+            Some(vec![0, 1, 0, 1, 1, 0, 1, 0])
+        );
+    }
+
+    #[test]
+    fn test_gif_resizes_later_frames() {
+        let mut recorder = GifRecorder::new(Vec::new(), Renderer::default());
+        recorder.capture_image(checkerboard(4, 2, 0)).unwrap();
+        recorder.capture_image(checkerboard(2, 1, 0)).unwrap();
+        recorder.capture_image(checkerboard(4, 2, 1)).unwrap();
+
+        // The scaled-up 2x1 frame follows the first after 2/100 s, but lasts
+        // less than that itself, so the last frame takes its place.
+        assert_eq!(recorder.frames(), 1);
+        assert!(recorder.finish().is_ok());
+        assert!(
+            GifRecorder::new(Vec::new(), Renderer::default())
+                .finish()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,815 @@
+use std::fmt;
+use std::ops::Range;
+
+use crate::{Emu, EmuError, Instruction, StepOutcome, TIMER_HZ};
+
+/// Instructions `step_over`, `step_out` and `run` give up after by default.
+const DEFAULT_STEP_LIMIT: u64 = 1_000_000;
+
+/// A value a `Condition` can look at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// V0 to VF.
+    V(u8),
+    I,
+    Pc,
+    /// Stack depth.
+    Sp,
+    Dt,
+    St,
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+            Register::Pc => write!(f, "PC"),
+            Register::Sp => write!(f, "SP"),
+            Register::Dt => write!(f, "DT"),
+            Register::St => write!(f, "ST"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Compare::Eq => "==",
+            Compare::Ne => "!=",
+            Compare::Lt => "<",
+            Compare::Le => "<=",
+            Compare::Gt => ">",
+            Compare::Ge => ">=",
+        })
+    }
+}
+
+/// A test on a register, e.g. `V3 == 0x10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub compare: Compare,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn new(register: Register, compare: Compare, value: u16) -> Self {
+        Self {
+            register,
+            compare,
+            value,
+        }
+    }
+
+    pub fn holds(&self, emu: &Emu) -> bool {
+        let current = match self.register {
+            Register::V(x) => emu.v_reg[x as usize & 0xF] as u16,
+            Register::I => emu.i_reg,
+            Register::Pc => emu.pc,
+            Register::Sp => emu.sp,
+            Register::Dt => emu.dt as u16,
+            Register::St => emu.st as u16,
+        };
+        match self.compare {
+            Compare::Eq => current == self.value,
+            Compare::Ne => current != self.value,
+            Compare::Lt => current < self.value,
+            Compare::Le => current <= self.value,
+            Compare::Gt => current > self.value,
+            Compare::Ge => current >= self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} 0x{:X}", self.register, self.compare, self.value)
+    }
+}
+
+/// Stops execution before an instruction runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// The instruction at this address is about to run.
+    Pc(u16),
+    /// `condition` holds, either at `pc` only or before any instruction.
+    Conditional {
+        pc: Option<u16>,
+        condition: Condition,
+    },
+    /// The opcode about to run has `value` in the bits set in `mask`.
+    Opcode { mask: u16, value: u16 },
+}
+
+impl Breakpoint {
+    /// Parses an opcode pattern such as `"DXYN"` or `"00E0"`: hex digits must
+    /// match, any of `X`, `Y`, `N` or `K` matches anything.
+    pub fn opcode(pattern: &str) -> Option<Breakpoint> {
+        if pattern.len() != 4 {
+            return None;
+        }
+        let (mut mask, mut value) = (0u16, 0u16);
+        for c in pattern.chars() {
+            mask <<= 4;
+            value <<= 4;
+            match c.to_ascii_uppercase() {
+                'X' | 'Y' | 'N' | 'K' => {}
+                c => {
+                    value |= c.to_digit(16)? as u16;
+                    mask |= 0xF;
+                }
+            }
+        }
+        Some(Breakpoint::Opcode { mask, value })
+    }
+
+    fn hits(&self, emu: &Emu) -> bool {
+        match *self {
+            Breakpoint::Pc(addr) => emu.pc == addr,
+            Breakpoint::Conditional { pc, condition } => {
+                pc.is_none_or(|addr| emu.pc == addr) && condition.holds(emu)
+            }
+            Breakpoint::Opcode { mask, value } => {
+                emu.read_word(emu.pc).is_some_and(|op| op & mask == value)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Access::Read => "read",
+            Access::Write => "write",
+        })
+    }
+}
+
+/// Stops execution before an instruction reads or writes a range of RAM.
+///
+/// Only data accesses through I count; fetching instructions does not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: Range<u16>,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    pub fn read(range: Range<u16>) -> Self {
+        Self {
+            range,
+            read: true,
+            write: false,
+        }
+    }
+
+    pub fn write(range: Range<u16>) -> Self {
+        Self {
+            range,
+            read: false,
+            write: true,
+        }
+    }
+
+    /// Triggers on both reads and writes.
+    pub fn access(range: Range<u16>) -> Self {
+        Self {
+            range,
+            read: true,
+            write: true,
+        }
+    }
+
+    /// First watched address in `range`, if this watchpoint cares about `access`.
+    fn hit(&self, range: &Range<usize>, access: Access) -> Option<u16> {
+        let wanted = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        let start = range.start.max(self.range.start as usize);
+        let end = range.end.min(self.range.end as usize);
+        (wanted && start < end).then_some(start as u16)
+    }
+}
+
+/// Why `Debugger` handed control back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested step finished.
+    Step,
+    /// Breakpoint `id` fired; the instruction at `pc` has not run yet.
+    Breakpoint { id: usize, pc: u16 },
+    /// The instruction at `pc` is about to touch `addr`, watched by `id`.
+    Watchpoint {
+        id: usize,
+        pc: u16,
+        addr: u16,
+        access: Access,
+    },
+    /// `FX0A` is waiting for a key press.
+    WaitingForKey,
+    /// The program executed `00FD` (EXIT).
+    Halted,
+    /// The instruction failed; the machine is left as the error found it.
+    Error(EmuError),
+    /// The step limit ran out before anything else happened.
+    Limit,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Step => write!(f, "step"),
+            StopReason::Breakpoint { id, pc } => write!(f, "breakpoint {} at {:03X}", id, pc),
+            StopReason::Watchpoint {
+                id,
+                pc,
+                addr,
+                access,
+            } => write!(
+                f,
+                "watchpoint {}: {} of {:03X} at {:03X}",
+                id, access, addr, pc
+            ),
+            StopReason::WaitingForKey => write!(f, "waiting for key"),
+            StopReason::Halted => write!(f, "halted"),
+            StopReason::Error(err) => write!(f, "{}", err),
+            StopReason::Limit => write!(f, "step limit reached"),
+        }
+    }
+}
+
+/// Wraps an `Emu` with breakpoints, watchpoints and stepping for frontends.
+///
+/// Breakpoints and watchpoints are checked before each instruction runs, so
+/// on a stop the program counter still points at the offending instruction.
+/// Resuming runs that instruction without checking it again.
+pub struct Debugger {
+    emu: Emu,
+    breakpoints: Vec<(usize, Breakpoint)>,
+    watchpoints: Vec<(usize, Watchpoint)>,
+    next_id: usize,
+    /// Program counter of the last stop, whose checks the next instruction skips.
+    stopped_at: Option<u16>,
+    step_limit: u64,
+    // Timer ticks owed to instruction-wise execution, in 1/TIMER_HZ units
+    tick_acc: u32,
+}
+
+impl Debugger {
+    pub fn new(emu: Emu) -> Self {
+        Self {
+            emu,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_id: 1,
+            stopped_at: None,
+            step_limit: DEFAULT_STEP_LIMIT,
+            tick_acc: 0,
+        }
+    }
+
+    pub fn emu(&self) -> &Emu {
+        &self.emu
+    }
+
+    /// Gives access to the machine, e.g. to load a ROM or reset it. The
+    /// checks skipped after a stop run again, as the machine may have moved.
+    pub fn emu_mut(&mut self) -> &mut Emu {
+        self.stopped_at = None;
+        &mut self.emu
+    }
+
+    /// Presses a keypad key without leaving the current stop, so that a
+    /// machine waiting for a key goes on past its breakpoint.
+    pub fn key_down(&mut self, key: u8) {
+        self.emu.key_down(key);
+    }
+
+    /// Releases a keypad key without leaving the current stop.
+    pub fn key_up(&mut self, key: u8) {
+        self.emu.key_up(key);
+    }
+
+    pub fn into_inner(self) -> Emu {
+        self.emu
+    }
+
+    /// Adds a breakpoint and returns its id.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.alloc_id();
+        self.breakpoints.push((id, breakpoint));
+        id
+    }
+
+    /// Removes the breakpoint with `id`, returning whether it existed.
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|(bp, _)| *bp != id);
+        self.breakpoints.len() != len
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, bp)| (*id, bp))
+    }
+
+    /// Adds a watchpoint and returns its id.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.alloc_id();
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    /// Removes the watchpoint with `id`, returning whether it existed.
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|(wp, _)| *wp != id);
+        self.watchpoints.len() != len
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints.iter().map(|(id, wp)| (*id, wp))
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+    }
+
+    pub fn step_limit(&self) -> u64 {
+        self.step_limit
+    }
+
+    /// Sets how many instructions `step_over`, `step_out` and `run` execute at
+    /// most before returning `StopReason::Limit`.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = limit.max(1);
+    }
+
+    /// Runs exactly one instruction, ignoring breakpoints and watchpoints.
+    pub fn step_into(&mut self) -> StopReason {
+        self.stopped_at = Some(self.emu.pc);
+        match self.advance() {
+            Ok(_) => StopReason::Step,
+            Err(reason) => reason,
+        }
+    }
+
+    /// Like `step_into`, but runs a `CALL` until the subroutine returns.
+    pub fn step_over(&mut self) -> StopReason {
+        let pc = self.emu.pc;
+        match self.current_instruction() {
+            Some(ins @ Instruction::Call(_)) => {
+                let (ret, depth) = (pc.wrapping_add(ins.size() as u16), self.emu.sp);
+                self.stopped_at = Some(pc);
+                self.run_until(|emu| emu.pc == ret && emu.sp == depth)
+            }
+            _ => self.step_into(),
+        }
+    }
+
+    /// Runs until the current subroutine returns to its caller.
+    pub fn step_out(&mut self) -> StopReason {
+        let depth = self.emu.sp;
+        self.stopped_at = Some(self.emu.pc);
+        self.run_until(|emu| emu.sp < depth)
+    }
+
+    /// Runs until a breakpoint, watchpoint or other stop, or until the step
+    /// limit runs out. Timers count down at the pace set by the `Speed`.
+    pub fn run(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    /// `Emu::run_frame` with breakpoints and watchpoints: runs one frame's
+    /// worth of instructions and counts the timers down, or returns early with
+    /// the reason execution stopped. Waiting for a key is not a stop here.
+    pub fn run_frame(&mut self) -> Option<StopReason> {
+        for _ in 0..self.emu.frame_budget() {
+            match self.advance() {
+                Ok(StepOutcome::Executed) => {}
+                Ok(_) | Err(StopReason::WaitingForKey) => break,
+                Err(reason) => return Some(reason),
+            }
+        }
+        self.emu.tick_timers();
+        None
+    }
+
+    fn run_until(&mut self, done: impl Fn(&Emu) -> bool) -> StopReason {
+        for _ in 0..self.step_limit {
+            match self.advance() {
+                Ok(outcome) => self.pace(outcome),
+                Err(reason) => return reason,
+            }
+            if done(&self.emu) {
+                return StopReason::Step;
+            }
+        }
+        StopReason::Limit
+    }
+
+    /// Counts the timers down once per frame's worth of instructions.
+    fn pace(&mut self, outcome: StepOutcome) {
+        let ips = self.emu.speed.0.max(1);
+        if outcome == StepOutcome::WaitingForVblank {
+            self.tick_acc = ips;
+        } else {
+            self.tick_acc += TIMER_HZ;
+        }
+        while self.tick_acc >= ips {
+            self.tick_acc -= ips;
+            self.emu.tick_timers();
+        }
+    }
+
+    /// Checks and runs the next instruction. `Err` carries the reason to stop.
+    fn advance(&mut self) -> Result<StepOutcome, StopReason> {
+        let pc = self.emu.pc;
+        if self.stopped_at.take() != Some(pc)
+            && let Some(reason) = self.check()
+        {
+            self.stopped_at = Some(pc);
+            return Err(reason);
+        }
+        match self.emu.step() {
+            Ok(StepOutcome::WaitingForKey) => {
+                self.stopped_at = Some(pc);
+                Err(StopReason::WaitingForKey)
+            }
+            Ok(StepOutcome::Halted) => Err(StopReason::Halted),
+            Ok(outcome) => Ok(outcome),
+            Err(err) => Err(StopReason::Error(err)),
+        }
+    }
+
+    /// The first breakpoint or watchpoint the next instruction triggers.
+    fn check(&self) -> Option<StopReason> {
+        let pc = self.emu.pc;
+        if let Some(&(id, _)) = self.breakpoints.iter().find(|(_, bp)| bp.hits(&self.emu)) {
+            return Some(StopReason::Breakpoint { id, pc });
+        }
+        let (range, access) = self
+            .current_instruction()
+            .and_then(|ins| self.emu.memory_access(ins))?;
+        self.watchpoints.iter().find_map(|(id, wp)| {
+            wp.hit(&range, access).map(|addr| StopReason::Watchpoint {
+                id: *id,
+                pc,
+                addr,
+                access,
+            })
+        })
+    }
+
+    fn current_instruction(&self) -> Option<Instruction> {
+        let bytes = self.emu.ram.get(self.emu.pc as usize..).unwrap_or(&[]);
+        Instruction::read(bytes, self.emu.mode)
+    }
+
+    fn alloc_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mode, Speed};
+
+    fn debugger(rom: &[u8]) -> Debugger {
+        let mut emu = Emu::with_seed(1);
+        emu.load_rom(rom).unwrap();
+        Debugger::new(emu)
+    }
+
+    // 200: V0 += 1
+    // 202: CALL 20A
+    // 204: DRW V0, V0, 5
+    // 206: JP 200
+    // 208: (unused)
+    // 20A: LD I, 0x300
+    // 20C: LD [I], V1
+    // 20E: RET
+    const PROGRAM: [u8; 16] = [
+        0x70, 0x01, 0x22, 0x0A, 0xD0, 0x05, 0x12, 0x00, 0x00, 0x00, 0xA3, 0x00, 0xF1, 0x55, 0x00,
+        0xEE,
+    ];
+
+    #[test]
+    fn test_step_into_runs_one_instruction() {
+        let mut dbg = debugger(&PROGRAM);
+
+        assert_eq!(dbg.step_into(), StopReason::Step);
+        assert_eq!(dbg.emu().pc(), 0x202);
+        assert_eq!(dbg.emu().v_reg()[0], 1);
+
+        assert_eq!(dbg.step_into(), StopReason::Step);
+        assert_eq!(dbg.emu().pc(), 0x20A);
+        assert_eq!(dbg.emu().stack(), &[0x204]);
+    }
+
+    #[test]
+    fn test_pc_breakpoint_stops_before_instruction() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x204));
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x204 });
+        assert_eq!(dbg.emu().pc(), 0x204);
+    }
+
+    #[test]
+    fn test_resuming_does_not_hit_the_same_breakpoint_again() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x200));
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x200 });
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x200 });
+        assert_eq!(dbg.emu().v_reg()[0], 1);
+    }
+
+    #[test]
+    fn test_changing_the_machine_checks_breakpoints_again() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x200));
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x200 });
+        dbg.emu_mut().reset();
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x200 });
+        assert_eq!(dbg.emu().v_reg()[0], 0);
+    }
+
+    #[test]
+    fn test_removed_breakpoint_no_longer_fires() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x204));
+        dbg.set_step_limit(100);
+
+        assert!(dbg.remove_breakpoint(id));
+        assert!(!dbg.remove_breakpoint(id));
+        assert_eq!(dbg.run(), StopReason::Limit);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut dbg = debugger(&PROGRAM);
+        let condition = Condition::new(Register::V(0), Compare::Ge, 3);
+        let id = dbg.add_breakpoint(Breakpoint::Conditional {
+            pc: Some(0x204),
+            condition,
+        });
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x204 });
+        assert_eq!(dbg.emu().v_reg()[0], 3);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint_anywhere() {
+        let mut dbg = debugger(&PROGRAM);
+        let condition = Condition::new(Register::Sp, Compare::Eq, 1);
+        let id = dbg.add_breakpoint(Breakpoint::Conditional {
+            pc: None,
+            condition,
+        });
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x20A });
+    }
+
+    #[test]
+    fn test_opcode_pattern() {
+        assert_eq!(
+            Breakpoint::opcode("DXYN"),
+            Some(Breakpoint::Opcode {
+                mask: 0xF000,
+                value: 0xD000
+            })
+        );
+        assert_eq!(
+            Breakpoint::opcode("fx55"),
+            Some(Breakpoint::Opcode {
+                mask: 0xF0FF,
+                value: 0xF055
+            })
+        );
+        assert_eq!(Breakpoint::opcode("DXY"), None);
+        assert_eq!(Breakpoint::opcode("GXYN"), None);
+    }
+
+    #[test]
+    fn test_opcode_breakpoint() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::opcode("DXYN").unwrap());
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x204 });
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_watchpoint(Watchpoint::write(0x301..0x302));
+
+        assert_eq!(
+            dbg.run(),
+            StopReason::Watchpoint {
+                id,
+                pc: 0x20C,
+                addr: 0x301,
+                access: Access::Write
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_watchpoint_ignores_writes() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_watchpoint(Watchpoint::read(0x300..0x302));
+
+        // The subroutine writes 0x300, then DRW reads it back.
+        assert_eq!(
+            dbg.run(),
+            StopReason::Watchpoint {
+                id,
+                pc: 0x204,
+                addr: 0x300,
+                access: Access::Read
+            }
+        );
+    }
+
+    #[test]
+    fn test_step_over_runs_whole_call() {
+        let mut dbg = debugger(&PROGRAM);
+        dbg.step_into();
+
+        assert_eq!(dbg.step_over(), StopReason::Step);
+        assert_eq!(dbg.emu().pc(), 0x204);
+        assert_eq!(dbg.emu().sp(), 0);
+    }
+
+    #[test]
+    fn test_step_over_stops_at_breakpoint_inside_call() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x20C));
+        dbg.step_into();
+
+        assert_eq!(dbg.step_over(), StopReason::Breakpoint { id, pc: 0x20C });
+    }
+
+    #[test]
+    fn test_step_over_plain_instruction_is_step_into() {
+        let mut dbg = debugger(&PROGRAM);
+
+        assert_eq!(dbg.step_over(), StopReason::Step);
+        assert_eq!(dbg.emu().pc(), 0x202);
+    }
+
+    #[test]
+    fn test_step_out_returns_to_caller() {
+        let mut dbg = debugger(&PROGRAM);
+        dbg.step_into();
+        dbg.step_into();
+
+        assert_eq!(dbg.step_out(), StopReason::Step);
+        assert_eq!(dbg.emu().pc(), 0x204);
+        assert_eq!(dbg.emu().i_reg(), 0x300);
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut dbg = debugger(&PROGRAM);
+        dbg.set_step_limit(10);
+
+        // Not inside a subroutine, so there is nothing to return from.
+        assert_eq!(dbg.step_out(), StopReason::Limit);
+    }
+
+    #[test]
+    fn test_waiting_for_key() {
+        // LD V0, K
+        let mut dbg = debugger(&[0xF0, 0x0A]);
+
+        assert_eq!(dbg.run(), StopReason::WaitingForKey);
+        assert_eq!(dbg.run_frame(), None);
+
+        dbg.key_down(7);
+        assert_eq!(dbg.step_into(), StopReason::Step);
+        assert_eq!(dbg.emu().v_reg()[0], 7);
+    }
+
+    #[test]
+    fn test_key_press_keeps_the_stop() {
+        // LD V0, K / JP 202
+        let mut dbg = debugger(&[0xF0, 0x0A, 0x12, 0x02]);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x200));
+        dbg.set_step_limit(10);
+
+        assert_eq!(dbg.run(), StopReason::Breakpoint { id, pc: 0x200 });
+        assert_eq!(dbg.run(), StopReason::WaitingForKey);
+        dbg.key_down(7);
+        assert_eq!(dbg.run(), StopReason::Limit);
+        assert_eq!(dbg.emu().v_reg()[0], 7);
+    }
+
+    #[test]
+    fn test_halted_and_errors() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.load_rom(&[0x00, 0xFD]).unwrap();
+        let mut dbg = Debugger::new(emu);
+
+        assert_eq!(dbg.run(), StopReason::Halted);
+
+        let mut dbg = debugger(&[0x00, 0xEE]);
+        assert_eq!(
+            dbg.step_into(),
+            StopReason::Error(EmuError::StackUnderflow { addr: 0x200 })
+        );
+    }
+
+    #[test]
+    fn test_run_frame_matches_emu() {
+        let mut plain = Emu::with_seed(1);
+        plain.load_rom(&PROGRAM).unwrap();
+        plain.set_speed(Speed::ips(500));
+        let mut dbg = debugger(&PROGRAM);
+        dbg.emu_mut().set_speed(Speed::ips(500));
+
+        for _ in 0..10 {
+            plain.run_frame().unwrap();
+            assert_eq!(dbg.run_frame(), None);
+        }
+
+        assert_eq!(dbg.emu().save_state(), plain.save_state());
+    }
+
+    #[test]
+    fn test_run_frame_top_speed_does_not_overflow() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.load_rom(&[0x00, 0xFD]).unwrap();
+        let mut dbg = Debugger::new(emu);
+        dbg.emu_mut().set_speed(Speed::ips(u32::MAX));
+
+        assert_eq!(dbg.run_frame(), Some(StopReason::Halted));
+        assert_eq!(dbg.run_frame(), Some(StopReason::Halted));
+    }
+
+    #[test]
+    fn test_run_frame_stops_at_breakpoint() {
+        let mut dbg = debugger(&PROGRAM);
+        let id = dbg.add_breakpoint(Breakpoint::Pc(0x20E));
+
+        assert_eq!(
+            dbg.run_frame(),
+            Some(StopReason::Breakpoint { id, pc: 0x20E })
+        );
+        dbg.remove_breakpoint(id);
+        assert_eq!(dbg.run_frame(), None);
+    }
+
+    #[test]
+    fn test_run_ticks_timers() {
+        // LD V0, 2; LD DT, V0; LD V1, DT; SE V1, 0; JP 204; JP 20A
+        let mut dbg = debugger(&[
+            0x60, 0x02, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x12, 0x0A,
+        ]);
+        dbg.add_breakpoint(Breakpoint::Pc(0x20A));
+
+        assert!(matches!(dbg.run(), StopReason::Breakpoint { .. }));
+        assert_eq!(dbg.emu().delay_timer(), 0);
+    }
+
+    #[test]
+    fn test_display() {
+        let condition = Condition::new(Register::V(0xA), Compare::Ne, 0x10);
+        assert_eq!(condition.to_string(), "VA != 0x10");
+        assert_eq!(
+            StopReason::Watchpoint {
+                id: 2,
+                pc: 0x204,
+                addr: 0x300,
+                access: Access::Write
+            }
+            .to_string(),
+            "watchpoint 2: write of 300 at 204"
+        );
+    }
+}
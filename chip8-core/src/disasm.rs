@@ -0,0 +1,412 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::{Instruction, Mode, START_ADDR};
+
+/// How `Disassembly::listing` spells instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    /// Cowgod-style mnemonics (`LD VA, 0x02`) with addresses and raw bytes.
+    #[default]
+    Classic,
+    /// Octo source (`va := 0x02`) that assembles back to the same image.
+    Octo,
+}
+
+/// A ROM image split into code and data by recursive descent from `START_ADDR`.
+///
+/// Every path through `1NNN`, `2NNN`, skips and `BNNN` jump tables is
+/// followed; whatever is never reached is treated as data and listed as
+/// sprite bitmaps.
+pub struct Disassembly {
+    mode: Mode,
+    rom: Vec<u8>,
+    /// The instruction starting at each offset reached as code.
+    code: BTreeMap<usize, Instruction>,
+    /// Offsets covered by code, including the second halves of instructions.
+    covered: Vec<bool>,
+    labels: BTreeMap<u16, String>,
+}
+
+impl Disassembly {
+    pub fn new(rom: &[u8], mode: Mode) -> Self {
+        let mut dis = Disassembly {
+            mode,
+            rom: rom.to_vec(),
+            code: BTreeMap::new(),
+            covered: vec![false; rom.len()],
+            labels: BTreeMap::new(),
+        };
+        let targets = dis.trace();
+        dis.name_labels(targets);
+        dis
+    }
+
+    /// Follows every reachable path, returning the addresses worth a label.
+    fn trace(&mut self) -> Vec<(u16, char)> {
+        let mut targets = vec![(START_ADDR, 'L')];
+        let mut pending = vec![START_ADDR];
+
+        while let Some(addr) = pending.pop() {
+            let Some(offset) = self.offset(addr) else {
+                continue;
+            };
+            if self.covered[offset] {
+                continue;
+            }
+            let Some(ins) = Instruction::read(&self.rom[offset..], self.mode) else {
+                continue;
+            };
+            let size = ins.size();
+            if self.covered[offset..offset + size].iter().any(|&c| c) {
+                continue;
+            }
+            self.covered[offset..offset + size].fill(true);
+            self.code.insert(offset, ins);
+
+            let next = addr.wrapping_add(size as u16);
+            match ins {
+                Instruction::Jp(target) => {
+                    targets.push((target, 'L'));
+                    pending.push(target);
+                }
+                Instruction::Call(target) => {
+                    targets.push((target, 'L'));
+                    pending.extend([target, next]);
+                }
+                Instruction::JpV0(table) => {
+                    // Usually a table of jumps indexed by V0
+                    targets.push((table, 'L'));
+                    let mut entry = table;
+                    while let Some(offset) = self.offset(entry) {
+                        pending.push(entry);
+                        if self.rom.get(offset).is_none_or(|&b| b >> 4 != 1) {
+                            break;
+                        }
+                        entry = entry.wrapping_add(2);
+                    }
+                }
+                Instruction::Ret | Instruction::Exit => {}
+                Instruction::Se { .. }
+                | Instruction::Sne { .. }
+                | Instruction::SeReg { .. }
+                | Instruction::SneReg { .. }
+                | Instruction::Skp { .. }
+                | Instruction::Sknp { .. } => {
+                    let skipped = self
+                        .offset(next)
+                        .and_then(|o| Instruction::read(&self.rom[o..], self.mode))
+                        .map_or(2, |i| i.size());
+                    pending.extend([next.wrapping_add(skipped as u16), next]);
+                }
+                Instruction::LdI(target) | Instruction::LdILong(target) => {
+                    targets.push((target, 'S'));
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+        targets
+    }
+
+    /// Names the targets that start an instruction or a data byte; the rest
+    /// (outside the ROM, or inside an instruction) keep their numeric form.
+    fn name_labels(&mut self, targets: Vec<(u16, char)>) {
+        for (addr, prefix) in targets {
+            let Some(offset) = self.offset(addr) else {
+                continue;
+            };
+            let is_code_start = self.code.contains_key(&offset);
+            if self.covered[offset] && !is_code_start {
+                continue;
+            }
+            let name = match (addr, is_code_start) {
+                (START_ADDR, _) => "main".to_string(),
+                (_, true) => format!("L{:03X}", addr),
+                _ => format!("{}{:03X}", prefix, addr),
+            };
+            let entry = self.labels.entry(addr).or_insert(name);
+            if is_code_start && entry.starts_with('S') {
+                *entry = format!("L{:03X}", addr);
+            }
+        }
+    }
+
+    fn offset(&self, addr: u16) -> Option<usize> {
+        let offset = (addr as usize).checked_sub(START_ADDR as usize)?;
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    /// The instruction starting at `addr`, if it was reached as code.
+    pub fn instruction_at(&self, addr: u16) -> Option<Instruction> {
+        self.code.get(&self.offset(addr)?).copied()
+    }
+
+    /// Whether the byte at `addr` belongs to an instruction.
+    pub fn is_code(&self, addr: u16) -> bool {
+        self.offset(addr).is_some_and(|o| self.covered[o])
+    }
+
+    pub fn label(&self, addr: u16) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    /// Renders the whole image, one instruction or data byte per line.
+    pub fn listing(&self, syntax: Syntax) -> String {
+        let mut out = String::new();
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            let addr = START_ADDR + offset as u16;
+            if let Some(label) = self.label(addr) {
+                match syntax {
+                    Syntax::Classic => writeln!(out, "{}:", label),
+                    Syntax::Octo => writeln!(out, ": {}", label),
+                }
+                .unwrap();
+            }
+
+            if let Some(ins) = self.code.get(&offset) {
+                let bytes = &self.rom[offset..offset + ins.size()];
+                match syntax {
+                    Syntax::Classic => {
+                        let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                        writeln!(out, "0x{:03X}  {:<8}  {}", addr, hex, self.classic(ins))
+                    }
+                    Syntax::Octo => writeln!(out, "  {}", self.octo(ins)),
+                }
+                .unwrap();
+                offset += ins.size();
+            } else {
+                let byte = self.rom[offset];
+                match syntax {
+                    Syntax::Classic => writeln!(
+                        out,
+                        "0x{:03X}  {:02X}        DB 0x{:02X}  ; {}",
+                        addr,
+                        byte,
+                        byte,
+                        bitmap(byte)
+                    ),
+                    Syntax::Octo => writeln!(out, "  0x{:02X} # {}", byte, bitmap(byte)),
+                }
+                .unwrap();
+                offset += 1;
+            }
+        }
+        out
+    }
+
+    /// An address operand: its label if it has one, hex otherwise.
+    fn target(&self, addr: u16) -> String {
+        match self.label(addr) {
+            Some(label) => label.to_string(),
+            None => format!("0x{:03X}", addr),
+        }
+    }
+
+    fn classic(&self, ins: &Instruction) -> String {
+        match *ins {
+            Instruction::Jp(t) => format!("JP {}", self.target(t)),
+            Instruction::Call(t) => format!("CALL {}", self.target(t)),
+            Instruction::JpV0(t) => format!("JP V0, {}", self.target(t)),
+            Instruction::LdI(t) => format!("LD I, {}", self.target(t)),
+            Instruction::LdILong(t) => format!("LD I, long {}", self.target(t)),
+            _ => ins.to_string(),
+        }
+    }
+
+    fn octo(&self, ins: &Instruction) -> String {
+        use Instruction::*;
+
+        match *ins {
+            Nop | Sys(_) => {
+                let [hi, lo] = ins.encode().to_be_bytes();
+                format!("0x{:02X} 0x{:02X}", hi, lo)
+            }
+            Cls => "clear".into(),
+            Ret => "return".into(),
+            Jp(t) => format!("jump {}", self.target(t)),
+            Call(t) => format!(":call {}", self.target(t)),
+            Se { x, kk } => format!("if v{:x} != 0x{:02X} then", x, kk),
+            Sne { x, kk } => format!("if v{:x} == 0x{:02X} then", x, kk),
+            SeReg { x, y } => format!("if v{:x} != v{:x} then", x, y),
+            SneReg { x, y } => format!("if v{:x} == v{:x} then", x, y),
+            Ld { x, kk } => format!("v{:x} := 0x{:02X}", x, kk),
+            Add { x, kk } => format!("v{:x} += 0x{:02X}", x, kk),
+            LdReg { x, y } => format!("v{:x} := v{:x}", x, y),
+            Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+            And { x, y } => format!("v{:x} &= v{:x}", x, y),
+            Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+            AddReg { x, y } => format!("v{:x} += v{:x}", x, y),
+            Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+            Shr { x, y } => format!("v{:x} >>= v{:x}", x, y),
+            Subn { x, y } => format!("v{:x} =- v{:x}", x, y),
+            Shl { x, y } => format!("v{:x} <<= v{:x}", x, y),
+            LdI(t) => format!("i := {}", self.target(t)),
+            JpV0(t) => format!("jump0 {}", self.target(t)),
+            Rnd { x, kk } => format!("v{:x} := random 0x{:02X}", x, kk),
+            Drw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+            Skp { x } => format!("if v{:x} -key then", x),
+            Sknp { x } => format!("if v{:x} key then", x),
+            LdVxDt { x } => format!("v{:x} := delay", x),
+            LdVxK { x } => format!("v{:x} := key", x),
+            LdDtVx { x } => format!("delay := v{:x}", x),
+            LdStVx { x } => format!("buzzer := v{:x}", x),
+            AddI { x } => format!("i += v{:x}", x),
+            LdF { x } => format!("i := hex v{:x}", x),
+            LdB { x } => format!("bcd v{:x}", x),
+            LdIVx { x } => format!("save v{:x}", x),
+            LdVxI { x } => format!("load v{:x}", x),
+            Scd(n) => format!("scroll-down {}", n),
+            Scr => "scroll-right".into(),
+            Scl => "scroll-left".into(),
+            Exit => "exit".into(),
+            Low => "lores".into(),
+            High => "hires".into(),
+            LdHf { x } => format!("i := bighex v{:x}", x),
+            LdRVx { x } => format!("saveflags v{:x}", x),
+            LdVxR { x } => format!("loadflags v{:x}", x),
+            Scu(n) => format!("scroll-up {}", n),
+            LdIVxVy { x, y } => format!("save v{:x} - v{:x}", x, y),
+            LdVxVyI { x, y } => format!("load v{:x} - v{:x}", x, y),
+            LdILong(t) => format!("i := long {}", self.target(t)),
+            Plane(n) => format!("plane {}", n),
+            Audio => "audio".into(),
+            Pitch { x } => format!("pitch := v{:x}", x),
+        }
+    }
+}
+
+/// Draws a sprite row: `#` for set bits, `.` for clear ones.
+fn bitmap(byte: u8) -> String {
+    (0..8)
+        .map(|i| if byte & (0x80 >> i) != 0 { '#' } else { '.' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitmap() {
+        assert_eq!(bitmap(0xF0), "####....");
+        assert_eq!(bitmap(0x81), "#......#");
+    }
+
+    #[test]
+    fn test_jump_over_data() {
+        // JP 0x204; sprite byte 0xF0 0x90; LD I, 0x202; DRW V0, V0, 2; JP 0x208
+        let rom = [0x12, 0x04, 0xF0, 0x90, 0xA2, 0x02, 0xD0, 0x02, 0x12, 0x08];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert!(dis.is_code(0x200));
+        assert!(!dis.is_code(0x202));
+        assert!(!dis.is_code(0x203));
+        assert!(dis.is_code(0x204));
+        assert_eq!(dis.label(0x200), Some("main"));
+        assert_eq!(dis.label(0x202), Some("S202"));
+        assert_eq!(dis.label(0x204), Some("L204"));
+        assert_eq!(dis.label(0x208), Some("L208"));
+        assert_eq!(
+            dis.listing(Syntax::Classic),
+            "main:\n\
+             0x200  1204      JP L204\n\
+             S202:\n\
+             0x202  F0        DB 0xF0  ; ####....\n\
+             0x203  90        DB 0x90  ; #..#....\n\
+             L204:\n\
+             0x204  A202      LD I, S202\n\
+             0x206  D002      DRW V0, V0, 2\n\
+             L208:\n\
+             0x208  1208      JP L208\n"
+        );
+    }
+
+    #[test]
+    fn test_call_and_return() {
+        // CALL 0x206; JP 0x204; data; RET
+        let rom = [0x22, 0x06, 0x12, 0x04, 0xFF, 0xFF, 0x00, 0xEE];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert_eq!(dis.instruction_at(0x206), Some(Instruction::Ret));
+        assert_eq!(dis.instruction_at(0x202), Some(Instruction::Jp(0x204)));
+        assert!(!dis.is_code(0x204));
+        assert_eq!(dis.label(0x206), Some("L206"));
+    }
+
+    #[test]
+    fn test_skip_follows_both_paths() {
+        // SE V0, 0; JP 0x208; RET; data; RET
+        let rom = [0x30, 0x00, 0x12, 0x08, 0x00, 0xEE, 0xAA, 0xAA, 0x00, 0xEE];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert!(dis.is_code(0x202));
+        assert!(dis.is_code(0x204));
+        assert!(!dis.is_code(0x206));
+        assert!(dis.is_code(0x208));
+    }
+
+    #[test]
+    fn test_xo_skip_over_long_load() {
+        // SE V0, 0; LD I, long 0x0300; RET
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x03, 0x00, 0x00, 0xEE];
+        let dis = Disassembly::new(&rom, Mode::XoChip);
+
+        assert_eq!(dis.instruction_at(0x202), Some(Instruction::LdILong(0x300)));
+        assert_eq!(dis.instruction_at(0x206), Some(Instruction::Ret));
+        assert!(dis.instruction_at(0x204).is_none());
+    }
+
+    #[test]
+    fn test_jump_table() {
+        // JP V0, 0x202; JP 0x206; JP 0x208; RET; RET
+        let rom = [0xB2, 0x02, 0x12, 0x06, 0x12, 0x08, 0x00, 0xEE, 0x00, 0xEE];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert!((0x200..0x20A).all(|addr| dis.is_code(addr)));
+        assert_eq!(dis.label(0x202), Some("L202"));
+    }
+
+    #[test]
+    fn test_octo_syntax() {
+        // V1 := 5; SE V1, 5; CLS; DRW V1, V2, 3; LD I, 0x20C; JP 0x200; data
+        let rom = [
+            0x61, 0x05, 0x31, 0x05, 0x00, 0xE0, 0xD1, 0x23, 0xA2, 0x0C, 0x12, 0x00, 0x3C,
+        ];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert_eq!(
+            dis.listing(Syntax::Octo),
+            ": main\n  v1 := 0x05\n  if v1 != 0x05 then\n  clear\n  sprite v1 v2 3\n  \
+             i := S20C\n  jump main\n: S20C\n  0x3C # ..####..\n"
+        );
+    }
+
+    #[test]
+    fn test_unlabelled_targets_stay_numeric() {
+        // LD I, 0x050 (font); JP 0x200
+        let rom = [0xA0, 0x50, 0x12, 0x00];
+        let dis = Disassembly::new(&rom, Mode::Chip8);
+
+        assert_eq!(dis.label(0x050), None);
+        assert!(dis.listing(Syntax::Classic).contains("LD I, 0x050"));
+    }
+
+    #[test]
+    fn test_pong2() {
+        let rom = include_bytes!("../../roms/PONG2");
+        let dis = Disassembly::new(rom, Mode::Chip8);
+
+        // PONG2 starts with a call to its score drawing routine
+        assert_eq!(dis.instruction_at(0x200), Some(Instruction::Call(0x2F6)));
+        assert_eq!(dis.label(0x2F6), Some("L2F6"));
+        // The paddle sprite loaded by A2EA is data
+        assert!(!dis.is_code(0x2EA));
+        assert_eq!(dis.label(0x2EA), Some("S2EA"));
+        assert!(
+            dis.listing(Syntax::Classic)
+                .contains("0x2EA  80        DB 0x80  ; #.......")
+        );
+    }
+}
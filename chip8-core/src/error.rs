@@ -0,0 +1,131 @@
+use std::fmt;
+use std::io;
+
+/// Errors returned when loading a program image into the emulator.
+#[derive(Debug)]
+pub enum RomError {
+    /// The image does not fit between `START_ADDR` and the end of RAM.
+    TooLarge { size: usize, max: usize },
+    /// The ROM file could not be read.
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::TooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} bytes fit in RAM", size, max)
+            }
+            RomError::Io(err) => write!(f, "failed to read ROM: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RomError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(err: io::Error) -> Self {
+        RomError::Io(err)
+    }
+}
+
+/// Faults raised while executing a program.
+///
+/// Every variant carries the address of the instruction that caused it so
+/// frontends can point at the offending code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuError {
+    /// The opcode does not decode to any instruction.
+    UnknownOpcode { op: u16, addr: u16 },
+    /// `CALL` with all stack slots in use.
+    StackOverflow { addr: u16 },
+    /// `RET` with an empty stack.
+    StackUnderflow { addr: u16 },
+    /// The instruction tried to read or write `access`, which lies past the end of RAM.
+    MemoryOutOfBounds { addr: u16, access: usize },
+    /// The program counter no longer points at a whole instruction inside RAM.
+    PcOutOfBounds { pc: u16 },
+    /// `0NNN` called machine code at `target` and the `SysPolicy` refuses to run it.
+    UnsupportedSys { target: u16, addr: u16 },
+}
+
+impl fmt::Display for EmuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmuError::UnknownOpcode { op, addr } => {
+                write!(f, "unknown opcode {:04X} at {:03X}", op, addr)
+            }
+            EmuError::StackOverflow { addr } => write!(f, "stack overflow at {:03X}", addr),
+            EmuError::StackUnderflow { addr } => write!(f, "stack underflow at {:03X}", addr),
+            EmuError::MemoryOutOfBounds { addr, access } => write!(
+                f,
+                "memory access at {:04X} out of bounds (instruction at {:03X})",
+                access, addr
+            ),
+            EmuError::PcOutOfBounds { pc } => write!(f, "program counter {:04X} out of bounds", pc),
+            EmuError::UnsupportedSys { target, addr } => write!(
+                f,
+                "machine code call to {:03X} at {:03X} not supported",
+                target, addr
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EmuError {}
+
+/// Reasons a save state cannot be restored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    /// The data does not start with the save state magic bytes.
+    BadMagic,
+    /// The state was written by a newer, unknown format version.
+    UnsupportedVersion(u16),
+    /// The checksum does not match; the data is corrupted.
+    ChecksumMismatch,
+    /// The data ends before the state is complete.
+    Truncated,
+    /// A field holds a value no machine could be in.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::ChecksumMismatch => write!(f, "save state checksum mismatch"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "invalid save state: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// An assembly error, located at the source line that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// Name of the source file, `<input>` for source passed as a string.
+    pub file: String,
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
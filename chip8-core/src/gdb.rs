@@ -0,0 +1,759 @@
+//! A GDB remote serial protocol stub, so `gdb` (or any RSP client) can attach
+//! to a running machine over TCP with `target remote localhost:PORT`.
+//!
+//! # Registers
+//!
+//! Values are sent big-endian, the byte order of the CHIP-8 itself.
+//!
+//! | Number | Name    | Bits | Notes                   |
+//! |--------|---------|------|-------------------------|
+//! | 0-15   | v0-vf   | 8    |                         |
+//! | 16     | i       | 16   |                         |
+//! | 17     | pc      | 16   |                         |
+//! | 18     | sp      | 8    | number of stack entries |
+//! | 19     | dt      | 8    | delay timer             |
+//! | 20     | st      | 8    | sound timer             |
+//!
+//! Memory is the machine's RAM. `Z0`/`Z1` set breakpoints and `Z2`-`Z4`
+//! watchpoints. The call stack, which is not addressable, is printed by
+//! `monitor stack`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::{Breakpoint, Debugger, EmuError, STACK_SIZE, StopReason, Watchpoint};
+
+const NUM_REGISTERS: usize = 21;
+const INTERRUPT: u8 = 0x03;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chips-and-rust.chip8">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Serves one GDB connection at a time on top of a `Debugger`.
+pub struct GdbServer {
+    debugger: Debugger,
+    /// Ids of the break- and watchpoints set through `Z`, by (type, addr, kind).
+    points: HashMap<(u8, u16, u16), usize>,
+}
+
+impl GdbServer {
+    pub fn new(debugger: Debugger) -> Self {
+        Self {
+            debugger,
+            points: HashMap::new(),
+        }
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub fn into_inner(self) -> Debugger {
+        self.debugger
+    }
+
+    /// Waits for a client on `listener` and serves it until it detaches.
+    pub fn accept(&mut self, listener: &TcpListener) -> io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    /// Serves a connected client until it detaches, kills the target or
+    /// hangs up. Break- and watchpoints it set are removed afterwards.
+    pub fn serve(&mut self, stream: TcpStream) -> io::Result<()> {
+        let mut conn = Connection::new(stream)?;
+        let result = self.session(&mut conn);
+        for (_, id) in self.points.drain() {
+            self.debugger.remove_breakpoint(id);
+            self.debugger.remove_watchpoint(id);
+        }
+        result
+    }
+
+    fn session(&mut self, conn: &mut Connection) -> io::Result<()> {
+        while let Some(incoming) = conn.receive()? {
+            let packet = match incoming {
+                Incoming::Interrupt => {
+                    conn.send("S02")?;
+                    continue;
+                }
+                Incoming::Packet(packet) => packet,
+            };
+            match packet.as_bytes().first() {
+                Some(b'D') => {
+                    conn.send("OK")?;
+                    return Ok(());
+                }
+                Some(b'k') => return Ok(()),
+                Some(b'c') => {
+                    let reply = self.resume(conn, &packet[1..])?;
+                    conn.send(&reply)?;
+                }
+                Some(b's') => {
+                    let reply = self.step(&packet[1..]);
+                    conn.send(&reply)?;
+                }
+                _ => {
+                    let reply = self.reply(conn, &packet);
+                    conn.send(&reply)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Answers every packet that does not run the machine.
+    fn reply(&mut self, conn: &mut Connection, packet: &str) -> String {
+        let (Some(kind), Some(args)) = (packet.get(..1), packet.get(1..)) else {
+            return String::new();
+        };
+        let reply = match kind {
+            "?" => Some("S05".to_string()),
+            "g" => Some(self.read_registers()),
+            "G" => self.write_registers(args),
+            "p" => parse_hex(args).and_then(|n| self.read_register(n)),
+            "P" => args
+                .split_once('=')
+                .and_then(|(n, value)| self.write_register(parse_hex(n)?, &decode_hex(value)?)),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.insert_point(args),
+            "z" => self.remove_point(args),
+            "H" => Some("OK".to_string()),
+            "T" => Some("OK".to_string()),
+            "q" | "Q" => self.query(conn, packet),
+            _ => Some(String::new()),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn query(&mut self, conn: &mut Connection, packet: &str) -> Option<String> {
+        // `qRcmd` separates its argument with a comma, everything else with a colon.
+        let (name, args) = packet.split_once([':', ',']).unwrap_or((packet, ""));
+        let reply = match name {
+            "qSupported" => {
+                "PacketSize=4000;qXfer:features:read+;swbreak+;QStartNoAckMode+".to_string()
+            }
+            "QStartNoAckMode" => {
+                conn.ack = false;
+                "OK".to_string()
+            }
+            "qAttached" => "1".to_string(),
+            "qC" => "QC1".to_string(),
+            "qfThreadInfo" => "m1".to_string(),
+            "qsThreadInfo" => "l".to_string(),
+            "qXfer" => {
+                let (annex, range) = args.strip_prefix("features:read:")?.split_once(':')?;
+                if annex != "target.xml" {
+                    return None;
+                }
+                let (offset, len) = range.split_once(',')?;
+                transfer(TARGET_XML, parse_hex(offset)?, parse_hex(len)?)
+            }
+            "qRcmd" => {
+                let command = String::from_utf8(decode_hex(args)?).ok()?;
+                encode_hex(self.monitor(command.trim()).as_bytes())
+            }
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /// Runs a `monitor` command and returns its output.
+    fn monitor(&mut self, command: &str) -> String {
+        match command {
+            "stack" => {
+                let emu = self.debugger.emu();
+                let mut out = format!("#0 0x{:03X}\n", emu.pc());
+                for (depth, addr) in emu.stack().iter().rev().enumerate() {
+                    out += &format!("#{} 0x{:03X}\n", depth + 1, addr);
+                }
+                out
+            }
+            "reset" => {
+                self.debugger.emu_mut().reset();
+                "machine reset\n".to_string()
+            }
+            "help" => "stack -- print the call stack\nreset -- restart the ROM\n".to_string(),
+            _ => format!("unknown command `{}`, try `monitor help`\n", command),
+        }
+    }
+
+    /// Handles `c [addr]`: runs until something stops the machine or the
+    /// client sends an interrupt.
+    fn resume(&mut self, conn: &mut Connection, args: &str) -> io::Result<String> {
+        if self.jump(args).is_none() {
+            return Ok("E01".to_string());
+        }
+        loop {
+            if let Some(reason) = self.debugger.run_frame() {
+                return Ok(self.stop_reply(&reason));
+            }
+            if conn.interrupted()? {
+                return Ok("S02".to_string());
+            }
+        }
+    }
+
+    /// Handles `s [addr]`.
+    fn step(&mut self, args: &str) -> String {
+        if self.jump(args).is_none() {
+            return "E01".to_string();
+        }
+        let reason = self.debugger.step_into();
+        self.stop_reply(&reason)
+    }
+
+    /// Moves the program counter to the optional resume address of `c`/`s`.
+    fn jump(&mut self, args: &str) -> Option<()> {
+        if !args.is_empty() {
+            self.debugger.emu_mut().pc = parse_hex(args)? as u16;
+        }
+        Some(())
+    }
+
+    fn stop_reply(&self, reason: &StopReason) -> String {
+        match reason {
+            StopReason::Breakpoint { .. } => "T05swbreak:;".to_string(),
+            StopReason::Watchpoint { addr, id, .. } => {
+                let kind = self
+                    .points
+                    .iter()
+                    .find(|(_, point)| *point == id)
+                    .map_or(2, |((kind, _, _), _)| *kind);
+                let name = match kind {
+                    3 => "rwatch",
+                    4 => "awatch",
+                    _ => "watch",
+                };
+                format!("T05{}:{:x};", name, addr)
+            }
+            StopReason::Halted => "W00".to_string(),
+            StopReason::Error(EmuError::UnknownOpcode { .. }) => "S04".to_string(),
+            StopReason::Error(_) => "S0b".to_string(),
+            StopReason::Step | StopReason::WaitingForKey | StopReason::Limit => "S05".to_string(),
+        }
+    }
+
+    /// Big-endian bytes of register `n`, or `None` if there is no such register.
+    fn register(&self, n: usize) -> Option<Vec<u8>> {
+        let emu = self.debugger.emu();
+        let bytes = match n {
+            0..=15 => vec![emu.v_reg[n]],
+            16 => emu.i_reg.to_be_bytes().to_vec(),
+            17 => emu.pc.to_be_bytes().to_vec(),
+            18 => vec![emu.sp as u8],
+            19 => vec![emu.dt],
+            20 => vec![emu.st],
+            _ => return None,
+        };
+        Some(bytes)
+    }
+
+    fn read_register(&self, n: usize) -> Option<String> {
+        self.register(n).map(|bytes| encode_hex(&bytes))
+    }
+
+    fn read_registers(&self) -> String {
+        (0..NUM_REGISTERS)
+            .filter_map(|n| self.read_register(n))
+            .collect()
+    }
+
+    fn write_register(&mut self, n: usize, bytes: &[u8]) -> Option<String> {
+        let value = |len: usize| -> Option<u16> {
+            (bytes.len() == len).then(|| bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u16))
+        };
+        let emu = self.debugger.emu_mut();
+        match n {
+            0..=15 => emu.v_reg[n] = value(1)? as u8,
+            16 => emu.i_reg = value(2)?,
+            17 => emu.pc = value(2)?,
+            18 => emu.sp = value(1)?.min(STACK_SIZE as u16),
+            19 => emu.dt = value(1)? as u8,
+            20 => emu.st = value(1)? as u8,
+            _ => return None,
+        }
+        Some("OK".to_string())
+    }
+
+    fn write_registers(&mut self, args: &str) -> Option<String> {
+        let mut bytes = decode_hex(args)?.into_iter();
+        for n in 0..NUM_REGISTERS {
+            let len = self.register(n)?.len();
+            let value: Vec<u8> = bytes.by_ref().take(len).collect();
+            self.write_register(n, &value)?;
+        }
+        Some("OK".to_string())
+    }
+
+    /// `m addr,len`. Reads that run past the end of RAM return what is there.
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, len) = args.split_once(',')?;
+        let (addr, len) = (parse_hex(addr)?, parse_hex(len)?);
+        let ram = self.debugger.emu().ram();
+        let end = addr.saturating_add(len).min(ram.len());
+        if addr >= end && len > 0 {
+            return None;
+        }
+        Some(encode_hex(&ram[addr.min(end)..end]))
+    }
+
+    /// `M addr,len:data`.
+    fn write_memory(&mut self, args: &str) -> Option<String> {
+        let (range, data) = args.split_once(':')?;
+        let (addr, len) = range.split_once(',')?;
+        let (addr, len) = (parse_hex(addr)?, parse_hex(len)?);
+        let data = decode_hex(data)?;
+        let ram = &mut self.debugger.emu_mut().ram;
+        let end = addr.checked_add(len).filter(|&end| end <= ram.len())?;
+        if data.len() != len {
+            return None;
+        }
+        ram[addr..end].copy_from_slice(&data);
+        Some("OK".to_string())
+    }
+
+    /// `Z type,addr,kind`.
+    fn insert_point(&mut self, args: &str) -> Option<String> {
+        let key = parse_point(args)?;
+        if self.points.contains_key(&key) {
+            return Some("OK".to_string());
+        }
+        let (kind, addr, len) = key;
+        let range = addr..addr.saturating_add(len.max(1));
+        let id = match kind {
+            0 | 1 => self.debugger.add_breakpoint(Breakpoint::Pc(addr)),
+            2 => self.debugger.add_watchpoint(Watchpoint::write(range)),
+            3 => self.debugger.add_watchpoint(Watchpoint::read(range)),
+            4 => self.debugger.add_watchpoint(Watchpoint::access(range)),
+            _ => return Some(String::new()),
+        };
+        self.points.insert(key, id);
+        Some("OK".to_string())
+    }
+
+    /// `z type,addr,kind`.
+    fn remove_point(&mut self, args: &str) -> Option<String> {
+        let key = parse_point(args)?;
+        if key.0 > 4 {
+            return Some(String::new());
+        }
+        if let Some(id) = self.points.remove(&key) {
+            self.debugger.remove_breakpoint(id);
+            self.debugger.remove_watchpoint(id);
+        }
+        Some("OK".to_string())
+    }
+}
+
+fn parse_point(args: &str) -> Option<(u8, u16, u16)> {
+    let mut fields = args.splitn(3, ',');
+    let kind = fields.next()?.parse().ok()?;
+    let addr = parse_hex(fields.next()?)?;
+    // Anything after the kind (conditions, commands) is not supported and ignored.
+    let len = parse_hex(fields.next()?.split(';').next()?)?;
+    Some((kind, u16::try_from(addr).ok()?, len as u16))
+}
+
+/// Answers a `qXfer` read of `len` bytes at `offset` in `document`.
+fn transfer(document: &str, offset: usize, len: usize) -> String {
+    let bytes = document.as_bytes();
+    let start = offset.min(bytes.len());
+    let end = start.saturating_add(len).min(bytes.len());
+    let marker = if end == bytes.len() { 'l' } else { 'm' };
+    let mut out = marker.to_string();
+    for &b in &bytes[start..end] {
+        if matches!(b, b'#' | b'$' | b'}' | b'*') {
+            out.push('}');
+            out.push((b ^ 0x20) as char);
+        } else {
+            out.push(b as char);
+        }
+    }
+    out
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+}
+
+enum Incoming {
+    Packet(String),
+    /// The client pressed Ctrl-C (a bare 0x03 byte).
+    Interrupt,
+}
+
+/// Packet framing: `$data#checksum`, acknowledged with `+` until the client
+/// asks for no-ack mode.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    ack: bool,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+            ack: true,
+        })
+    }
+
+    /// Next byte from the client, or `None` once it hangs up.
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        let byte = self.reader.fill_buf()?.first().copied();
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(byte)
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Incoming>> {
+        loop {
+            match self.byte()? {
+                None => return Ok(None),
+                Some(INTERRUPT) => return Ok(Some(Incoming::Interrupt)),
+                Some(b'$') => {}
+                // Stray acks and line noise between packets.
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(b) => data.push(b),
+                }
+            }
+            let (Some(hi), Some(lo)) = (self.byte()?, self.byte()?) else {
+                return Ok(None);
+            };
+            let expected = std::str::from_utf8(&[hi, lo])
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+            if self.ack {
+                if expected != Some(checksum(&data)) {
+                    self.writer.write_all(b"-")?;
+                    continue;
+                }
+                self.writer.write_all(b"+")?;
+            }
+            return Ok(Some(Incoming::Packet(
+                String::from_utf8_lossy(&data).into_owned(),
+            )));
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        loop {
+            self.writer.write_all(packet.as_bytes())?;
+            self.writer.flush()?;
+            if !self.ack {
+                return Ok(());
+            }
+            match self.byte()? {
+                Some(b'-') => continue,
+                Some(_) | None => return Ok(()),
+            }
+        }
+    }
+
+    /// Whether the client sent an interrupt (or hung up) while the machine runs.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if self.reader.buffer().is_empty() {
+            self.reader.get_ref().set_nonblocking(true)?;
+            let filled = self.reader.fill_buf().map(|buf| buf.len());
+            self.reader.get_ref().set_nonblocking(false)?;
+            match filled {
+                Ok(0) => return Ok(true),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(err) => return Err(err),
+            }
+        }
+        if self.reader.buffer().first() == Some(&INTERRUPT) {
+            self.reader.consume(1);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emu, Mode};
+    use std::io::Read;
+    use std::thread;
+
+    // 200: V0 += 1
+    // 202: CALL 20A
+    // 204: DRW V0, V0, 5
+    // 206: JP 200
+    // 208: (unused)
+    // 20A: LD I, 0x300
+    // 20C: LD [I], V1
+    // 20E: RET
+    const PROGRAM: [u8; 16] = [
+        0x70, 0x01, 0x22, 0x0A, 0xD0, 0x05, 0x12, 0x00, 0x00, 0x00, 0xA3, 0x00, 0xF1, 0x55, 0x00,
+        0xEE,
+    ];
+
+    /// A scripted RSP client talking to a server on another thread.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send_raw(&mut self, bytes: &[u8]) {
+            self.stream.write_all(bytes).unwrap();
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        fn read_reply(&mut self) -> String {
+            while self.read_byte() != b'$' {}
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    b => data.push(b),
+                }
+            }
+            let sum = [self.read_byte(), self.read_byte()];
+            let sum = u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap();
+            assert_eq!(sum, checksum(&data));
+            self.send_raw(b"+");
+            String::from_utf8(data).unwrap()
+        }
+
+        fn request(&mut self, packet: &str) -> String {
+            let framed = format!("${}#{:02x}", packet, checksum(packet.as_bytes()));
+            self.send_raw(framed.as_bytes());
+            assert_eq!(self.read_byte(), b'+');
+            self.read_reply()
+        }
+    }
+
+    /// Serves `emu` to a client on another thread that runs `script`.
+    ///
+    /// `Emu` is not `Send`, so the server stays on the test thread.
+    fn run_session(emu: Emu, script: impl FnOnce(&mut Client) + Send + 'static) -> GdbServer {
+        let mut server = GdbServer::new(Debugger::new(emu));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut client = Client { stream };
+            script(&mut client);
+        });
+        server.accept(&listener).unwrap();
+        client.join().unwrap();
+        server
+    }
+
+    fn program() -> Emu {
+        let mut emu = Emu::with_seed(1);
+        emu.load_rom(&PROGRAM).unwrap();
+        emu
+    }
+
+    #[test]
+    fn test_hex_helpers() {
+        assert_eq!(encode_hex(&[0x00, 0xAB, 0x12]), "00ab12");
+        assert_eq!(decode_hex("00ab12"), Some(vec![0x00, 0xAB, 0x12]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(checksum(b"OK"), 0x9A);
+    }
+
+    #[test]
+    fn test_transfer_chunks_and_escapes() {
+        assert_eq!(transfer("abcdef", 0, 4), "mabcd");
+        assert_eq!(transfer("abcdef", 4, 4), "lef");
+        assert_eq!(transfer("a#b", 0, 10), "la}\x03b");
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let server = run_session(program(), |client| {
+            assert!(client.request("qSupported:swbreak+").contains("PacketSize"));
+            assert_eq!(client.request("?"), "S05");
+            let regs = client.request("g");
+            assert_eq!(regs.len(), (16 + 2 + 2 + 1 + 1 + 1) * 2);
+            assert_eq!(&regs[32..40], "00000200");
+
+            assert_eq!(client.request("P3=2a"), "OK");
+            assert_eq!(client.request("p3"), "2a");
+            assert_eq!(client.request("P10=0300"), "OK");
+            assert_eq!(client.request("p10"), "0300");
+            assert_eq!(client.request("p40"), "E01");
+
+            assert_eq!(client.request("m200,4"), "7001220a");
+            assert_eq!(client.request("M300,2:beef"), "OK");
+            assert_eq!(client.request("m300,2"), "beef");
+            assert_eq!(client.request("m2000,2"), "E01");
+            assert_eq!(client.request("Mfff,2:0000"), "E01");
+            assert_eq!(client.request("Mffffffffffffffff,1:00"), "E01");
+
+            assert_eq!(client.request("D"), "OK");
+        });
+
+        let emu = server.into_inner().into_inner();
+        assert_eq!(emu.v_reg()[3], 0x2A);
+        assert_eq!(emu.ram()[0x300..0x302], [0xBE, 0xEF]);
+    }
+
+    #[test]
+    fn test_write_all_registers() {
+        let server = run_session(program(), |client| {
+            let regs = format!("{}{}{}{}", "01".repeat(16), "0345", "0208", "000a0b");
+
+            assert_eq!(client.request(&format!("G{}", regs)), "OK");
+            assert_eq!(client.request("g"), regs);
+            client.request("D");
+        });
+
+        let emu = server.into_inner().into_inner();
+        assert_eq!(emu.pc(), 0x208);
+        assert_eq!(emu.delay_timer(), 0x0A);
+    }
+
+    #[test]
+    fn test_breakpoint_step_and_continue() {
+        let server = run_session(program(), |client| {
+            assert_eq!(client.request("Z0,20c,2"), "OK");
+            assert_eq!(client.request("c"), "T05swbreak:;");
+            assert_eq!(client.request("p11"), "020c");
+
+            assert_eq!(client.request("s"), "S05");
+            assert_eq!(client.request("p11"), "020e");
+
+            assert_eq!(client.request("c"), "T05swbreak:;");
+            assert_eq!(client.request("p11"), "020c");
+
+            assert_eq!(client.request("z0,20c,2"), "OK");
+            assert_eq!(client.request("Z2,300,1"), "OK");
+            assert_eq!(client.request("c"), "T05watch:300;");
+            client.request("D");
+        });
+
+        let dbg = server.into_inner();
+        assert_eq!(dbg.breakpoints().count(), 0);
+        assert_eq!(dbg.watchpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_monitor_stack() {
+        run_session(program(), |client| {
+            client.request("Z0,20c,2");
+            client.request("c");
+
+            let reply = client.request(&format!("qRcmd,{}", encode_hex(b"stack")));
+            let text = String::from_utf8(decode_hex(&reply).unwrap()).unwrap();
+
+            assert_eq!(text, "#0 0x20C\n#1 0x204\n");
+            client.request("D");
+        });
+    }
+
+    #[test]
+    fn test_interrupt_stops_running_machine() {
+        let mut emu = Emu::new();
+        // JP 200
+        emu.load_rom(&[0x12, 0x00]).unwrap();
+
+        run_session(emu, |client| {
+            client.send_raw(b"$c#63");
+            assert_eq!(client.read_byte(), b'+');
+            client.send_raw(&[INTERRUPT]);
+
+            assert_eq!(client.read_reply(), "S02");
+            client.request("D");
+        });
+    }
+
+    #[test]
+    fn test_no_ack_mode_and_bad_checksum() {
+        run_session(program(), |client| {
+            client.send_raw(b"$?#00");
+            assert_eq!(client.read_byte(), b'-');
+
+            assert_eq!(client.request("QStartNoAckMode"), "OK");
+            client.send_raw(format!("$m200,2#{:02x}", checksum(b"m200,2")).as_bytes());
+            let mut reply = [0; 8];
+            client.stream.read_exact(&mut reply).unwrap();
+            assert_eq!(&reply, b"$7001#c8");
+
+            client.send_raw(b"$k#6b");
+        });
+    }
+
+    #[test]
+    fn test_halt_reports_exit() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.load_rom(&[0x00, 0xFD]).unwrap();
+
+        run_session(emu, |client| {
+            assert_eq!(client.request("c"), "W00");
+            client.request("D");
+        });
+    }
+}
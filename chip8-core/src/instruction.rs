@@ -0,0 +1,498 @@
+use std::fmt;
+
+use crate::{Mode, NUM_FLAGS, SCHIP_NUM_FLAGS};
+
+/// A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction.
+///
+/// `x` and `y` are register indices, `kk` an immediate byte, `n` a nibble
+/// and bare `u16`s are 12-bit addresses (16-bit for `LdILong`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `0000` -- NOP
+    Nop,
+    /// `00E0` -- CLS
+    Cls,
+    /// `00EE` -- RET
+    Ret,
+    /// `0NNN` -- SYS addr
+    Sys(u16),
+    /// `1NNN` -- JP addr
+    Jp(u16),
+    /// `2NNN` -- CALL addr
+    Call(u16),
+    /// `3XKK` -- SE Vx, byte
+    Se { x: u8, kk: u8 },
+    /// `4XKK` -- SNE Vx, byte
+    Sne { x: u8, kk: u8 },
+    /// `5XY0` -- SE Vx, Vy
+    SeReg { x: u8, y: u8 },
+    /// `6XKK` -- LD Vx, byte
+    Ld { x: u8, kk: u8 },
+    /// `7XKK` -- ADD Vx, byte
+    Add { x: u8, kk: u8 },
+    /// `8XY0` -- LD Vx, Vy
+    LdReg { x: u8, y: u8 },
+    /// `8XY1` -- OR Vx, Vy
+    Or { x: u8, y: u8 },
+    /// `8XY2` -- AND Vx, Vy
+    And { x: u8, y: u8 },
+    /// `8XY3` -- XOR Vx, Vy
+    Xor { x: u8, y: u8 },
+    /// `8XY4` -- ADD Vx, Vy
+    AddReg { x: u8, y: u8 },
+    /// `8XY5` -- SUB Vx, Vy
+    Sub { x: u8, y: u8 },
+    /// `8XY6` -- SHR Vx {, Vy}
+    Shr { x: u8, y: u8 },
+    /// `8XY7` -- SUBN Vx, Vy
+    Subn { x: u8, y: u8 },
+    /// `8XYE` -- SHL Vx {, Vy}
+    Shl { x: u8, y: u8 },
+    /// `9XY0` -- SNE Vx, Vy
+    SneReg { x: u8, y: u8 },
+    /// `ANNN` -- LD I, addr
+    LdI(u16),
+    /// `BNNN` -- JP V0, addr (`BXNN` with the jump quirk)
+    JpV0(u16),
+    /// `CXKK` -- RND Vx, byte
+    Rnd { x: u8, kk: u8 },
+    /// `DXYN` -- DRW Vx, Vy, nibble
+    Drw { x: u8, y: u8, n: u8 },
+    /// `EX9E` -- SKP Vx
+    Skp { x: u8 },
+    /// `EXA1` -- SKNP Vx
+    Sknp { x: u8 },
+    /// `FX07` -- LD Vx, DT
+    LdVxDt { x: u8 },
+    /// `FX0A` -- LD Vx, K
+    LdVxK { x: u8 },
+    /// `FX15` -- LD DT, Vx
+    LdDtVx { x: u8 },
+    /// `FX18` -- LD ST, Vx
+    LdStVx { x: u8 },
+    /// `FX1E` -- ADD I, Vx
+    AddI { x: u8 },
+    /// `FX29` -- LD F, Vx
+    LdF { x: u8 },
+    /// `FX33` -- LD B, Vx
+    LdB { x: u8 },
+    /// `FX55` -- LD [I], Vx
+    LdIVx { x: u8 },
+    /// `FX65` -- LD Vx, [I]
+    LdVxI { x: u8 },
+    /// `00CN` -- SCD nibble (SUPER-CHIP)
+    Scd(u8),
+    /// `00FB` -- SCR (SUPER-CHIP)
+    Scr,
+    /// `00FC` -- SCL (SUPER-CHIP)
+    Scl,
+    /// `00FD` -- EXIT (SUPER-CHIP)
+    Exit,
+    /// `00FE` -- LOW (SUPER-CHIP)
+    Low,
+    /// `00FF` -- HIGH (SUPER-CHIP)
+    High,
+    /// `FX30` -- LD HF, Vx (SUPER-CHIP)
+    LdHf { x: u8 },
+    /// `FX75` -- LD R, Vx (SUPER-CHIP)
+    LdRVx { x: u8 },
+    /// `FX85` -- LD Vx, R (SUPER-CHIP)
+    LdVxR { x: u8 },
+    /// `00DN` -- SCU nibble (XO-CHIP)
+    Scu(u8),
+    /// `5XY2` -- LD [I], Vx - Vy (XO-CHIP)
+    LdIVxVy { x: u8, y: u8 },
+    /// `5XY3` -- LD Vx - Vy, [I] (XO-CHIP)
+    LdVxVyI { x: u8, y: u8 },
+    /// `F000 NNNN` -- LD I, long addr (XO-CHIP, the only four byte instruction)
+    LdILong(u16),
+    /// `FN01` -- PLANE n (XO-CHIP)
+    Plane(u8),
+    /// `F002` -- AUDIO (XO-CHIP)
+    Audio,
+    /// `FX3A` -- PITCH Vx (XO-CHIP)
+    Pitch { x: u8 },
+}
+
+impl Instruction {
+    /// Decodes a single opcode as `mode` interprets it.
+    ///
+    /// Returns `None` for opcodes the mode does not define, and for XO-CHIP's
+    /// `F000`, whose operand lives in the following word; use `read` for that.
+    pub fn decode(op: u16, mode: Mode) -> Option<Instruction> {
+        use Instruction::*;
+
+        let schip = mode != Mode::Chip8;
+        let xo = mode == Mode::XoChip;
+        let num_flags = if xo { NUM_FLAGS } else { SCHIP_NUM_FLAGS } as u8;
+
+        let x = ((op & 0x0F00) >> 8) as u8;
+        let y = ((op & 0x00F0) >> 4) as u8;
+        let n = (op & 0x000F) as u8;
+        let kk = (op & 0x00FF) as u8;
+        let nnn = op & 0x0FFF;
+
+        let ins = match (op >> 12, x, y, n) {
+            (0, 0, 0, 0) => Nop,
+            (0, 0, 0xE, 0) => Cls,
+            (0, 0, 0xE, 0xE) => Ret,
+            (0, 0, 0xD, n) if xo => Scu(n),
+            (0, 0, 0xC, n) if schip => Scd(n),
+            (0, 0, 0xF, 0xB) if schip => Scr,
+            (0, 0, 0xF, 0xC) if schip => Scl,
+            (0, 0, 0xF, 0xD) if schip => Exit,
+            (0, 0, 0xF, 0xE) if schip => Low,
+            (0, 0, 0xF, 0xF) if schip => High,
+            (0, _, _, _) => Sys(nnn),
+            (1, _, _, _) => Jp(nnn),
+            (2, _, _, _) => Call(nnn),
+            (3, _, _, _) => Se { x, kk },
+            (4, _, _, _) => Sne { x, kk },
+            (5, _, _, 0) => SeReg { x, y },
+            (5, _, _, 2) if xo => LdIVxVy { x, y },
+            (5, _, _, 3) if xo => LdVxVyI { x, y },
+            (6, _, _, _) => Ld { x, kk },
+            (7, _, _, _) => Add { x, kk },
+            (8, _, _, 0) => LdReg { x, y },
+            (8, _, _, 1) => Or { x, y },
+            (8, _, _, 2) => And { x, y },
+            (8, _, _, 3) => Xor { x, y },
+            (8, _, _, 4) => AddReg { x, y },
+            (8, _, _, 5) => Sub { x, y },
+            (8, _, _, 6) => Shr { x, y },
+            (8, _, _, 7) => Subn { x, y },
+            (8, _, _, 0xE) => Shl { x, y },
+            (9, _, _, 0) => SneReg { x, y },
+            (0xA, _, _, _) => LdI(nnn),
+            (0xB, _, _, _) => JpV0(nnn),
+            (0xC, _, _, _) => Rnd { x, kk },
+            (0xD, _, _, _) => Drw { x, y, n },
+            (0xE, _, 9, 0xE) => Skp { x },
+            (0xE, _, 0xA, 1) => Sknp { x },
+            (0xF, n, 0, 1) if xo => Plane(n),
+            (0xF, 0, 0, 2) if xo => Audio,
+            (0xF, _, 0, 7) => LdVxDt { x },
+            (0xF, _, 0, 0xA) => LdVxK { x },
+            (0xF, _, 1, 5) => LdDtVx { x },
+            (0xF, _, 1, 8) => LdStVx { x },
+            (0xF, _, 1, 0xE) => AddI { x },
+            (0xF, _, 2, 9) => LdF { x },
+            (0xF, _, 3, 0) if schip => LdHf { x },
+            (0xF, _, 3, 3) => LdB { x },
+            (0xF, _, 3, 0xA) if xo => Pitch { x },
+            (0xF, _, 5, 5) => LdIVx { x },
+            (0xF, _, 6, 5) => LdVxI { x },
+            (0xF, _, 7, 5) if schip && x < num_flags => LdRVx { x },
+            (0xF, _, 8, 5) if schip && x < num_flags => LdVxR { x },
+            _ => return None,
+        };
+        Some(ins)
+    }
+
+    /// Decodes the instruction at the start of `bytes`, including `F000 NNNN`.
+    ///
+    /// Returns `None` if `bytes` is too short or the opcode is not defined.
+    pub fn read(bytes: &[u8], mode: Mode) -> Option<Instruction> {
+        let word = |i: usize| Some(u16::from_be_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]));
+        let op = word(0)?;
+        if op == 0xF000 && mode == Mode::XoChip {
+            return Some(Instruction::LdILong(word(2)?));
+        }
+        Self::decode(op, mode)
+    }
+
+    /// The opcode word; for `LdILong` this is `F000`, with the address in a
+    /// second word (see `to_bytes`).
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+
+        let xkk = |op: u16, x: u8, kk: u8| op | (x as u16) << 8 | kk as u16;
+        let xyn = |op: u16, x: u8, y: u8, n: u8| op | (x as u16) << 8 | (y as u16) << 4 | n as u16;
+        let fx = |x: u8, low: u16| 0xF000 | (x as u16) << 8 | low;
+
+        match *self {
+            Nop => 0x0000,
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            Sys(nnn) => nnn & 0x0FFF,
+            Jp(nnn) => 0x1000 | nnn & 0x0FFF,
+            Call(nnn) => 0x2000 | nnn & 0x0FFF,
+            Se { x, kk } => xkk(0x3000, x, kk),
+            Sne { x, kk } => xkk(0x4000, x, kk),
+            SeReg { x, y } => xyn(0x5000, x, y, 0),
+            Ld { x, kk } => xkk(0x6000, x, kk),
+            Add { x, kk } => xkk(0x7000, x, kk),
+            LdReg { x, y } => xyn(0x8000, x, y, 0),
+            Or { x, y } => xyn(0x8000, x, y, 1),
+            And { x, y } => xyn(0x8000, x, y, 2),
+            Xor { x, y } => xyn(0x8000, x, y, 3),
+            AddReg { x, y } => xyn(0x8000, x, y, 4),
+            Sub { x, y } => xyn(0x8000, x, y, 5),
+            Shr { x, y } => xyn(0x8000, x, y, 6),
+            Subn { x, y } => xyn(0x8000, x, y, 7),
+            Shl { x, y } => xyn(0x8000, x, y, 0xE),
+            SneReg { x, y } => xyn(0x9000, x, y, 0),
+            LdI(nnn) => 0xA000 | nnn & 0x0FFF,
+            JpV0(nnn) => 0xB000 | nnn & 0x0FFF,
+            Rnd { x, kk } => xkk(0xC000, x, kk),
+            Drw { x, y, n } => xyn(0xD000, x, y, n),
+            Skp { x } => xkk(0xE000, x, 0x9E),
+            Sknp { x } => xkk(0xE000, x, 0xA1),
+            LdVxDt { x } => fx(x, 0x07),
+            LdVxK { x } => fx(x, 0x0A),
+            LdDtVx { x } => fx(x, 0x15),
+            LdStVx { x } => fx(x, 0x18),
+            AddI { x } => fx(x, 0x1E),
+            LdF { x } => fx(x, 0x29),
+            LdB { x } => fx(x, 0x33),
+            LdIVx { x } => fx(x, 0x55),
+            LdVxI { x } => fx(x, 0x65),
+            Scd(n) => 0x00C0 | n as u16,
+            Scr => 0x00FB,
+            Scl => 0x00FC,
+            Exit => 0x00FD,
+            Low => 0x00FE,
+            High => 0x00FF,
+            LdHf { x } => fx(x, 0x30),
+            LdRVx { x } => fx(x, 0x75),
+            LdVxR { x } => fx(x, 0x85),
+            Scu(n) => 0x00D0 | n as u16,
+            LdIVxVy { x, y } => xyn(0x5000, x, y, 2),
+            LdVxVyI { x, y } => xyn(0x5000, x, y, 3),
+            LdILong(_) => 0xF000,
+            Plane(n) => fx(n, 0x01),
+            Audio => 0xF002,
+            Pitch { x } => fx(x, 0x3A),
+        }
+    }
+
+    /// The instruction as it is stored in memory, big-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.encode().to_be_bytes().to_vec();
+        if let Instruction::LdILong(addr) = *self {
+            bytes.extend_from_slice(&addr.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Size of the instruction in bytes: 4 for `LdILong`, 2 for everything else.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LdILong(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// The oldest platform that has this instruction. `DXY0` counts as
+    /// SUPER-CHIP: on CHIP-8 it draws nothing, so no program uses it there.
+    pub fn platform(&self) -> Mode {
+        use Instruction::*;
+
+        match *self {
+            LdRVx { x } | LdVxR { x } if x as usize >= crate::SCHIP_NUM_FLAGS => Mode::XoChip,
+            Scu(_)
+            | LdIVxVy { .. }
+            | LdVxVyI { .. }
+            | LdILong(_)
+            | Plane(_)
+            | Audio
+            | Pitch { .. } => Mode::XoChip,
+            Scd(_)
+            | Scr
+            | Scl
+            | Exit
+            | Low
+            | High
+            | LdHf { .. }
+            | LdRVx { .. }
+            | LdVxR { .. }
+            | Drw { n: 0, .. } => Mode::SuperChip,
+            _ => Mode::Chip8,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Nop => write!(f, "NOP"),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            Sys(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            Jp(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Se { x, kk } => write!(f, "SE V{:X}, 0x{:02X}", x, kk),
+            Sne { x, kk } => write!(f, "SNE V{:X}, 0x{:02X}", x, kk),
+            SeReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Ld { x, kk } => write!(f, "LD V{:X}, 0x{:02X}", x, kk),
+            Add { x, kk } => write!(f, "ADD V{:X}, 0x{:02X}", x, kk),
+            LdReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SneReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LdI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            JpV0(nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Rnd { x, kk } => write!(f, "RND V{:X}, 0x{:02X}", x, kk),
+            Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp { x } => write!(f, "SKP V{:X}", x),
+            Sknp { x } => write!(f, "SKNP V{:X}", x),
+            LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            LdVxK { x } => write!(f, "LD V{:X}, K", x),
+            LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            AddI { x } => write!(f, "ADD I, V{:X}", x),
+            LdF { x } => write!(f, "LD F, V{:X}", x),
+            LdB { x } => write!(f, "LD B, V{:X}", x),
+            LdIVx { x } => write!(f, "LD [I], V{:X}", x),
+            LdVxI { x } => write!(f, "LD V{:X}, [I]", x),
+            Scd(n) => write!(f, "SCD {}", n),
+            Scr => write!(f, "SCR"),
+            Scl => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            LdHf { x } => write!(f, "LD HF, V{:X}", x),
+            LdRVx { x } => write!(f, "LD R, V{:X}", x),
+            LdVxR { x } => write!(f, "LD V{:X}, R", x),
+            Scu(n) => write!(f, "SCU {}", n),
+            LdIVxVy { x, y } => write!(f, "LD [I], V{:X} - V{:X}", x, y),
+            LdVxVyI { x, y } => write!(f, "LD V{:X} - V{:X}, [I]", x, y),
+            LdILong(nnnn) => write!(f, "LD I, long 0x{:04X}", nnnn),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            Pitch { x } => write!(f, "PITCH V{:X}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [Mode; 3] = [Mode::Chip8, Mode::SuperChip, Mode::XoChip];
+
+    #[test]
+    fn test_platform() {
+        assert_eq!(Instruction::Cls.platform(), Mode::Chip8);
+        assert_eq!(
+            Instruction::Drw { x: 0, y: 0, n: 5 }.platform(),
+            Mode::Chip8
+        );
+        assert_eq!(
+            Instruction::Drw { x: 0, y: 0, n: 0 }.platform(),
+            Mode::SuperChip
+        );
+        assert_eq!(Instruction::LdRVx { x: 7 }.platform(), Mode::SuperChip);
+        assert_eq!(Instruction::LdVxR { x: 8 }.platform(), Mode::XoChip);
+        assert_eq!(Instruction::Plane(3).platform(), Mode::XoChip);
+    }
+
+    #[test]
+    fn test_decode_encode_round_trip_all_opcodes() {
+        for mode in MODES {
+            for op in 0..=u16::MAX {
+                if let Some(ins) = Instruction::decode(op, mode) {
+                    assert_eq!(ins.encode(), op, "{:?} {:04X}", mode, op);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_base_instructions() {
+        assert_eq!(
+            Instruction::decode(0x00E0, Mode::Chip8),
+            Some(Instruction::Cls)
+        );
+        assert_eq!(
+            Instruction::decode(0x1ABC, Mode::Chip8),
+            Some(Instruction::Jp(0xABC))
+        );
+        assert_eq!(
+            Instruction::decode(0x3A12, Mode::Chip8),
+            Some(Instruction::Se { x: 0xA, kk: 0x12 })
+        );
+        assert_eq!(
+            Instruction::decode(0xD125, Mode::Chip8),
+            Some(Instruction::Drw { x: 1, y: 2, n: 5 })
+        );
+        assert_eq!(Instruction::decode(0x8128, Mode::Chip8), None);
+        assert_eq!(Instruction::decode(0xE1FF, Mode::Chip8), None);
+    }
+
+    #[test]
+    fn test_decode_depends_on_mode() {
+        assert_eq!(
+            Instruction::decode(0x00FF, Mode::Chip8),
+            Some(Instruction::Sys(0x0FF))
+        );
+        assert_eq!(
+            Instruction::decode(0x00FF, Mode::SuperChip),
+            Some(Instruction::High)
+        );
+        assert_eq!(
+            Instruction::decode(0x00D2, Mode::SuperChip),
+            Some(Instruction::Sys(0x0D2))
+        );
+        assert_eq!(
+            Instruction::decode(0x00D2, Mode::XoChip),
+            Some(Instruction::Scu(2))
+        );
+        assert_eq!(Instruction::decode(0x5122, Mode::SuperChip), None);
+        assert_eq!(Instruction::decode(0xF875, Mode::SuperChip), None);
+        assert_eq!(
+            Instruction::decode(0xF875, Mode::XoChip),
+            Some(Instruction::LdRVx { x: 8 })
+        );
+        assert_eq!(Instruction::decode(0xF000, Mode::XoChip), None);
+    }
+
+    #[test]
+    fn test_read_long_load() {
+        let bytes = [0xF0, 0x00, 0xBE, 0xEF];
+        let ins = Instruction::read(&bytes, Mode::XoChip).unwrap();
+
+        assert_eq!(ins, Instruction::LdILong(0xBEEF));
+        assert_eq!(ins.size(), 4);
+        assert_eq!(ins.to_bytes(), bytes);
+        assert_eq!(Instruction::read(&bytes[..3], Mode::XoChip), None);
+        assert_eq!(Instruction::read(&bytes, Mode::Chip8), None);
+    }
+
+    #[test]
+    fn test_display_mnemonics() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x0123, "SYS 0x123"),
+            (0x2345, "CALL 0x345"),
+            (0x4A0F, "SNE VA, 0x0F"),
+            (0x8AB6, "SHR VA, VB"),
+            (0xB300, "JP V0, 0x300"),
+            (0xD12F, "DRW V1, V2, 15"),
+            (0xF30A, "LD V3, K"),
+            (0xF455, "LD [I], V4"),
+            (0xF565, "LD V5, [I]"),
+            (0x00C4, "SCD 4"),
+            (0xF230, "LD HF, V2"),
+            (0x5243, "LD V2 - V4, [I]"),
+            (0xF201, "PLANE 2"),
+        ];
+
+        for (op, text) in cases {
+            let ins = Instruction::decode(op, Mode::XoChip).unwrap();
+            assert_eq!(ins.to_string(), text);
+        }
+        assert_eq!(
+            Instruction::LdILong(0x1234).to_string(),
+            "LD I, long 0x1234"
+        );
+    }
+}
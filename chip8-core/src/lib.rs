@@ -1,12 +1,67 @@
-use rand::Rng;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+mod asm;
+mod audio;
+mod capture;
+mod debug;
+mod disasm;
+mod error;
+mod gdb;
+mod instruction;
+mod mode;
+mod phosphor;
+mod quirks;
+mod render;
+mod rewind;
+mod rng;
+mod state;
+mod sys;
+mod trace;
+
+pub use asm::{Assembler, assemble};
+pub use audio::{Beeper, WavWriter};
+pub use capture::GifRecorder;
+pub use debug::{
+    Access, Breakpoint, Compare, Condition, Debugger, Register, StopReason, Watchpoint,
+};
+pub use disasm::{Disassembly, Syntax};
+pub use error::{AsmError, EmuError, RomError, StateError};
+pub use gdb::GdbServer;
+pub use instruction::Instruction;
+pub use mode::Mode;
+pub use phosphor::{Persistence, Phosphor};
+pub use quirks::{MemoryIncrement, Quirks};
+pub use render::{Image, Palette, Renderer, Rgba};
+pub use rewind::Rewind;
+#[cfg(feature = "rand")]
+pub use rng::ThreadRandom;
+pub use rng::{RandomSource, XorShift64};
+pub use sys::{SysContext, SysHandler, SysPolicy};
+pub use trace::{MemoryWrite, RegisterChange, TraceEntry, TraceFilter, Tracer};
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
-const RAM_SIZE: usize = 4 * 1024;
+/// Display size in SUPER-CHIP high resolution mode.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+pub const RAM_SIZE: usize = 4 * 1024;
+/// Address space of an XO-CHIP machine.
+pub const XO_RAM_SIZE: usize = 64 * 1024;
 const NUM_REGS: usize = 16;
 const STACK_SIZE: usize = 16;
 const NUM_KEYS: usize = 16;
-const START_ADDR: u16 = 0x200;
+pub const START_ADDR: u16 = 0x200;
+/// Largest program image that fits in RAM after `START_ADDR` (CHIP-8 and SUPER-CHIP).
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_ADDR as usize;
+/// Rate at which the delay and sound timers count down.
+pub const TIMER_HZ: u32 = 60;
+const NUM_FLAGS: usize = 16;
+const SCHIP_NUM_FLAGS: usize = 8;
+/// Length of the XO-CHIP audio pattern buffer in bytes (128 one-bit samples).
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+const DEFAULT_PITCH: u8 = 64;
 const FONTSET_SIZE: usize = 80;
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -26,12 +81,87 @@ const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+// The SUPER-CHIP 8x10 font lives right after the small one
+const BIG_FONTSET_ADDR: usize = FONTSET_SIZE;
+const BIG_FONTSET_SIZE: usize = 160;
+const BIG_FONTSET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/// What happened during a single `Emu::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The instruction ran and the program counter moved on.
+    Executed,
+    /// `FX0A` found no key pressed; the same instruction runs again next step.
+    WaitingForKey,
+    /// A sprite was drawn with the `display_wait` quirk on; nothing more runs this frame.
+    WaitingForVblank,
+    /// The program executed `00FD` (EXIT); nothing runs until `reset`.
+    Halted,
+}
+
+/// CPU speed, in instructions executed per second of emulated time.
+///
+/// Rates that are not a multiple of `TIMER_HZ` are spread evenly over the
+/// frames, so e.g. 500 instructions per second alternates between 8 and 9
+/// instructions per frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Speed(u32);
+
+impl Speed {
+    /// Roughly the pace of the original COSMAC VIP interpreter.
+    pub const SLOW: Speed = Speed(500);
+    /// A good default for most CHIP-8 games.
+    pub const NORMAL: Speed = Speed(700);
+    /// For SUPER-CHIP games written for faster HP-48 calculators.
+    pub const FAST: Speed = Speed(1000);
+
+    pub const fn ips(instructions_per_second: u32) -> Self {
+        Speed(instructions_per_second)
+    }
+
+    pub const fn instructions_per_second(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
 
+/// A CHIP-8 machine.
+///
+/// Frontends drive it with `run_frame` once per 60 Hz frame, feed input through `key_down`/`key_up`
+/// (or `set_keys`), and read output from `framebuffer` and `is_beeping`.
 #[allow(dead_code)]
 pub struct Emu {
     pc: u16,
-    ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    ram: Vec<u8>,
+    // first display plane; the only one outside XO-CHIP
+    screen: Vec<bool>,
+    plane2: Vec<bool>,
+    // XO-CHIP planes targeted by drawing, clearing and scrolling, bit 0 = `screen`
+    plane_mask: u8,
+    audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pitch: u8,
     v_reg: [u8; NUM_REGS],
     i_reg: u16,
     sp: u16,
@@ -39,6 +169,19 @@ pub struct Emu {
     keys: [bool; NUM_KEYS],
     dt: u8,
     st: u8,
+    rom: Vec<u8>,
+    mode: Mode,
+    hires: bool,
+    halted: bool,
+    // SUPER-CHIP/XO-CHIP RPL user flags, kept across resets like the HP-48 kept them
+    flags: [u8; NUM_FLAGS],
+    quirks: Quirks,
+    sys: SysPolicy,
+    rng: Box<dyn RandomSource>,
+    speed: Speed,
+    // Instructions owed to the current frame, in 1/TIMER_HZ units
+    cycle_acc: u32,
+    tracer: Option<Tracer>,
 }
 
 impl Default for Emu {
@@ -51,8 +194,12 @@ impl Emu {
     pub fn new() -> Self {
         let mut new_emu = Self {
             pc: START_ADDR,
-            ram: [0; RAM_SIZE],
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            ram: vec![0; RAM_SIZE],
+            screen: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            plane2: vec![false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            plane_mask: 1,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
             v_reg: [0; NUM_REGS],
             i_reg: 0,
             sp: 0,
@@ -60,15 +207,80 @@ impl Emu {
             keys: [false; NUM_KEYS],
             dt: 0,
             st: 0,
+            rom: Vec::new(),
+            mode: Mode::Chip8,
+            hires: false,
+            halted: false,
+            flags: [0; NUM_FLAGS],
+            quirks: Quirks::default(),
+            sys: SysPolicy::default(),
+            rng: Box::new(XorShift64::from_entropy()),
+            speed: Speed::default(),
+            cycle_acc: 0,
+            tracer: None,
         };
-        new_emu.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        new_emu.load_fonts();
         new_emu
     }
 
+    /// Creates a machine whose `CXKK` results are fully determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        let mut emu = Self::new();
+        emu.seed(seed);
+        emu
+    }
+
+    /// Restarts the default generator from `seed`, replacing any custom source.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Box::new(XorShift64::new(seed));
+    }
+
+    /// Replaces the generator used by `CXKK`.
+    pub fn set_rng(&mut self, rng: Box<dyn RandomSource>) {
+        self.rng = rng;
+    }
+
+    /// Creates a machine running `mode`, with the quirks that platform expects.
+    pub fn with_mode(mode: Mode) -> Self {
+        let mut emu = Self::new();
+        emu.mode = mode;
+        emu.quirks = mode.default_quirks();
+        emu.reset();
+        emu
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Creates a machine that interprets ambiguous opcodes according to `quirks`.
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        let mut emu = Self::new();
+        emu.quirks = quirks;
+        emu
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Chooses how `0NNN` machine code calls are handled. The default is `SysPolicy::Error`.
+    pub fn set_sys_policy(&mut self, policy: SysPolicy) {
+        self.sys = policy;
+    }
+
     pub fn reset(&mut self) {
         self.pc = START_ADDR;
-        self.ram = [0; RAM_SIZE];
-        self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+        self.ram = vec![0; self.mode.ram_size()];
+        self.set_hires(false);
+        self.plane_mask = 1;
+        self.audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        self.pitch = DEFAULT_PITCH;
+        self.halted = false;
         self.v_reg = [0; NUM_REGS];
         self.i_reg = 0;
         self.sp = 0;
@@ -76,393 +288,2010 @@ impl Emu {
         self.keys = [false; NUM_KEYS];
         self.dt = 0;
         self.st = 0;
+        self.cycle_acc = 0;
+        self.load_fonts();
+        self.copy_rom();
+    }
+
+    fn load_fonts(&mut self) {
         self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        if self.mode != Mode::Chip8 {
+            self.ram[BIG_FONTSET_ADDR..BIG_FONTSET_ADDR + BIG_FONTSET_SIZE]
+                .copy_from_slice(&BIG_FONTSET);
+        }
     }
 
-    #[allow(dead_code)]
-    fn push(&mut self, val: u16) {
+    /// Loads a program image at `START_ADDR` and resets the machine.
+    ///
+    /// The image is kept so that later calls to `reset` restart the same
+    /// program instead of leaving RAM empty.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        let max = self.max_rom_size();
+        if rom.len() > max {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.rom = rom.to_vec();
+        self.reset();
+        Ok(())
+    }
+
+    /// Largest program image `load_rom` accepts in the current mode.
+    pub fn max_rom_size(&self) -> usize {
+        self.ram.len() - START_ADDR as usize
+    }
+
+    /// Reads a program image from disk and loads it with `load_rom`.
+    pub fn load_rom_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        let rom = fs::read(path)?;
+        self.load_rom(&rom)
+    }
+
+    fn copy_rom(&mut self) {
+        let start = START_ADDR as usize;
+        self.ram[start..start + self.rom.len()].copy_from_slice(&self.rom);
+    }
+
+    fn push(&mut self, val: u16) -> Result<(), EmuError> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(EmuError::StackOverflow {
+                addr: self.op_addr(),
+            });
+        }
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        Ok(())
     }
 
-    #[allow(dead_code)]
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, EmuError> {
+        if self.sp == 0 {
+            return Err(EmuError::StackUnderflow {
+                addr: self.op_addr(),
+            });
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
+    }
+
+    /// Address of the instruction being executed (`fetch` has already moved past it).
+    fn op_addr(&self) -> u16 {
+        self.pc.wrapping_sub(2)
+    }
+
+    /// Returns the RAM range `start..start + len`, or an error if any of it lies past the end of RAM.
+    fn mem_range(&self, start: usize, len: usize) -> Result<Range<usize>, EmuError> {
+        let end = start + len;
+        if end > self.ram.len() {
+            return Err(EmuError::MemoryOutOfBounds {
+                addr: self.op_addr(),
+                access: start.max(self.ram.len()),
+            });
+        }
+        Ok(start..end)
+    }
+
+    pub fn speed(&self) -> Speed {
+        self.speed
     }
 
-    pub fn tick(&mut self) {
-        let op = self.fetch();
-        self.execute(op);
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.cycle_acc = 0;
+    }
+
+    /// Emulates one 1/60 s frame: runs as many instructions as the current
+    /// `Speed` allows, then counts the timers down once.
+    ///
+    /// Returns the outcome of the last instruction executed. On error the
+    /// frame is cut short and the timers are left untouched.
+    pub fn run_frame(&mut self) -> Result<StepOutcome, EmuError> {
+        let mut outcome = StepOutcome::Executed;
+        for _ in 0..self.frame_budget() {
+            outcome = self.step()?;
+            if matches!(outcome, StepOutcome::WaitingForVblank | StepOutcome::Halted) {
+                break;
+            }
+        }
         self.tick_timers();
+        Ok(outcome)
+    }
+
+    /// Takes one frame's share of the `Speed`: the number of instructions
+    /// to run this frame, carrying the fraction over to the next one.
+    pub(crate) fn frame_budget(&mut self) -> u64 {
+        // Summed in u64 so that speeds close to u32::MAX cannot overflow.
+        let total = self.cycle_acc as u64 + self.speed.0 as u64;
+        self.cycle_acc = (total % TIMER_HZ as u64) as u32;
+        total / TIMER_HZ as u64
+    }
+
+    /// Fetches and executes exactly one instruction. The timers are not
+    /// touched; call `tick_timers` at 60 Hz or use `run_frame`.
+    pub fn step(&mut self) -> Result<StepOutcome, EmuError> {
+        if self.halted {
+            return Ok(StepOutcome::Halted);
+        }
+        if self.tracer.is_some() {
+            return self.step_traced();
+        }
+        self.step_untraced()
+    }
+
+    fn step_untraced(&mut self) -> Result<StepOutcome, EmuError> {
+        let op = self.fetch()?;
+        self.execute(op)
+    }
+
+    fn fetch(&mut self) -> Result<u16, EmuError> {
+        let op = self
+            .read_word(self.pc)
+            .ok_or(EmuError::PcOutOfBounds { pc: self.pc })?;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(op)
+    }
+
+    /// Reads the big-endian word at `addr`, if both of its bytes are in RAM.
+    fn read_word(&self, addr: u16) -> Option<u16> {
+        let addr = addr as usize;
+        if addr + 1 >= self.ram.len() {
+            return None;
+        }
+        Some(((self.ram[addr] as u16) << 8) | self.ram[addr + 1] as u16)
     }
 
-    fn fetch(&mut self) -> u16 {
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let op = (higher_byte << 8) | lower_byte;
-        self.pc += 2;
-        op
+    /// Skips the next instruction, which on XO-CHIP may be the four byte `F000 NNNN`.
+    fn skip(&mut self) {
+        let step = if self.mode == Mode::XoChip && self.read_word(self.pc) == Some(0xF000) {
+            4
+        } else {
+            2
+        };
+        self.pc = self.pc.wrapping_add(step);
+    }
+
+    /// Decodes and runs one opcode; `F000` takes its operand from the word at `pc`.
+    fn execute(&mut self, op: u16) -> Result<StepOutcome, EmuError> {
+        let ins = if op == 0xF000 && self.xo() {
+            let nnnn = self
+                .read_word(self.pc)
+                .ok_or(EmuError::PcOutOfBounds { pc: self.pc })?;
+            self.pc = self.pc.wrapping_add(2);
+            Instruction::LdILong(nnnn)
+        } else {
+            Instruction::decode(op, self.mode).ok_or(EmuError::UnknownOpcode {
+                op,
+                addr: self.op_addr(),
+            })?
+        };
+        self.execute_instruction(ins)
     }
 
-    fn execute(&mut self, op: u16) {
-        let digit1 = (op & 0xF000) >> 12;
-        let digit2 = (op & 0x0F00) >> 8;
-        let digit3 = (op & 0x00F0) >> 4;
-        let digit4 = op & 0x000F;
+    fn execute_instruction(&mut self, ins: Instruction) -> Result<StepOutcome, EmuError> {
+        use Instruction::*;
 
-        #[allow(clippy::match_single_binding)]
-        match (digit1, digit2, digit3, digit4) {
+        match ins {
             // 0000 -- No operation (NOP)
-            (0, 0, 0, 0) => (),
-            // 00E0 -- Clears screen (CLS)
-            (0, 0, 0xE, 0) => {
-                self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
+            Nop => (),
+            // 00E0 -- Clears screen (CLS), only the selected planes on XO-CHIP
+            Cls => {
+                for plane in self.selected_planes() {
+                    self.plane_mut(plane).fill(false);
+                }
+            }
+            // 00DN -- Scroll display N lines up (SCU nibble)
+            Scu(n) => {
+                self.scroll(0, -(n as isize));
+            }
+            // 00CN -- Scroll display N lines down (SCD nibble)
+            Scd(n) => {
+                self.scroll(0, n as isize);
+            }
+            // 00FB -- Scroll display 4 pixels right (SCR)
+            Scr => {
+                self.scroll(4, 0);
+            }
+            // 00FC -- Scroll display 4 pixels left (SCL)
+            Scl => {
+                self.scroll(-4, 0);
+            }
+            // 00FD -- Exit the interpreter (EXIT)
+            Exit => {
+                self.halted = true;
+                return Ok(StepOutcome::Halted);
+            }
+            // 00FE -- Switch to 64x32 low resolution (LOW)
+            Low => {
+                self.set_hires(false);
+            }
+            // 00FF -- Switch to 128x64 high resolution (HIGH)
+            High => {
+                self.set_hires(true);
             }
             // 00EE -- Return from subroutine (RET)
-            (0, 0, 0xE, 0xE) => {
-                let ret_addr = self.pop();
+            Ret => {
+                let ret_addr = self.pop()?;
                 self.pc = ret_addr;
             }
+            // 0NNN -- Call 1802 machine code routine at NNN (SYS addr)
+            Sys(nnn) => {
+                self.sys_call(nnn)?;
+            }
             // 1NNN -- Jump to location NNN (JMP)
-            (1, _, _, _) => {
-                let nnn = op & 0x0FFF;
+            Jp(nnn) => {
                 self.pc = nnn;
             }
             // 2NNN -- Call subroutine at NNN (CALL)
-            (2, _, _, _) => {
-                let nnn = op & 0x0FFF;
-                self.push(self.pc);
+            Call(nnn) => {
+                self.push(self.pc)?;
                 self.pc = nnn;
             }
-            // VXKK -- Skip next instruction if VX=KK
-            (3, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0x00FF) as u8;
-                if self.v_reg[x] == nn {
-                    self.pc += 2;
+            // 3XKK -- Skip next instruction if VX=KK
+            Se { x, kk } => {
+                if self.v_reg[x as usize] == kk {
+                    self.skip();
                 }
             }
-            // BNNN -- jump to location NNN + V0
-            (0xB, _, _, _) => {
-                let nnn = op & 0x0FFF;
-                self.pc = (self.v_reg[0] as u16) + nnn;
-            }
             // 4XKK -- Skip next instruction if VX != kk
-            (4, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = op & 0x00FF;
-                if (self.v_reg[x] as u16) != nn {
-                    self.pc += 2;
+            Sne { x, kk } => {
+                if self.v_reg[x as usize] != kk {
+                    self.skip();
                 }
             }
             // 5XY0 -- Skip next instruction if VX = VY
-            (5, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2;
+            SeReg { x, y } => {
+                if self.v_reg[x as usize] == self.v_reg[y as usize] {
+                    self.skip();
                 }
             }
             // 9XY0 -- Skip next instruction if VX != VY
-            (9, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                if self.v_reg[x] != self.v_reg[y] {
-                    self.pc += 2;
+            SneReg { x, y } => {
+                if self.v_reg[x as usize] != self.v_reg[y as usize] {
+                    self.skip();
+                }
+            }
+            // 5XY2 -- Store VX..VY in RAM starting at I, I unchanged (XO-CHIP)
+            LdIVxVy { x, y } => {
+                let regs = Self::reg_range(x as usize, y as usize);
+                let range = self.mem_range(self.i_reg as usize, regs.len())?;
+                for (addr, reg) in range.zip(regs) {
+                    self.ram[addr] = self.v_reg[reg];
+                }
+            }
+            // 5XY3 -- Load VX..VY from RAM starting at I, I unchanged (XO-CHIP)
+            LdVxVyI { x, y } => {
+                let regs = Self::reg_range(x as usize, y as usize);
+                let range = self.mem_range(self.i_reg as usize, regs.len())?;
+                for (addr, reg) in range.zip(regs) {
+                    self.v_reg[reg] = self.ram[addr];
+                }
+            }
+            // 6XKK -- Set VX = KK
+            Ld { x, kk } => {
+                self.v_reg[x as usize] = kk;
+            }
+            // 7XKK -- Set VX = VX + KK
+            Add { x, kk } => {
+                let x = x as usize;
+                self.v_reg[x] = self.v_reg[x].wrapping_add(kk);
+            }
+            // 8XY0 -- Set VX = VY
+            LdReg { x, y } => {
+                self.v_reg[x as usize] = self.v_reg[y as usize];
+            }
+            // 8XY1 -- Set VX = VX OR VY
+            Or { x, y } => {
+                self.v_reg[x as usize] |= self.v_reg[y as usize];
+                self.reset_vf();
+            }
+            // 8XY2 -- Set VX = VX AND VY
+            And { x, y } => {
+                self.v_reg[x as usize] &= self.v_reg[y as usize];
+                self.reset_vf();
+            }
+            // 8XY3 -- Set VX = VX XOR VY
+            Xor { x, y } => {
+                self.v_reg[x as usize] ^= self.v_reg[y as usize];
+                self.reset_vf();
+            }
+            // 8XY4 -- Set VX = VX + VY, set VF = carry
+            AddReg { x, y } => {
+                let sum = self.v_reg[x as usize] as u16 + self.v_reg[y as usize] as u16;
+                self.v_reg[x as usize] = sum as u8;
+                self.v_reg[0xF] = if sum > 0xFF { 1 } else { 0 };
+            }
+            // 8XY5 -- Set VX = VX - VY, set VF = NOT borrow
+            Sub { x, y } => {
+                let (vx, vy) = (self.v_reg[x as usize], self.v_reg[y as usize]);
+                self.v_reg[x as usize] = vx.wrapping_sub(vy);
+                self.v_reg[0xF] = if vx >= vy { 1 } else { 0 };
+            }
+            // 8XY6 -- Set VX = VY SHR 1 (VX SHR 1 with the shift quirk)
+            Shr { x, y } => {
+                let src = self.v_reg[if self.quirks.shift_vx { x } else { y } as usize];
+                self.v_reg[x as usize] = src >> 1;
+                self.v_reg[0xF] = src & 1;
+            }
+            // 8XY7 -- Set VX = VY - VX, set VF = NOT borrow
+            Subn { x, y } => {
+                let (vx, vy) = (self.v_reg[x as usize], self.v_reg[y as usize]);
+                self.v_reg[x as usize] = vy.wrapping_sub(vx);
+                self.v_reg[0xF] = if vy >= vx { 1 } else { 0 };
+            }
+            // 8XYE -- Set VX = VY SHL 1 (VX SHL 1 with the shift quirk)
+            Shl { x, y } => {
+                let src = self.v_reg[if self.quirks.shift_vx { x } else { y } as usize];
+                self.v_reg[x as usize] = src << 1;
+                self.v_reg[0xF] = (src >> 7) & 1;
+            }
+            // ANNN -- Set I = NNN
+            LdI(nnn) => {
+                self.i_reg = nnn;
+            }
+            // BNNN -- jump to location NNN + V0 (BXNN -- XNN + VX with the jump quirk)
+            JpV0(nnn) => {
+                let x = if self.quirks.jump_vx {
+                    (nnn >> 8) as usize
+                } else {
+                    0
+                };
+                self.pc = (self.v_reg[x] as u16) + nnn;
+            }
+            // CXKK (RND) -- Set VX to random byte AND KK
+            Rnd { x, kk } => {
+                let random_byte = self.rng.next_byte();
+
+                self.v_reg[x as usize] = random_byte & kk;
+            }
+            // DXYN -- Display N-byte sprite starting at memory location I at (VX, VY); set VF if collision
+            // DXY0 -- Display 16x16 sprite on SUPER-CHIP
+            Drw { x, y, n } => {
+                let x = self.v_reg[x as usize] as usize;
+                let y = self.v_reg[y as usize] as usize;
+                self.draw_sprite(x, y, n as usize)?;
+
+                if self.quirks.display_wait {
+                    return Ok(StepOutcome::WaitingForVblank);
                 }
             }
             // EX9E -- Skip next instruction if key with the value of Vx is pressed
-            (0xE, _, 9, 0xE) => {
-                let x = digit2 as usize;
-                let key_val = self.v_reg[x] as usize;
+            Skp { x } => {
+                let key_val = self.v_reg[x as usize] as usize;
                 if key_val < NUM_KEYS && self.keys[key_val] {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             // EXA1 -- Skip next instruction if key with the value of VX is not pressed
-            (0xE, _, 0xA, 1) => {
-                let x = digit2 as usize;
-                let key_val = self.v_reg[x] as usize;
+            Sknp { x } => {
+                let key_val = self.v_reg[x as usize] as usize;
                 if key_val < NUM_KEYS && !self.keys[key_val] {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
+            // FX07 -- Store delay timer value in VX
+            LdVxDt { x } => {
+                self.v_reg[x as usize] = self.dt;
+            }
             // FX0A -- Wait for a key press, store the value of the key in VX
-            (0xF, _, 0, 0xA) => {
-                let x = digit2 as usize;
+            LdVxK { x } => {
                 if let Some(key) = (0..self.keys.len()).find(|&i| self.keys[i]) {
-                    self.v_reg[x] = key as u8;
+                    self.v_reg[x as usize] = key as u8;
                 } else {
-                    self.pc -= 2;
+                    self.pc = self.pc.wrapping_sub(2);
+                    return Ok(StepOutcome::WaitingForKey);
                 }
             }
+            // FX15 -- Set delay timer to VX
+            LdDtVx { x } => {
+                self.dt = self.v_reg[x as usize];
+            }
+            // FX18 -- Sets sound timer to VX
+            LdStVx { x } => {
+                self.st = self.v_reg[x as usize];
+            }
             // FX1E -- Set I = I + VX
-            (0xF, _, 1, 0xE) => {
-                let x = digit2 as usize;
-                self.i_reg += self.v_reg[x] as u16;
+            AddI { x } => {
+                self.i_reg = self.i_reg.wrapping_add(self.v_reg[x as usize] as u16);
             }
             // FX29 -- Set I = location of sprite for digit Vx.
-            (0xF, _, 2, 9) => {
-                let x = digit2 as usize;
-                self.i_reg = (self.v_reg[x] as u16) * 5; // font is stored RAM using 5 bytes per character
+            LdF { x } => {
+                self.i_reg = (self.v_reg[x as usize] as u16) * 5; // font is stored RAM using 5 bytes per character
             }
-            // 6XKK -- Set VX = KK
-            (6, _, _, _) => {
-                let x = digit2 as usize;
-                let kk = (op & 0x00FF) as u8;
-                self.v_reg[x] = kk;
+            // FX30 -- Set I = location of the 10-byte big font sprite for digit VX (LD HF, Vx)
+            LdHf { x } => {
+                let digit = (self.v_reg[x as usize] & 0xF) as usize;
+                self.i_reg = (BIG_FONTSET_ADDR + digit * 10) as u16;
             }
-            // 7XKK -- Set VX = VX + KK
-            (7, _, _, _) => {
-                let x = digit2 as usize;
-                let kk = (op & 0x00FF) as u8;
-                self.v_reg[x] = self.v_reg[x].wrapping_add(kk);
+            // FX33 (BCD) -- Store VX as BCD (Binary Coded Decimal) in the I
+            LdB { x } => {
+                let vx = self.v_reg[x as usize];
+                let addr = self.mem_range(self.i_reg as usize, 3)?.start;
+
+                self.ram[addr] = vx / 100;
+                self.ram[addr + 1] = (vx / 10) % 10;
+                self.ram[addr + 2] = vx % 10;
             }
-            (8, _, _, n) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
-                let vx = self.v_reg[x];
-                let vy = self.v_reg[y];
-
-                match n {
-                    // 8XY0 -- Set VX = VY
-                    0 => self.v_reg[x] = vy,
-                    // 8XY1 -- Set VX = VX OR VY
-                    1 => self.v_reg[x] |= vy,
-                    // 8XY2 -- Set VX = VX AND VY
-                    2 => self.v_reg[x] &= vy,
-                    // 8XY3 -- Set VX = VX XOR VY
-                    3 => self.v_reg[x] ^= vy,
-                    // 8XY4 -- Set VX = VX + VY, set VF = carry
-                    4 => {
-                        let sum = vx as u16 + vy as u16;
-                        self.v_reg[x] = sum as u8;
-                        self.v_reg[0xF] = if sum > 0xFF { 1 } else { 0 };
-                    }
-                    // 8XY5 -- Set VX = VX - VY, set VF = NOT borrow
-                    5 => {
-                        self.v_reg[0xF] = if vx >= vy { 1 } else { 0 };
-                        self.v_reg[x] = vx.wrapping_sub(vy);
-                    }
-                    // 8XY6 -- Set VX = VX SHR 1
-                    6 => {
-                        self.v_reg[x] = vy;
-                        self.v_reg[0xF] = vy & 1;
-                        self.v_reg[x] >>= 1;
-                    }
-                    // 8XY7 -- Set VX = VY - VX, set VF = NOT borrow
-                    7 => {
-                        self.v_reg[0xF] = if vy >= vx { 1 } else { 0 };
-                        self.v_reg[x] = vy.wrapping_sub(vx);
-                    }
-                    // 8XYE -- Set VX SHL 1
-                    0xE => {
-                        self.v_reg[x] = vy;
-                        self.v_reg[0xF] = (vy >> 7) & 1;
-                        self.v_reg[x] <<= 1;
+            // FX55 -- Stores V0-VX registers in the RAM starting at I
+            LdIVx { x } => {
+                let x = x as usize;
+                let range = self.mem_range(self.i_reg as usize, x + 1)?;
+                self.ram[range].copy_from_slice(&self.v_reg[..=x]);
+                self.advance_i(x);
+            }
+            // FX65 -- Reads values from memory starting at I to registers V0-VX
+            LdVxI { x } => {
+                let x = x as usize;
+                let range = self.mem_range(self.i_reg as usize, x + 1)?;
+                self.v_reg[..=x].copy_from_slice(&self.ram[range]);
+                self.advance_i(x);
+            }
+            // FX75 -- Store V0-VX in the RPL user flags (LD R, Vx)
+            LdRVx { x } => {
+                let x = x as usize;
+                self.flags[..=x].copy_from_slice(&self.v_reg[..=x]);
+            }
+            // FX85 -- Read V0-VX from the RPL user flags (LD Vx, R)
+            LdVxR { x } => {
+                let x = x as usize;
+                self.v_reg[..=x].copy_from_slice(&self.flags[..=x]);
+            }
+            // F000 NNNN -- Set I = NNNN, the following word (XO-CHIP)
+            LdILong(nnnn) => {
+                self.i_reg = nnnn;
+            }
+            // FN01 -- Select the display planes N for drawing (XO-CHIP)
+            Plane(n) => {
+                self.plane_mask = n & 0b11;
+            }
+            // F002 -- Load the 16 byte audio pattern from RAM at I (XO-CHIP)
+            Audio => {
+                let range = self.mem_range(self.i_reg as usize, AUDIO_PATTERN_SIZE)?;
+                self.audio_pattern.copy_from_slice(&self.ram[range]);
+            }
+            // FX3A -- Set the audio pattern playback pitch to VX (XO-CHIP)
+            Pitch { x } => {
+                self.pitch = self.v_reg[x as usize];
+            }
+        }
+        Ok(StepOutcome::Executed)
+    }
+
+    /// Dispatches a `0NNN` call according to the `SysPolicy`.
+    fn sys_call(&mut self, target: u16) -> Result<(), EmuError> {
+        match &mut self.sys {
+            SysPolicy::Ignore => Ok(()),
+            SysPolicy::Error => Err(EmuError::UnsupportedSys {
+                target,
+                addr: self.op_addr(),
+            }),
+            SysPolicy::Handler(handler) => handler.call(
+                target,
+                SysContext {
+                    v_reg: &mut self.v_reg,
+                    i_reg: &mut self.i_reg,
+                    ram: &mut self.ram,
+                    dt: &mut self.dt,
+                    st: &mut self.st,
+                },
+            ),
+        }
+    }
+
+    /// Whether the SUPER-CHIP opcodes are available.
+    fn schip(&self) -> bool {
+        self.mode != Mode::Chip8
+    }
+
+    /// Whether the XO-CHIP opcodes are available.
+    fn xo(&self) -> bool {
+        self.mode == Mode::XoChip
+    }
+
+    /// Registers VX..VY for `5XY2`/`5XY3`, in descending order if X > Y.
+    fn reg_range(x: usize, y: usize) -> Vec<usize> {
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
+        }
+    }
+
+    /// Indices (0 or 1) of the display planes selected by `FN01`.
+    fn selected_planes(&self) -> impl Iterator<Item = usize> + use<> {
+        let mask = self.plane_mask;
+        (0..2).filter(move |plane| mask & (1 << plane) != 0)
+    }
+
+    /// The RAM `ins` would read or write through I if it ran now.
+    pub(crate) fn memory_access(&self, ins: Instruction) -> Option<(Range<usize>, Access)> {
+        use Instruction::*;
+
+        let (len, access) = match ins {
+            LdIVx { x } => (x as usize + 1, Access::Write),
+            LdVxI { x } => (x as usize + 1, Access::Read),
+            LdB { .. } => (3, Access::Write),
+            LdIVxVy { x, y } => (x.abs_diff(y) as usize + 1, Access::Write),
+            LdVxVyI { x, y } => (x.abs_diff(y) as usize + 1, Access::Read),
+            Drw { n, .. } => {
+                let bytes = if n == 0 && self.schip() {
+                    32
+                } else {
+                    n as usize
+                };
+                (bytes * self.selected_planes().count(), Access::Read)
+            }
+            Audio => (AUDIO_PATTERN_SIZE, Access::Read),
+            _ => return None,
+        };
+        let start = self.i_reg as usize;
+        Some((start..start + len, access))
+    }
+
+    fn plane_mut(&mut self, plane: usize) -> &mut Vec<bool> {
+        if plane == 0 {
+            &mut self.screen
+        } else {
+            &mut self.plane2
+        }
+    }
+
+    /// XORs the sprite at I onto the display at (x, y), setting VF on collision.
+    ///
+    /// `n` rows of 8 pixels are drawn, or with SUPER-CHIP a 16x16 sprite of
+    /// two bytes per row when `n` is zero. On XO-CHIP each selected plane gets
+    /// its own copy of the sprite data, stored one after the other.
+    fn draw_sprite(&mut self, x: usize, y: usize, n: usize) -> Result<(), EmuError> {
+        let (width, height) = (self.screen_width(), self.screen_height());
+        let (cols, rows) = if n == 0 && self.schip() {
+            (16, 16)
+        } else {
+            (8, n)
+        };
+        let bytes_per_row = cols / 8;
+        let sprite_len = rows * bytes_per_row;
+        let planes: Vec<usize> = self.selected_planes().collect();
+        let sprite = self.mem_range(self.i_reg as usize, sprite_len * planes.len())?;
+        let data = self.ram[sprite].to_vec();
+        let clipping = self.quirks.clipping;
+
+        // the starting position always wraps, only the sprite body may be clipped
+        let x_coord = x % width;
+        let y_coord = y % height;
+
+        let mut collision = false;
+        for (plane, plane_data) in planes.into_iter().zip(data.chunks(sprite_len.max(1))) {
+            let buf = self.plane_mut(plane);
+            for (row, row_bytes) in plane_data.chunks(bytes_per_row).enumerate() {
+                let sprite_row = row_bytes.iter().fold(0u16, |acc, &b| (acc << 8) | b as u16);
+                for col in 0..cols {
+                    let pixel = (sprite_row >> (cols - 1 - col)) & 1;
+                    if pixel == 1 {
+                        let mut screen_x = x_coord + col;
+                        let mut screen_y = y_coord + row;
+                        if screen_x >= width || screen_y >= height {
+                            if clipping {
+                                continue;
+                            }
+                            screen_x %= width;
+                            screen_y %= height;
+                        }
+                        let index = screen_y * width + screen_x;
+
+                        if buf[index] {
+                            collision = true;
+                        }
+
+                        buf[index] ^= true;
                     }
-                    _ => unimplemented!("Unimplemented 8XY{} opcode", n),
                 }
             }
-            // ANNN -- Set I = NNN
-            (0xA, _, _, _) => {
-                let nnn = op & 0x0FFF;
-                self.i_reg = nnn;
+        }
+        self.v_reg[0xF] = collision as u8;
+        Ok(())
+    }
+
+    /// Moves the selected planes by (dx, dy) pixels, filling the uncovered area with unlit pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.screen_width() as isize, self.screen_height() as isize);
+        for plane in self.selected_planes() {
+            let buf = self.plane_mut(plane);
+            let old = buf.clone();
+            for y in 0..height {
+                for x in 0..width {
+                    let (src_x, src_y) = (x - dx, y - dy);
+                    let lit = (0..width).contains(&src_x)
+                        && (0..height).contains(&src_y)
+                        && old[(src_y * width + src_x) as usize];
+                    buf[(y * width + x) as usize] = lit;
+                }
             }
-            // DXYN -- Display N-byte sprite starting at memory location I at (VX, VY); set VF if collision
-            (0xD, _, _, n) => {
-                let x_coord = self.v_reg[digit2 as usize] as usize;
-                let y_coord = self.v_reg[digit3 as usize] as usize;
-                let i = self.i_reg as usize;
-                let height = n as usize;
-
-                self.v_reg[0xF] = 0; // initially no collision
-
-                for row in 0..height {
-                    let sprite_row = self.ram[i + row];
-                    for col in 0..8 {
-                        let pixel = (sprite_row >> (7 - col)) & 1;
-                        if pixel == 1 {
-                            let screen_x = (x_coord + col) % SCREEN_WIDTH;
-                            let screen_y = (y_coord + row) % SCREEN_HEIGHT;
-                            let index = screen_y * SCREEN_WIDTH + screen_x;
-
-                            if self.screen[index] {
-                                self.v_reg[0xF] = 1;
-                            }
+        }
+    }
+
+    /// Switches between 64x32 and 128x64 resolution, clearing both planes.
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.screen = vec![false; self.screen_width() * self.screen_height()];
+        self.plane2 = vec![false; self.screen.len()];
+    }
+
+    /// Clears VF after a logic instruction if the `vf_reset` quirk is on.
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v_reg[0xF] = 0;
+        }
+    }
+
+    /// Moves I past registers V0-VX after `FX55`/`FX65` as the quirks require.
+    fn advance_i(&mut self, x: usize) {
+        let step = match self.quirks.memory_increment {
+            MemoryIncrement::Unchanged => return,
+            MemoryIncrement::ByX => x,
+            MemoryIncrement::ByXPlusOne => x + 1,
+        };
+        self.i_reg = self.i_reg.wrapping_add(step as u16);
+    }
+
+    /// Counts the delay and sound timers down by one. Call at `TIMER_HZ`.
+    ///
+    /// The buzzer sounds while the sound timer runs; a `Beeper` turns that
+    /// into samples.
+    pub fn tick_timers(&mut self) {
+        if self.dt > 0 {
+            self.dt -= 1;
+        }
+        if self.st > 0 {
+            self.st -= 1;
+        }
+    }
+
+    /// The display as one `bool` per pixel, row by row, `true` meaning lit.
+    ///
+    /// The slice holds `screen_width() * screen_height()` pixels; pixel (x, y)
+    /// is at `y * screen_width() + x`. Its size changes when a SUPER-CHIP
+    /// program switches resolution.
+    pub fn framebuffer(&self) -> &[bool] {
+        &self.screen
+    }
+
+    /// The display as 2-bit colour indices, one per pixel in the same order as
+    /// `framebuffer`: bit 0 is the first plane, bit 1 the XO-CHIP second plane.
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        self.screen
+            .iter()
+            .zip(&self.plane2)
+            .map(|(&p1, &p2)| p1 as u8 | (p2 as u8) << 1)
+    }
+
+    /// Current display width: `SCREEN_WIDTH`, or `HIRES_WIDTH` in high resolution.
+    pub fn screen_width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            SCREEN_WIDTH
+        }
+    }
+
+    /// Current display height: `SCREEN_HEIGHT`, or `HIRES_HEIGHT` in high resolution.
+    pub fn screen_height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            SCREEN_HEIGHT
+        }
+    }
+
+    /// Whether a SUPER-CHIP program switched to the 128x64 display.
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Whether the program has stopped itself with `00FD`.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Marks keypad key `key` (0x0-0xF) as held. Out of range keys are ignored.
+    pub fn key_down(&mut self, key: u8) {
+        if let Some(state) = self.keys.get_mut(key as usize) {
+            *state = true;
+        }
+    }
+
+    /// Marks keypad key `key` (0x0-0xF) as released. Out of range keys are ignored.
+    pub fn key_up(&mut self, key: u8) {
+        if let Some(state) = self.keys.get_mut(key as usize) {
+            *state = false;
+        }
+    }
+
+    /// Replaces the whole keypad state; bit `n` of `mask` holds key `n`.
+    pub fn set_keys(&mut self, mask: u16) {
+        for (key, state) in self.keys.iter_mut().enumerate() {
+            *state = mask & (1 << key) != 0;
+        }
+    }
+
+    /// Returns whether keypad key `key` is currently held.
+    pub fn is_key_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+
+    /// Returns whether the buzzer should sound, i.e. the sound timer is running.
+    pub fn is_beeping(&self) -> bool {
+        self.st > 0
+    }
+
+    /// The XO-CHIP audio pattern: 128 one-bit samples, most significant bit first,
+    /// played in a loop while the sound timer runs. All zero until `F002` loads one.
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+
+    /// The XO-CHIP pitch register set by `FX3A`, 64 by default.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Rate in samples per second at which the audio pattern bits are played,
+    /// `4000 * 2^((pitch - 64) / 48)`.
+    pub fn audio_playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    /// Current value of the delay timer.
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    /// Current value of the sound timer.
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    /// Address of the next instruction to execute.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i_reg(&self) -> u16 {
+        self.i_reg
+    }
+
+    /// V0 to VF.
+    pub fn v_reg(&self) -> &[u8; NUM_REGS] {
+        &self.v_reg
+    }
+
+    /// Number of return addresses on the stack.
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    /// Return addresses pushed by `CALL`, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// RAM for debuggers and tools to patch.
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    #[allow(dead_code)]
+    pub fn dump_ram(&self) {
+        println!("CHIP-8 RAM Dump (0x000 - 0x{:03X}):", self.ram.len() - 1);
+        for addr in (0..self.ram.len()).step_by(16) {
+            print!("{:04X}: ", addr);
+
+            for i in 0..16 {
+                if addr + i < self.ram.len() {
+                    print!("{:02X} ", self.ram[addr + i]);
+                } else {
+                    print!("   ");
+                }
+            }
+
+            println!();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initialization() {
+        let emu = Emu::new();
+
+        assert_eq!(emu.pc, START_ADDR);
+        assert!(emu.v_reg.iter().all(|&v| v == 0));
+        assert_eq!(emu.i_reg, 0);
+        assert_eq!(emu.sp, 0);
+        assert!(emu.stack.iter().all(|&v| v == 0));
+        assert!(emu.keys.iter().all(|&k| !k));
+        assert!(emu.screen.iter().all(|&pixel| !pixel));
+    }
+
+    #[test]
+    fn test_ram_head_contains_fontset() {
+        let emu = Emu::new();
+
+        assert_eq!(emu.ram[..FONTSET_SIZE], FONTSET);
+    }
+
+    #[test]
+    fn test_ram_tail_initially_zero() {
+        let emu = Emu::new();
+
+        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_screen_dimensions() {
+        let emu = Emu::new();
+
+        assert_eq!(emu.screen.len(), SCREEN_HEIGHT * SCREEN_WIDTH);
+        assert_eq!(emu.screen.len(), 2048);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut emu = Emu::new();
+
+        // Modify state
+        emu.pc = 0x300;
+        emu.v_reg[0] = 42;
+        emu.dt = 10;
+        emu.st = 5;
+        emu.screen[0] = true;
+        emu.ram[100] = 0xFF;
+
+        // Reset state
+        emu.reset();
+
+        assert_eq!(emu.pc, START_ADDR);
+        assert!(emu.v_reg.iter().all(|&v| v == 0));
+        assert_eq!(emu.dt, 0);
+        assert_eq!(emu.st, 0);
+        assert!(emu.screen.iter().all(|&pixel| !pixel));
+        assert_eq!(emu.ram[..FONTSET_SIZE], FONTSET); // fontset still loaded
+        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&b| b == 0)); // rest of the ram still zero
+    }
+
+    #[test]
+    fn test_load_rom_copies_to_start_addr() {
+        let mut emu = Emu::new();
+
+        emu.load_rom(&[0x12, 0x34, 0x56]).unwrap();
+
+        assert_eq!(emu.ram[0x200..0x203], [0x12, 0x34, 0x56]);
+        assert_eq!(emu.ram[0x203], 0);
+        assert_eq!(emu.pc, START_ADDR);
+    }
+
+    #[test]
+    fn test_load_rom_max_size() {
+        let mut emu = Emu::new();
+        let rom = vec![0xAB; MAX_ROM_SIZE];
+
+        emu.load_rom(&rom).unwrap();
+
+        assert_eq!(emu.ram[RAM_SIZE - 1], 0xAB);
+    }
+
+    #[test]
+    fn test_load_rom_too_large() {
+        let mut emu = Emu::new();
+        let rom = vec![0xAB; MAX_ROM_SIZE + 1];
+
+        let err = emu.load_rom(&rom).unwrap_err();
+
+        assert!(matches!(
+            err,
+            RomError::TooLarge { size, max } if size == MAX_ROM_SIZE + 1 && max == MAX_ROM_SIZE
+        ));
+        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&b| b == 0)); // nothing copied
+    }
+
+    #[test]
+    fn test_load_rom_replaces_previous_rom() {
+        let mut emu = Emu::new();
+
+        emu.load_rom(&[0x11, 0x22, 0x33, 0x44]).unwrap();
+        emu.load_rom(&[0xAA]).unwrap();
+
+        assert_eq!(emu.ram[0x200..0x204], [0xAA, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_reset_keeps_loaded_rom() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x42]).unwrap();
+
+        emu.ram[0x200] = 0x00; // program modified itself
+        emu.ram[0x300] = 0xFF;
+        emu.v_reg[0] = 7;
+        emu.reset();
+
+        assert_eq!(emu.ram[0x200..0x202], [0x60, 0x42]);
+        assert_eq!(emu.ram[0x300], 0);
+        assert_eq!(emu.v_reg[0], 0);
+    }
+
+    #[test]
+    fn test_load_rom_file() {
+        let path = std::env::temp_dir().join("chip8_core_test_load_rom_file.ch8");
+        fs::write(&path, [0xA2, 0x2A]).unwrap();
+
+        let mut emu = Emu::new();
+        emu.load_rom_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(emu.ram[0x200..0x202], [0xA2, 0x2A]);
+    }
+
+    #[test]
+    fn test_load_rom_file_missing() {
+        let mut emu = Emu::new();
+
+        let err = emu
+            .load_rom_file("/nonexistent/chip8_core_missing.ch8")
+            .unwrap_err();
+
+        assert!(matches!(err, RomError::Io(_)));
+    }
+
+    #[test]
+    fn test_load_rom_file_pong2() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../roms/PONG2");
+        let rom = fs::read(path).unwrap();
+
+        let mut emu = Emu::new();
+        emu.load_rom_file(path).unwrap();
+
+        assert_eq!(emu.ram[0x200..0x200 + rom.len()], rom[..]);
+    }
+
+    #[test]
+    fn test_framebuffer_reflects_screen() {
+        let mut emu = Emu::new();
+        emu.screen[3 * SCREEN_WIDTH + 7] = true;
+
+        let fb = emu.framebuffer();
+
+        assert_eq!(fb.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert!(fb[3 * SCREEN_WIDTH + 7]);
+        assert_eq!(fb.iter().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_key_down_and_up() {
+        let mut emu = Emu::new();
+
+        emu.key_down(0xA);
+        assert!(emu.keys[0xA]);
+        assert!(emu.is_key_down(0xA));
+
+        emu.key_up(0xA);
+        assert!(!emu.keys[0xA]);
+        assert!(!emu.is_key_down(0xA));
+    }
+
+    #[test]
+    fn test_key_out_of_range_ignored() {
+        let mut emu = Emu::new();
+
+        emu.key_down(16);
+        emu.key_up(0xFF);
+
+        assert!(emu.keys.iter().all(|&k| !k));
+        assert!(!emu.is_key_down(16));
+    }
+
+    #[test]
+    fn test_set_keys_bitmask() {
+        let mut emu = Emu::new();
+        emu.keys[2] = true;
+
+        emu.set_keys(0b1000_0000_0000_0011);
+
+        assert!(emu.keys[0]);
+        assert!(emu.keys[1]);
+        assert!(!emu.keys[2]); // cleared by the new mask
+        assert!(emu.keys[15]);
+        assert_eq!(emu.keys.iter().filter(|&&k| k).count(), 3);
+    }
+
+    #[test]
+    fn test_key_down_satisfies_ex9e() {
+        let mut emu = Emu::new();
+        emu.pc = 0x200;
+        emu.ram[0x200] = 0xE0;
+        emu.ram[0x201] = 0x9E;
+        emu.v_reg[0] = 0x7;
+
+        emu.key_down(0x7);
+        emu.step().unwrap();
+
+        assert_eq!(emu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_is_beeping_follows_sound_timer() {
+        let mut emu = Emu::new();
+        assert!(!emu.is_beeping());
+
+        emu.st = 2;
+        assert!(emu.is_beeping());
+        assert_eq!(emu.sound_timer(), 2);
+
+        emu.tick_timers();
+        emu.tick_timers();
+        assert!(!emu.is_beeping());
+    }
+
+    #[test]
+    fn test_delay_timer_accessor() {
+        let mut emu = Emu::new();
+        emu.dt = 33;
+
+        assert_eq!(emu.delay_timer(), 33);
+    }
+
+    #[test]
+    fn test_step_does_not_touch_timers() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x01, 0x61, 0x02]).unwrap();
+        emu.dt = 10;
+        emu.st = 10;
+
+        emu.step().unwrap();
+        emu.step().unwrap();
+
+        assert_eq!(emu.dt, 10);
+        assert_eq!(emu.st, 10);
+        assert_eq!(emu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_run_frame_executes_speed_worth_of_instructions() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x70, 0x01, 0x12, 0x00]).unwrap(); // V0 += 1; JP 0x200
+        emu.set_speed(Speed::ips(600));
+        emu.dt = 3;
+
+        emu.run_frame().unwrap();
+
+        assert_eq!(emu.v_reg[0], 5); // 10 instructions, half of them ADDs
+        assert_eq!(emu.dt, 2); // timers tick once per frame
+    }
+
+    #[test]
+    fn test_run_frame_spreads_fractional_speed() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x70, 0x01].repeat(100)).unwrap(); // V0 += 1, over and over
+        emu.set_speed(Speed::SLOW);
+
+        let mut per_frame = Vec::new();
+        for _ in 0..6 {
+            let before = emu.v_reg[0];
+            emu.run_frame().unwrap();
+            per_frame.push(emu.v_reg[0] - before);
+        }
+
+        assert_eq!(per_frame, [8, 8, 9, 8, 8, 9]); // 8.33 instructions per frame
+    }
+
+    #[test]
+    fn test_run_frame_one_second_matches_speed() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0xF0, 0x1E].repeat(500)).unwrap(); // I += V0, over and over
+        emu.v_reg[0] = 1;
+        emu.set_speed(Speed::SLOW);
+        emu.dt = 60;
+        emu.st = 30;
+
+        for _ in 0..TIMER_HZ {
+            emu.run_frame().unwrap();
+        }
+
+        assert_eq!(emu.i_reg, 500);
+        assert_eq!(emu.dt, 0);
+        assert_eq!(emu.st, 0);
+    }
+
+    #[test]
+    fn test_run_frame_zero_speed_only_ticks_timers() {
+        let mut emu = Emu::new();
+        emu.set_speed(Speed::ips(0));
+        emu.dt = 2;
+
+        emu.run_frame().unwrap();
+
+        assert_eq!(emu.pc, START_ADDR);
+        assert_eq!(emu.dt, 1);
+    }
+
+    #[test]
+    fn test_run_frame_top_speed_does_not_overflow() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        // EXIT, so the frame ends after the first instruction.
+        emu.load_rom(&[0x00, 0xFD]).unwrap();
+        emu.set_speed(Speed::ips(u32::MAX));
+
+        for _ in 0..3 {
+            assert_eq!(emu.run_frame().unwrap(), StepOutcome::Halted);
+            assert!(emu.cycle_acc < TIMER_HZ);
+        }
+    }
+
+    #[test]
+    fn test_run_frame_stops_on_error() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x01, 0xFF, 0xFF]).unwrap();
+        emu.dt = 5;
+
+        assert_eq!(
+            emu.run_frame(),
+            Err(EmuError::UnknownOpcode {
+                op: 0xFFFF,
+                addr: 0x202
+            })
+        );
+        assert_eq!(emu.v_reg[0], 1);
+        assert_eq!(emu.dt, 5);
+    }
+
+    #[test]
+    fn test_run_frame_reports_waiting_for_key() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0xF0, 0x0A]).unwrap();
+
+        assert_eq!(emu.run_frame(), Ok(StepOutcome::WaitingForKey));
+        assert_eq!(emu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_default_speed() {
+        let emu = Emu::new();
+
+        assert_eq!(emu.speed(), Speed::NORMAL);
+        assert_eq!(Speed::NORMAL.instructions_per_second(), 700);
+    }
+
+    #[test]
+    fn test_default_quirks() {
+        let emu = Emu::new();
+
+        assert_eq!(emu.quirks(), Quirks::default());
+    }
+
+    #[test]
+    fn test_with_quirks() {
+        let emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+
+        assert_eq!(emu.quirks(), Quirks::COSMAC_VIP);
+        assert_eq!(emu.pc, START_ADDR);
+        assert_eq!(emu.ram[..FONTSET_SIZE], FONTSET);
+    }
+
+    #[test]
+    fn test_reset_keeps_quirks() {
+        let mut emu = Emu::with_quirks(Quirks::SCHIP_1_1);
+
+        emu.reset();
+
+        assert_eq!(emu.quirks(), Quirks::SCHIP_1_1);
+    }
+
+    #[test]
+    fn test_quirk_shift_vx_shr() {
+        let mut emu = Emu::with_quirks(Quirks {
+            shift_vx: true,
+            ..Quirks::default()
+        });
+        emu.v_reg[0x1] = 0b0000_0011;
+        emu.v_reg[0x2] = 0b1000_0000;
+
+        emu.execute(0x8126).unwrap();
+
+        assert_eq!(emu.v_reg[0x1], 0b0000_0001); // VY ignored
+        assert_eq!(emu.v_reg[0xF], 1);
+        assert_eq!(emu.v_reg[0x2], 0b1000_0000);
+    }
+
+    #[test]
+    fn test_quirk_shift_vx_shl() {
+        let mut emu = Emu::with_quirks(Quirks {
+            shift_vx: true,
+            ..Quirks::default()
+        });
+        emu.v_reg[0x1] = 0b1100_0000;
+        emu.v_reg[0x2] = 0b0000_0001;
+
+        emu.execute(0x812E).unwrap();
+
+        assert_eq!(emu.v_reg[0x1], 0b1000_0000);
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn test_shift_flag_wins_when_x_is_vf() {
+        let mut emu = Emu::new();
+        emu.v_reg[0x1] = 0b0000_0011;
+
+        emu.execute(0x8F16).unwrap();
+
+        assert_eq!(emu.v_reg[0xF], 1); // VF ends up holding the shifted-out bit
+    }
+
+    #[test]
+    fn test_sub_flag_wins_when_x_is_vf() {
+        let mut emu = Emu::new();
+        emu.v_reg[0x1] = 2;
+        emu.v_reg[0xF] = 5;
+
+        emu.execute(0x8F15).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1); // VF ends up holding NOT borrow
+
+        emu.execute(0x8F17).unwrap();
+        assert_eq!(emu.v_reg[0xF], 1);
+    }
+
+    #[test]
+    fn test_quirk_memory_increment_unchanged() {
+        let mut emu = Emu::with_quirks(Quirks::SCHIP_1_1);
+        emu.i_reg = 0x300;
+
+        emu.execute(0xF355).unwrap();
+        assert_eq!(emu.i_reg, 0x300);
+
+        emu.execute(0xF365).unwrap();
+        assert_eq!(emu.i_reg, 0x300);
+    }
+
+    #[test]
+    fn test_quirk_memory_increment_by_x() {
+        let mut emu = Emu::with_quirks(Quirks::CHIP48);
+        emu.i_reg = 0x300;
+
+        emu.execute(0xF355).unwrap();
+        assert_eq!(emu.i_reg, 0x303);
+
+        emu.execute(0xF265).unwrap();
+        assert_eq!(emu.i_reg, 0x305);
+    }
+
+    #[test]
+    fn test_quirk_memory_increment_by_x_plus_one() {
+        let mut emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+        emu.i_reg = 0x300;
+        emu.v_reg[0x0] = 0xAA;
+        emu.v_reg[0x1] = 0xBB;
+
+        emu.execute(0xF155).unwrap();
+        assert_eq!(emu.ram[0x300..0x302], [0xAA, 0xBB]);
+        assert_eq!(emu.i_reg, 0x302);
+
+        emu.i_reg = 0x300;
+        emu.execute(0xF065).unwrap();
+        assert_eq!(emu.i_reg, 0x301);
+    }
+
+    #[test]
+    fn test_quirk_jump_vx() {
+        let mut emu = Emu::with_quirks(Quirks::CHIP48);
+        emu.v_reg[0x0] = 0x01;
+        emu.v_reg[0x2] = 0x10;
+
+        emu.execute(0xB234).unwrap();
+
+        assert_eq!(emu.pc, 0x244); // 0x234 + V2
+    }
+
+    #[test]
+    fn test_quirk_vf_reset() {
+        for op in [0x8121, 0x8122, 0x8123] {
+            let mut emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+            emu.v_reg[0xF] = 0x55;
+            emu.v_reg[0x1] = 0b1100;
+            emu.v_reg[0x2] = 0b1010;
+
+            emu.execute(op).unwrap();
+
+            assert_eq!(emu.v_reg[0xF], 0, "opcode {:04X}", op);
+        }
+    }
+
+    #[test]
+    fn test_no_vf_reset_by_default() {
+        let mut emu = Emu::new();
+        emu.v_reg[0xF] = 0x55;
+
+        emu.execute(0x8121).unwrap();
+
+        assert_eq!(emu.v_reg[0xF], 0x55);
+    }
+
+    #[test]
+    fn test_quirk_clipping_horizontal() {
+        let mut emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+        emu.i_reg = 0x50;
+        emu.ram[0x50] = 0xFF;
+        emu.v_reg[0x0] = 60;
+        emu.v_reg[0x1] = 0;
+
+        emu.execute(0xD011).unwrap();
+
+        for x in 60..64 {
+            assert!(emu.screen[x]);
+        }
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 4);
+    }
+
+    #[test]
+    fn test_quirk_clipping_vertical() {
+        let mut emu = Emu::with_quirks(Quirks::SCHIP_1_1);
+        emu.i_reg = 0x50;
+        emu.ram[0x50] = 0x80;
+        emu.ram[0x51] = 0x80;
+        emu.v_reg[0x0] = 0;
+        emu.v_reg[0x1] = 31;
+
+        emu.execute(0xD012).unwrap();
+
+        assert!(emu.screen[31 * SCREEN_WIDTH]);
+        assert!(!emu.screen[0]);
+    }
+
+    #[test]
+    fn test_quirk_clipping_still_wraps_start_position() {
+        let mut emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+        emu.i_reg = 0x50;
+        emu.ram[0x50] = 0x80;
+        emu.v_reg[0x0] = 64 + 5;
+        emu.v_reg[0x1] = 32 + 2;
+
+        emu.execute(0xD011).unwrap();
+
+        assert!(emu.screen[2 * SCREEN_WIDTH + 5]);
+    }
+
+    #[test]
+    fn test_quirk_display_wait_ends_frame() {
+        let mut emu = Emu::with_quirks(Quirks::COSMAC_VIP);
+        // V0 += 1; DRW V1, V1, 1; JP 0x200
+        emu.load_rom(&[0x70, 0x01, 0xD1, 0x11, 0x12, 0x00]).unwrap();
+
+        assert_eq!(emu.run_frame(), Ok(StepOutcome::WaitingForVblank));
+        assert_eq!(emu.v_reg[0], 1);
+        assert_eq!(emu.pc, 0x204);
+
+        emu.run_frame().unwrap();
+        assert_eq!(emu.v_reg[0], 2);
+    }
+
+    #[test]
+    fn test_no_display_wait_by_default() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x70, 0x01, 0xD1, 0x11, 0x12, 0x00]).unwrap();
+        emu.set_speed(Speed::ips(9 * TIMER_HZ));
+
+        assert_eq!(emu.run_frame(), Ok(StepOutcome::Executed));
+        assert_eq!(emu.v_reg[0], 3);
+    }
+
+    #[test]
+    fn test_with_mode_super_chip() {
+        let emu = Emu::with_mode(Mode::SuperChip);
+
+        assert_eq!(emu.mode(), Mode::SuperChip);
+        assert_eq!(emu.quirks(), Quirks::SCHIP_1_1);
+        assert!(!emu.is_hires());
+        assert_eq!(emu.framebuffer().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert_eq!(
+            emu.ram[BIG_FONTSET_ADDR..BIG_FONTSET_ADDR + BIG_FONTSET_SIZE],
+            BIG_FONTSET
+        );
+    }
+
+    #[test]
+    fn test_chip8_mode_has_no_big_font() {
+        let emu = Emu::with_mode(Mode::Chip8);
+
+        assert_eq!(emu.quirks(), Quirks::default());
+        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_schip_opcodes_unknown_in_chip8_mode() {
+        for op in [0xF030, 0xF075, 0xF085] {
+            let mut emu = Emu::new();
+            emu.pc = 0x202;
+
+            assert_eq!(
+                emu.execute(op),
+                Err(EmuError::UnknownOpcode { op, addr: 0x200 }),
+                "opcode {:04X}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_schip_screen_opcodes_are_sys_calls_in_chip8_mode() {
+        for op in [0x00C1, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF] {
+            let mut emu = Emu::new();
+            emu.pc = 0x202;
+
+            assert_eq!(
+                emu.execute(op),
+                Err(EmuError::UnsupportedSys {
+                    target: op,
+                    addr: 0x200
+                }),
+                "opcode {:04X}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_opcode_00ff_hires() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.screen[0] = true;
+
+        emu.execute(0x00FF).unwrap();
+
+        assert!(emu.is_hires());
+        assert_eq!(emu.screen_width(), HIRES_WIDTH);
+        assert_eq!(emu.screen_height(), HIRES_HEIGHT);
+        assert_eq!(emu.framebuffer().len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(emu.screen.iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn test_opcode_00fe_lores() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.execute(0x00FF).unwrap();
+
+        emu.execute(0x00FE).unwrap();
+
+        assert!(!emu.is_hires());
+        assert_eq!(emu.framebuffer().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn test_reset_returns_to_lores() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.execute(0x00FF).unwrap();
+
+        emu.reset();
+
+        assert!(!emu.is_hires());
+        assert_eq!(emu.framebuffer().len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+    }
+
+    #[test]
+    fn test_hires_draw_uses_full_width() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.execute(0x00FF).unwrap();
+        emu.i_reg = 0x300;
+        emu.ram[0x300] = 0x80;
+        emu.v_reg[0x0] = 100;
+        emu.v_reg[0x1] = 50;
+
+        emu.execute(0xD011).unwrap();
+
+        assert!(emu.screen[50 * HIRES_WIDTH + 100]);
+    }
+
+    #[test]
+    fn test_opcode_dxy0_draws_16x16_sprite() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.execute(0x00FF).unwrap();
+        emu.i_reg = 0x300;
+        for row in 0..16 {
+            emu.ram[0x300 + row * 2] = 0x80; // leftmost pixel
+            emu.ram[0x300 + row * 2 + 1] = 0x01; // rightmost pixel
+        }
+        emu.v_reg[0x0] = 10;
+        emu.v_reg[0x1] = 20;
+
+        emu.execute(0xD010).unwrap();
+
+        for row in 0..16 {
+            assert!(emu.screen[(20 + row) * HIRES_WIDTH + 10]);
+            assert!(emu.screen[(20 + row) * HIRES_WIDTH + 25]);
+        }
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 32);
+        assert_eq!(emu.v_reg[0xF], 0);
+    }
+
+    #[test]
+    fn test_opcode_dxy0_collision() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.i_reg = 0x300;
+        emu.ram[0x300..0x320].fill(0xFF);
+
+        emu.execute(0xD010).unwrap();
+        emu.execute(0xD010).unwrap();
+
+        assert_eq!(emu.v_reg[0xF], 1);
+        assert!(emu.screen.iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn test_opcode_00cn_scroll_down() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.screen[5] = true;
+        emu.screen[31 * SCREEN_WIDTH] = true; // falls off the bottom
+
+        emu.execute(0x00C3).unwrap();
+
+        assert!(emu.screen[3 * SCREEN_WIDTH + 5]);
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_opcode_00fb_scroll_right() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.execute(0x00FF).unwrap();
+        emu.screen[HIRES_WIDTH + 10] = true;
+        emu.screen[HIRES_WIDTH + 126] = true; // falls off the right edge
+
+        emu.execute(0x00FB).unwrap();
+
+        assert!(emu.screen[HIRES_WIDTH + 14]);
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_opcode_00fc_scroll_left() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.screen[SCREEN_WIDTH + 10] = true;
+        emu.screen[SCREEN_WIDTH + 2] = true; // falls off the left edge
+
+        emu.execute(0x00FC).unwrap();
+
+        assert!(emu.screen[SCREEN_WIDTH + 6]);
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_opcode_00fd_exit_halts() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.load_rom(&[0x60, 0x01, 0x00, 0xFD, 0x60, 0x02]).unwrap();
+
+        assert_eq!(emu.run_frame(), Ok(StepOutcome::Halted));
+        assert!(emu.is_halted());
+        assert_eq!(emu.step(), Ok(StepOutcome::Halted));
+        assert_eq!(emu.v_reg[0], 1);
+        assert_eq!(emu.pc, 0x204);
+
+        emu.reset();
+        assert!(!emu.is_halted());
+    }
+
+    #[test]
+    fn test_opcode_fx30_big_font() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.v_reg[0x2] = 7;
+
+        emu.execute(0xF230).unwrap();
+
+        assert_eq!(emu.i_reg as usize, BIG_FONTSET_ADDR + 70);
+        assert_eq!(
+            emu.ram[emu.i_reg as usize..emu.i_reg as usize + 2],
+            [0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_opcode_fx75_fx85_flags() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        for i in 0..8 {
+            emu.v_reg[i] = i as u8 + 1;
+        }
+
+        emu.execute(0xF775).unwrap();
+        emu.v_reg = [0; NUM_REGS];
+        emu.execute(0xF385).unwrap();
+
+        assert_eq!(emu.v_reg[..5], [1, 2, 3, 4, 0]);
+    }
+
+    #[test]
+    fn test_flags_survive_reset() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.v_reg[0] = 0x42;
+        emu.execute(0xF075).unwrap();
+
+        emu.reset();
+        emu.execute(0xF085).unwrap();
+
+        assert_eq!(emu.v_reg[0], 0x42);
+    }
+
+    #[test]
+    fn test_opcode_fx75_only_eight_flags() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.pc = 0x202;
+
+        assert_eq!(
+            emu.execute(0xF875),
+            Err(EmuError::UnknownOpcode {
+                op: 0xF875,
+                addr: 0x200
+            })
+        );
+    }
+
+    #[test]
+    fn test_with_mode_xo_chip() {
+        let emu = Emu::with_mode(Mode::XoChip);
+
+        assert_eq!(emu.quirks(), Quirks::XO_CHIP);
+        assert_eq!(emu.ram.len(), XO_RAM_SIZE);
+        assert_eq!(emu.max_rom_size(), XO_RAM_SIZE - START_ADDR as usize);
+        assert_eq!(emu.pitch(), 64);
+        assert_eq!(emu.audio_pattern(), &[0; AUDIO_PATTERN_SIZE]);
+    }
+
+    #[test]
+    fn test_xo_chip_loads_large_rom() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        let rom = vec![0x5A; 0x8000];
+
+        emu.load_rom(&rom).unwrap();
+
+        assert_eq!(emu.ram[0x200 + 0x7FFF], 0x5A);
+        assert!(Emu::new().load_rom(&rom).is_err());
+    }
+
+    #[test]
+    fn test_xo_opcodes_unknown_in_schip_mode() {
+        for op in [0x5122, 0x5123, 0xF000, 0xF201, 0xF002, 0xF13A] {
+            let mut emu = Emu::with_mode(Mode::SuperChip);
+            emu.pc = 0x202;
+
+            assert_eq!(
+                emu.execute(op),
+                Err(EmuError::UnknownOpcode { op, addr: 0x200 }),
+                "opcode {:04X}",
+                op
+            );
+        }
+    }
+
+    #[test]
+    fn test_opcode_f000_long_i_load() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.load_rom(&[0xF0, 0x00, 0xBE, 0xEF, 0x60, 0x01]).unwrap();
+
+        emu.step().unwrap();
+
+        assert_eq!(emu.i_reg, 0xBEEF);
+        assert_eq!(emu.pc, 0x204);
+        emu.step().unwrap();
+        assert_eq!(emu.v_reg[0], 1);
+    }
+
+    #[test]
+    fn test_xo_skip_jumps_over_long_load() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        // SE V0, 0; I := long 0x1234; V1 := 1
+        emu.load_rom(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x61, 0x01])
+            .unwrap();
+
+        emu.step().unwrap();
+
+        assert_eq!(emu.pc, 0x206);
+        emu.step().unwrap();
+        assert_eq!(emu.v_reg[1], 1);
+        assert_eq!(emu.i_reg, 0);
+    }
+
+    #[test]
+    fn test_schip_skip_is_always_two_bytes() {
+        let mut emu = Emu::with_mode(Mode::SuperChip);
+        emu.load_rom(&[0x30, 0x00, 0xF0, 0x00]).unwrap();
+
+        emu.step().unwrap();
+
+        assert_eq!(emu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_opcode_5xy2_save_range() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x1000;
+        emu.v_reg[2] = 0x22;
+        emu.v_reg[3] = 0x33;
+        emu.v_reg[4] = 0x44;
+
+        emu.execute(0x5242).unwrap();
+
+        assert_eq!(emu.ram[0x1000..0x1003], [0x22, 0x33, 0x44]);
+        assert_eq!(emu.i_reg, 0x1000);
+    }
+
+    #[test]
+    fn test_opcode_5xy2_save_range_reversed() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x1000;
+        emu.v_reg[2] = 0x22;
+        emu.v_reg[3] = 0x33;
+
+        emu.execute(0x5322).unwrap();
+
+        assert_eq!(emu.ram[0x1000..0x1002], [0x33, 0x22]);
+    }
+
+    #[test]
+    fn test_opcode_5xy3_load_range() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0xF000;
+        emu.ram[0xF000..0xF003].copy_from_slice(&[7, 8, 9]);
+
+        emu.execute(0x5683).unwrap();
+
+        assert_eq!(emu.v_reg[5..=9], [0, 7, 8, 9, 0]);
+        assert_eq!(emu.i_reg, 0xF000);
+    }
+
+    #[test]
+    fn test_opcode_fn01_plane_select_and_draw() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x300;
+        emu.ram[0x300] = 0x80; // plane 1 data
+        emu.ram[0x301] = 0x40; // plane 2 data
 
-                            self.screen[index] ^= true;
-                        }
-                    }
-                }
-            }
-            // FX07 -- Store delay timer value in VX
-            (0xF, _, 0, 7) => {
-                let x = digit2 as usize;
-                self.v_reg[x] = self.dt;
-            }
-            // FX15 -- Set delay timer to VX
-            (0xF, _, 1, 5) => {
-                let x = digit2 as usize;
-                self.dt = self.v_reg[x];
-            }
-            // FX18 -- Sets sound timer to VX
-            (0xF, _, 1, 8) => {
-                let x = digit2 as usize;
-                self.st = self.v_reg[x];
-            }
-            // CXKK (RND) -- Set VX to random byte AND KK
-            (0xC, _, _, _) => {
-                let x = digit2 as usize;
-                let kk = (op & 0x00FF) as u8;
+        emu.execute(0xF301).unwrap();
+        emu.execute(0xD011).unwrap();
 
-                let mut rng = rand::rng();
-                let random_byte: u8 = rng.random_range(0..=255);
+        assert!(emu.screen[0]);
+        assert!(emu.plane2[1]);
+        assert!(!emu.plane2[0]);
+        let pixels: Vec<u8> = emu.pixels().take(2).collect();
+        assert_eq!(pixels, [1, 2]);
+    }
 
-                self.v_reg[x] = random_byte & kk;
-            }
-            // FX33 (BCD) -- Store VX as BCD (Binary Coded Decimal) in the I
-            (0xF, _, 3, 3) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x];
-                let addr = self.i_reg as usize;
+    #[test]
+    fn test_draw_only_second_plane() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x300;
+        emu.ram[0x300] = 0x80;
 
-                self.ram[addr] = vx / 100;
-                self.ram[addr + 1] = (vx / 10) % 10;
-                self.ram[addr + 2] = vx % 10;
-            }
-            // FX55 -- Stores V0-VX registers in the RAM starting at I
-            (0xF, _, 5, 5) => {
-                let x = digit2 as usize;
-                let addr = self.i_reg as usize;
-                for i in 0..=x {
-                    self.ram[addr + i] = self.v_reg[i];
-                }
-            }
+        emu.execute(0xF201).unwrap();
+        emu.execute(0xD011).unwrap();
 
-            // FX65 -- Reads values from memory starting at I to registers V0-VX
-            (0xF, _, 6, 5) => {
-                let x = digit2 as usize;
-                let addr = self.i_reg as usize;
-                for i in 0..=x {
-                    self.v_reg[i] = self.ram[addr + i];
-                }
-            }
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {:04X}", op),
-        }
+        assert!(!emu.screen[0]);
+        assert!(emu.plane2[0]);
+        assert_eq!(emu.pixels().next(), Some(2));
     }
 
-    #[allow(dead_code)]
-    fn tick_timers(&mut self) {
-        if self.dt > 0 {
-            self.dt -= 1;
-        }
-        if self.st > 0 {
-            if self.st == 1 {
-                // TODO: Emit beep sound
-            }
-            self.st -= 1;
-        }
+    #[test]
+    fn test_draw_collision_on_second_plane() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x300;
+        emu.ram[0x300] = 0x80;
+        emu.plane2[0] = true;
+
+        emu.execute(0xF201).unwrap();
+        emu.execute(0xD011).unwrap();
+
+        assert_eq!(emu.v_reg[0xF], 1);
     }
 
-    #[allow(dead_code)]
-    pub fn dump_ram(&self) {
-        println!("CHIP-8 RAM Dump (0x000 - 0xFFF):");
-        for addr in (0..RAM_SIZE).step_by(16) {
-            print!("{:04X}: ", addr);
+    #[test]
+    fn test_draw_no_plane_selected() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x300;
+        emu.ram[0x300] = 0xFF;
 
-            for i in 0..16 {
-                if addr + i < RAM_SIZE {
-                    print!("{:02X} ", self.ram[addr + i]);
-                } else {
-                    print!("   ");
-                }
-            }
+        emu.execute(0xF001).unwrap();
+        emu.execute(0xD011).unwrap();
 
-            println!();
-        }
+        assert!(emu.pixels().all(|p| p == 0));
     }
 
-    #[allow(dead_code)]
-    pub fn dump_screen(&self) {
-        println!("CHIP-8 Screen (64x32):");
-        for y in 0..SCREEN_HEIGHT {
-            for x in 0..SCREEN_WIDTH {
-                let idx = y * SCREEN_WIDTH + x;
-                if self.screen[idx] {
-                    print!("■");
-                } else {
-                    print!("⋅");
-                }
-            }
-            println!();
+    #[test]
+    fn test_cls_clears_selected_planes_only() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.screen[0] = true;
+        emu.plane2[0] = true;
+
+        emu.execute(0xF201).unwrap();
+        emu.execute(0x00E0).unwrap();
+
+        assert!(emu.screen[0]);
+        assert!(!emu.plane2[0]);
+    }
+
+    #[test]
+    fn test_opcode_00dn_scroll_up() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.screen[4 * SCREEN_WIDTH + 1] = true;
+        emu.screen[SCREEN_WIDTH] = true; // falls off the top
+
+        emu.execute(0x00D2).unwrap();
+
+        assert!(emu.screen[2 * SCREEN_WIDTH + 1]);
+        assert_eq!(emu.screen.iter().filter(|&&p| p).count(), 1);
+    }
+
+    #[test]
+    fn test_scroll_moves_selected_planes_only() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.screen[0] = true;
+        emu.plane2[0] = true;
+
+        emu.execute(0xF201).unwrap();
+        emu.execute(0x00FB).unwrap();
+
+        assert!(emu.screen[0]);
+        assert!(emu.plane2[4]);
+    }
+
+    #[test]
+    fn test_opcode_f002_audio_pattern() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.i_reg = 0x400;
+        for i in 0..AUDIO_PATTERN_SIZE {
+            emu.ram[0x400 + i] = i as u8;
         }
-        println!("--- End of screen ---");
+
+        emu.execute(0xF002).unwrap();
+
+        assert_eq!(emu.audio_pattern()[..4], [0, 1, 2, 3]);
+        assert_eq!(emu.audio_pattern()[15], 15);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_opcode_fx3a_pitch() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        assert_eq!(emu.audio_playback_rate(), 4000.0);
+        emu.v_reg[0x5] = 112;
+
+        emu.execute(0xF53A).unwrap();
+
+        assert_eq!(emu.pitch(), 112);
+        assert_eq!(emu.audio_playback_rate(), 8000.0); // one octave up
+    }
 
     #[test]
-    fn test_initialization() {
-        let emu = Emu::new();
+    fn test_xo_sixteen_flags() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.v_reg[0xF] = 0x99;
 
-        assert_eq!(emu.pc, START_ADDR);
-        assert!(emu.v_reg.iter().all(|&v| v == 0));
-        assert_eq!(emu.i_reg, 0);
-        assert_eq!(emu.sp, 0);
-        assert!(emu.stack.iter().all(|&v| v == 0));
-        assert!(emu.keys.iter().all(|&k| !k));
-        assert!(emu.screen.iter().all(|&pixel| !pixel));
+        emu.execute(0xFF75).unwrap();
+        emu.v_reg[0xF] = 0;
+        emu.execute(0xFF85).unwrap();
+
+        assert_eq!(emu.v_reg[0xF], 0x99);
     }
 
     #[test]
-    fn test_ram_head_contains_fontset() {
-        let emu = Emu::new();
+    fn test_xo_pc_wraps_at_end_of_memory() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.pc = 0xFFFE;
+        emu.ram[0xFFFE] = 0x60;
+        emu.ram[0xFFFF] = 0x07;
 
-        assert_eq!(emu.ram[..FONTSET_SIZE], FONTSET);
+        emu.step().unwrap();
+
+        assert_eq!(emu.v_reg[0], 7);
+        assert_eq!(emu.pc, 0x0000);
     }
 
     #[test]
-    fn test_ram_tail_initially_zero() {
-        let emu = Emu::new();
+    fn test_hires_clears_both_planes() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.plane2[3] = true;
 
-        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&byte| byte == 0));
+        emu.execute(0x00FF).unwrap();
+
+        assert_eq!(emu.plane2.len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(emu.pixels().all(|p| p == 0));
+    }
+
+    struct AddToV0 {
+        calls: std::rc::Rc<std::cell::Cell<u32>>,
+    }
+
+    impl SysHandler for AddToV0 {
+        fn call(&mut self, addr: u16, ctx: SysContext<'_>) -> Result<(), EmuError> {
+            self.calls.set(self.calls.get() + 1);
+            match addr {
+                0x123 => {
+                    ctx.v_reg[0] += 1;
+                    *ctx.i_reg = 0x456;
+                    ctx.ram[0x456] = ctx.v_reg[0];
+                    Ok(())
+                }
+                _ => Err(EmuError::UnsupportedSys {
+                    target: addr,
+                    addr: 0,
+                }),
+            }
+        }
     }
 
     #[test]
-    fn test_screen_dimensions() {
-        let emu = Emu::new();
+    fn test_opcode_0nnn_errors_by_default() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x01, 0x23]).unwrap();
 
-        assert_eq!(emu.screen.len(), SCREEN_HEIGHT * SCREEN_WIDTH);
-        assert_eq!(emu.screen.len(), 2048);
+        assert_eq!(
+            emu.step(),
+            Err(EmuError::UnsupportedSys {
+                target: 0x123,
+                addr: 0x200
+            })
+        );
     }
 
     #[test]
-    fn test_reset() {
+    fn test_opcode_0nnn_ignored() {
         let mut emu = Emu::new();
+        emu.set_sys_policy(SysPolicy::Ignore);
+        emu.load_rom(&[0x01, 0x23, 0x60, 0x05]).unwrap();
 
-        // Modify state
-        emu.pc = 0x300;
-        emu.v_reg[0] = 42;
-        emu.dt = 10;
-        emu.st = 5;
-        emu.screen[0] = true;
-        emu.ram[100] = 0xFF;
+        assert_eq!(emu.step(), Ok(StepOutcome::Executed));
+        emu.step().unwrap();
 
-        // Reset state
-        emu.reset();
+        assert_eq!(emu.v_reg[0], 5);
+        assert_eq!(emu.pc, 0x204);
+    }
 
-        assert_eq!(emu.pc, START_ADDR);
-        assert!(emu.v_reg.iter().all(|&v| v == 0));
-        assert_eq!(emu.dt, 0);
-        assert_eq!(emu.st, 0);
-        assert!(emu.screen.iter().all(|&pixel| !pixel));
-        assert_eq!(emu.ram[..FONTSET_SIZE], FONTSET); // fontset still loaded
-        assert!(emu.ram[FONTSET_SIZE..].iter().all(|&b| b == 0)); // rest of the ram still zero
+    #[test]
+    fn test_opcode_0nnn_delegated_to_handler() {
+        let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut emu = Emu::new();
+        emu.set_sys_policy(SysPolicy::Handler(Box::new(AddToV0 {
+            calls: calls.clone(),
+        })));
+        emu.load_rom(&[0x01, 0x23, 0x01, 0x23]).unwrap();
+        emu.v_reg[0] = 40;
+
+        emu.step().unwrap();
+        emu.step().unwrap();
+
+        assert_eq!(calls.get(), 2);
+        assert_eq!(emu.v_reg[0], 42);
+        assert_eq!(emu.i_reg, 0x456);
+        assert_eq!(emu.ram[0x456], 42);
+        assert_eq!(emu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_opcode_0nnn_handler_error_propagates() {
+        let mut emu = Emu::new();
+        emu.set_sys_policy(SysPolicy::Handler(Box::new(AddToV0 {
+            calls: Default::default(),
+        })));
+        emu.load_rom(&[0x0A, 0xBC]).unwrap();
+
+        assert!(matches!(
+            emu.step(),
+            Err(EmuError::UnsupportedSys { target: 0xABC, .. })
+        ));
+    }
+
+    #[test]
+    fn test_opcode_0000_still_nop_with_error_policy() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x00, 0x00]).unwrap();
+
+        assert_eq!(emu.step(), Ok(StepOutcome::Executed));
     }
 
     #[test]
@@ -472,7 +2301,7 @@ mod tests {
         emu.ram[0x200] = 0xAB;
         emu.ram[0x201] = 0xCD;
 
-        let op = emu.fetch();
+        let op = emu.fetch().unwrap();
 
         assert_eq!(op, 0xABCD);
         assert_eq!(emu.pc, 0x202);
@@ -482,24 +2311,41 @@ mod tests {
     fn test_push_and_pop() {
         let mut emu = Emu::new();
 
-        emu.push(0x123);
-        emu.push(0x456);
-        emu.push(0x789);
+        emu.push(0x123).unwrap();
+        emu.push(0x456).unwrap();
+        emu.push(0x789).unwrap();
 
         assert_eq!(emu.sp, 3);
 
-        assert_eq!(emu.pop(), 0x789);
-        assert_eq!(emu.pop(), 0x456);
-        assert_eq!(emu.pop(), 0x123);
+        assert_eq!(emu.pop().unwrap(), 0x789);
+        assert_eq!(emu.pop().unwrap(), 0x456);
+        assert_eq!(emu.pop().unwrap(), 0x123);
 
         assert_eq!(emu.sp, 0);
     }
 
     #[test]
-    #[should_panic(expected = "attempt to subtract with overflow")]
     fn test_pop_underflow() {
         let mut emu = Emu::new();
-        emu.pop(); // should panick (sp = 0, sp -= 1 -> underflow)
+        emu.pc = 0x202;
+
+        assert_eq!(emu.pop(), Err(EmuError::StackUnderflow { addr: 0x200 }));
+        assert_eq!(emu.sp, 0);
+    }
+
+    #[test]
+    fn test_push_overflow() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        for i in 0..STACK_SIZE {
+            emu.push(i as u16).unwrap();
+        }
+
+        assert_eq!(
+            emu.push(0xFFF),
+            Err(EmuError::StackOverflow { addr: 0x200 })
+        );
+        assert_eq!(emu.sp as usize, STACK_SIZE);
     }
 
     #[test]
@@ -528,15 +2374,87 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Unimplemented opcode")]
     fn test_execute_unimplemented() {
         let mut emu = Emu::new();
+        emu.pc = 0x302;
 
         // Some unimplemented opcode
         let op: u16 = 0xFFFF;
 
-        // Expected panic with specific communicate
-        emu.execute(op);
+        assert_eq!(
+            emu.execute(op),
+            Err(EmuError::UnknownOpcode {
+                op: 0xFFFF,
+                addr: 0x300
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute_unimplemented_8xy_opcode() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+
+        assert_eq!(
+            emu.execute(0x8128),
+            Err(EmuError::UnknownOpcode {
+                op: 0x8128,
+                addr: 0x200
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_reports_unknown_opcode_address() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x01, 0xFF, 0xFF]).unwrap();
+
+        assert_eq!(emu.step(), Ok(StepOutcome::Executed));
+        assert_eq!(
+            emu.step(),
+            Err(EmuError::UnknownOpcode {
+                op: 0xFFFF,
+                addr: 0x202
+            })
+        );
+    }
+
+    #[test]
+    fn test_fetch_pc_out_of_bounds() {
+        let mut emu = Emu::new();
+        emu.pc = (RAM_SIZE - 1) as u16;
+
+        assert_eq!(
+            emu.step(),
+            Err(EmuError::PcOutOfBounds {
+                pc: (RAM_SIZE - 1) as u16
+            })
+        );
+    }
+
+    #[test]
+    fn test_fetch_last_instruction_in_ram() {
+        let mut emu = Emu::new();
+        emu.pc = (RAM_SIZE - 2) as u16;
+        emu.ram[RAM_SIZE - 2] = 0x61;
+        emu.ram[RAM_SIZE - 1] = 0x05;
+
+        emu.step().unwrap();
+
+        assert_eq!(emu.v_reg[1], 5);
+        assert_eq!(emu.step(), Err(EmuError::PcOutOfBounds { pc: 0x1000 }));
+    }
+
+    #[test]
+    fn test_call_stack_overflow_from_rom() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x22, 0x00]).unwrap(); // calls itself forever
+
+        for _ in 0..STACK_SIZE {
+            emu.step().unwrap();
+        }
+
+        assert_eq!(emu.step(), Err(EmuError::StackOverflow { addr: 0x200 }));
     }
 
     // Opcodes
@@ -546,7 +2464,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.pc = 0x300;
 
-        emu.execute(0x0000);
+        emu.execute(0x0000).unwrap();
 
         assert_eq!(emu.pc, 0x300);
     }
@@ -559,7 +2477,7 @@ mod tests {
         emu.screen[100] = true;
         emu.screen[2047] = true;
 
-        emu.execute(0x00E0);
+        emu.execute(0x00E0).unwrap();
 
         assert!(emu.screen.iter().all(|&pixel| !pixel));
         assert_eq!(emu.pc, START_ADDR);
@@ -569,27 +2487,32 @@ mod tests {
     fn test_opcode_00ee_ret() {
         let mut emu = Emu::new();
 
-        emu.push(0x456);
+        emu.push(0x456).unwrap();
         emu.pc = 0x300;
 
-        emu.execute(0x00EE);
+        emu.execute(0x00EE).unwrap();
 
         assert_eq!(emu.pc, 0x456);
         assert_eq!(emu.sp, 0);
     }
 
     #[test]
-    #[should_panic(expected = "attempt to subtract with overflow")]
     fn test_opcode_00ee_ret_empty_stack() {
         let mut emu = Emu::new();
-        emu.execute(0x00EE);
+        emu.pc = 0x202;
+
+        assert_eq!(
+            emu.execute(0x00EE),
+            Err(EmuError::StackUnderflow { addr: 0x200 })
+        );
+        assert_eq!(emu.pc, 0x202);
     }
 
     #[test]
     fn test_opcode_1nnn_jump() {
         let mut emu = Emu::new();
 
-        emu.execute(0x1ABC);
+        emu.execute(0x1ABC).unwrap();
 
         assert_eq!(emu.pc, 0xABC);
     }
@@ -599,11 +2522,11 @@ mod tests {
         let mut emu = Emu::new();
         emu.pc = 0x300;
 
-        emu.execute(0x2DEF);
+        emu.execute(0x2DEF).unwrap();
 
         assert_eq!(emu.pc, 0xDEF);
         assert_eq!(emu.sp, 1);
-        assert_eq!(emu.pop(), 0x300);
+        assert_eq!(emu.pop().unwrap(), 0x300);
     }
 
     #[test]
@@ -611,7 +2534,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0] = 0x50;
 
-        emu.execute(0xB123);
+        emu.execute(0xB123).unwrap();
 
         assert_eq!(emu.pc, 0x173);
     }
@@ -621,7 +2544,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0] = 0x00;
 
-        emu.execute(0xB000);
+        emu.execute(0xB000).unwrap();
 
         assert_eq!(emu.pc, 0x000);
     }
@@ -636,7 +2559,7 @@ mod tests {
 
         emu.v_reg[0xB] = 0x42;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200 + 4); // +2 (fetch) and +2 (skip)
     }
@@ -647,7 +2570,7 @@ mod tests {
         emu.pc = 0x300;
         emu.v_reg[0x4] = 0xAB;
 
-        emu.execute(0x45AB);
+        emu.execute(0x45AB).unwrap();
 
         assert_eq!(emu.pc, 0x300 + 2); // normal jump
     }
@@ -663,7 +2586,7 @@ mod tests {
         emu.v_reg[0x1] = 0xFE;
         emu.v_reg[0xA] = 0xFE;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200 + 4); // +2 (fetch), +2 (skip)
     }
@@ -679,7 +2602,7 @@ mod tests {
         emu.v_reg[0x1] = 0x21;
         emu.v_reg[0xA] = 0x37;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200 + 2);
     }
@@ -695,7 +2618,7 @@ mod tests {
         emu.v_reg[0xB] = 0x21;
         emu.v_reg[0xC] = 0x21;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200 + 2);
     }
@@ -711,7 +2634,7 @@ mod tests {
         emu.v_reg[0x9] = 0x21;
         emu.v_reg[0x8] = 0x37;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200 + 4);
     }
@@ -727,7 +2650,7 @@ mod tests {
         emu.v_reg[0x4] = 0x9;
         emu.keys[9] = true;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x400 + 4);
     }
@@ -743,7 +2666,7 @@ mod tests {
         emu.v_reg[0xD] = 0x2;
         emu.keys[2] = false;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x300 + 2);
     }
@@ -758,7 +2681,7 @@ mod tests {
 
         emu.v_reg[0x1] = 0xFF; // out of range 0-15
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x400 + 2);
     }
@@ -773,7 +2696,7 @@ mod tests {
 
         emu.keys[0xC] = true;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x3], 0xC);
         assert_eq!(emu.pc, 0x200 + 2);
@@ -787,18 +2710,29 @@ mod tests {
         emu.ram[0x200] = 0xF8;
         emu.ram[0x201] = 0x0A;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x200); // reversed by 2
     }
 
+    #[test]
+    fn test_opcode_fx0a_reports_waiting_for_key() {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0xF0, 0x0A]).unwrap();
+
+        assert_eq!(emu.step(), Ok(StepOutcome::WaitingForKey));
+
+        emu.keys[1] = true;
+        assert_eq!(emu.step(), Ok(StepOutcome::Executed));
+    }
+
     #[test]
     fn test_opcode_fx0a_takes_first_pressed_key() {
         let mut emu = Emu::new();
         emu.keys[5] = true;
         emu.keys[11] = true;
 
-        emu.execute(0xFE0A); // store to V14
+        emu.execute(0xFE0A).unwrap(); // store to V14
 
         assert_eq!(emu.v_reg[0xE], 5); // takes key with lowest index
     }
@@ -812,7 +2746,7 @@ mod tests {
         emu.ram[0x200] = 0x61;
         emu.ram[0x201] = 0x42;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[1], 0x42);
         assert_eq!(emu.pc, 0x202);
@@ -827,7 +2761,7 @@ mod tests {
         emu.ram[0x200] = 0x60;
         emu.ram[0x201] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x0], 0x00);
         assert_eq!(emu.pc, 0x202);
@@ -842,7 +2776,7 @@ mod tests {
         emu.ram[0x300] = 0x6F;
         emu.ram[0x301] = 0xFF;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xF], 0xFF);
         assert_eq!(emu.pc, 0x302);
@@ -860,7 +2794,7 @@ mod tests {
         emu.ram[0x300] = 0x6F;
         emu.ram[0x301] = 0x33;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xF], 0x33);
         assert_eq!(emu.pc, 0x302);
@@ -874,19 +2808,19 @@ mod tests {
 
         emu.ram[0x300] = 0x64;
         emu.ram[0x301] = 0x33;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x4], 0x33);
         assert_eq!(emu.pc, 0x302);
 
         emu.ram[0x302] = 0x64;
         emu.ram[0x303] = 0x42;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x4], 0x42);
         assert_eq!(emu.pc, 0x304);
 
         emu.ram[0x304] = 0x64;
         emu.ram[0x305] = 0x21;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x4], 0x21);
         assert_eq!(emu.pc, 0x306);
     }
@@ -898,19 +2832,19 @@ mod tests {
 
         emu.ram[0x300] = 0x64;
         emu.ram[0x301] = 0x33;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x4], 0x33);
         assert_eq!(emu.pc, 0x302);
 
         emu.ram[0x302] = 0x62;
         emu.ram[0x303] = 0x42;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x2], 0x42);
         assert_eq!(emu.pc, 0x304);
 
         emu.ram[0x304] = 0x6A;
         emu.ram[0x305] = 0x21;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0xA], 0x21);
         assert_eq!(emu.pc, 0x306);
     }
@@ -923,7 +2857,7 @@ mod tests {
         emu.ram[0x201] = 0x45;
         emu.v_reg[0x3] = 0x20;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x3], 0x20 + 0x45);
         assert_eq!(emu.pc, 0x202);
@@ -937,7 +2871,7 @@ mod tests {
         emu.ram[0x301] = 0x00;
         emu.v_reg[0x0] = 0xAB;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x0], 0xAB);
         assert_eq!(emu.pc, 0x302);
@@ -951,7 +2885,7 @@ mod tests {
         emu.ram[0x401] = 0xFF;
         emu.v_reg[0xF] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xF], 0xFF);
         assert_eq!(emu.pc, 0x402);
@@ -965,7 +2899,7 @@ mod tests {
         emu.ram[0x201] = 0x80;
         emu.v_reg[0xA] = 0xFF;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xA], 0x7F); // 0x017F → 0x7F (overflow, 8-bit wrap)
         assert_eq!(emu.pc, 0x202);
@@ -984,13 +2918,13 @@ mod tests {
         emu.ram[0x204] = 0x75;
         emu.ram[0x205] = 0x40;
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x5], 0x30);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x5], 0x60);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x5], 0xA0);
         assert_eq!(emu.pc, 0x206);
     }
@@ -1003,7 +2937,7 @@ mod tests {
         emu.ram[0x201] = 0x20;
         emu.v_reg[0x2] = 0xAB;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x1], 0xAB);
         assert_eq!(emu.pc, 0x202);
@@ -1017,7 +2951,7 @@ mod tests {
         emu.ram[0x301] = 0x00;
         emu.v_reg[0x0] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x3], 0x00);
         assert_eq!(emu.pc, 0x302);
@@ -1031,7 +2965,7 @@ mod tests {
         emu.ram[0x401] = 0x40;
         emu.v_reg[0x4] = 0xFF;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xF], 0xFF);
         assert_eq!(emu.pc, 0x402);
@@ -1046,7 +2980,7 @@ mod tests {
         emu.v_reg[0x5] = 0x12;
         emu.v_reg[0x6] = 0xCD;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x5], 0xCD);
         assert_eq!(emu.pc, 0x202);
@@ -1060,7 +2994,7 @@ mod tests {
         emu.ram[0x201] = 0x80;
         emu.v_reg[0x8] = 0x5A;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x8], 0x5A);
         assert_eq!(emu.pc, 0x202);
@@ -1082,13 +3016,13 @@ mod tests {
         emu.v_reg[0x4] = 0x22;
         emu.v_reg[0x6] = 0x33;
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x1], 0x11);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x3], 0x22);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x5], 0x33);
 
         assert_eq!(emu.pc, 0x206);
@@ -1101,7 +3035,7 @@ mod tests {
         emu.ram[0x200] = 0xA1;
         emu.ram[0x201] = 0x23;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.i_reg, 0x123);
         assert_eq!(emu.pc, 0x202);
@@ -1114,7 +3048,7 @@ mod tests {
         emu.ram[0x300] = 0xA0;
         emu.ram[0x301] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.i_reg, 0x000);
         assert_eq!(emu.pc, 0x302);
@@ -1127,7 +3061,7 @@ mod tests {
         emu.ram[0x400] = 0xAF;
         emu.ram[0x401] = 0xFF;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.i_reg, 0xFFF);
         assert_eq!(emu.pc, 0x402);
@@ -1142,7 +3076,7 @@ mod tests {
         emu.ram[0x200] = 0xA7;
         emu.ram[0x201] = 0x89;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.i_reg, 0x789);
         assert_eq!(emu.pc, 0x202);
@@ -1160,13 +3094,13 @@ mod tests {
         emu.ram[0x204] = 0xA3;
         emu.ram[0x205] = 0x33;
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.i_reg, 0x111);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.i_reg, 0x222);
 
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.i_reg, 0x333);
         assert_eq!(emu.pc, 0x206);
     }
@@ -1180,7 +3114,7 @@ mod tests {
         emu.v_reg[0x1] = 0b1010_1010;
         emu.v_reg[0x2] = 0b1100_1100;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x1], 0b1110_1110);
         assert_eq!(emu.pc, 0x202);
@@ -1195,7 +3129,7 @@ mod tests {
         emu.v_reg[0x3] = 0x00;
         emu.v_reg[0x2] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x3], 0x00);
         assert_eq!(emu.pc, 0x202);
@@ -1210,7 +3144,7 @@ mod tests {
         emu.v_reg[0x4] = 0b1111_0000;
         emu.v_reg[0x2] = 0b1010_1010;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x4], 0b1010_0000);
         assert_eq!(emu.pc, 0x202);
@@ -1225,7 +3159,7 @@ mod tests {
         emu.v_reg[0x5] = 0xFF;
         emu.v_reg[0x2] = 0x00;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x5], 0x00);
         assert_eq!(emu.pc, 0x202);
@@ -1240,7 +3174,7 @@ mod tests {
         emu.v_reg[0x6] = 0b1010_1010;
         emu.v_reg[0x2] = 0b1111_0000;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x6], 0b0101_1010);
         assert_eq!(emu.pc, 0x202);
@@ -1255,7 +3189,7 @@ mod tests {
         emu.v_reg[0x7] = 0xAB;
         emu.v_reg[0x2] = 0xAB;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x7], 0x00);
         assert_eq!(emu.pc, 0x202);
@@ -1270,7 +3204,7 @@ mod tests {
         emu.v_reg[0x8] = 0x50;
         emu.v_reg[0x4] = 0x30;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x8], 0x80);
         assert_eq!(emu.v_reg[0xF], 0);
@@ -1286,7 +3220,7 @@ mod tests {
         emu.v_reg[0x9] = 0xFF;
         emu.v_reg[0x4] = 0x01;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x9], 0x00);
         assert_eq!(emu.v_reg[0xF], 1);
@@ -1302,7 +3236,7 @@ mod tests {
         emu.v_reg[0xA] = 0x70;
         emu.v_reg[0x5] = 0x20;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xA], 0x50);
         assert_eq!(emu.v_reg[0xF], 1);
@@ -1318,7 +3252,7 @@ mod tests {
         emu.v_reg[0xB] = 0x10;
         emu.v_reg[0x5] = 0x20;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xB], 0xF0); // 0x10 - 0x20 = 0xF0 (wrap)
         assert_eq!(emu.v_reg[0xF], 0);
@@ -1334,7 +3268,7 @@ mod tests {
         emu.v_reg[0xC] = 0x00;
         emu.v_reg[0x0] = 0b1010_1101; // LSB = 1
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xC], 0b0101_0110); // przesunięte o 1
         assert_eq!(emu.v_reg[0xF], 1);
@@ -1350,7 +3284,7 @@ mod tests {
         emu.v_reg[0xD] = 0x00;
         emu.v_reg[0x0] = 0b1010_1100; // LSB = 0
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xD], 0b0101_0110);
         assert_eq!(emu.v_reg[0xF], 0);
@@ -1366,7 +3300,7 @@ mod tests {
         emu.v_reg[0xE] = 0x20;
         emu.v_reg[0x7] = 0x70;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xE], 0x50); // Vy - Vx
         assert_eq!(emu.v_reg[0xF], 1);
@@ -1382,7 +3316,7 @@ mod tests {
         emu.v_reg[0xE] = 0x70;
         emu.v_reg[0x7] = 0x20;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0xE], 0xB0); // Vy - Vx = 0x20 - 0x70 = wrap
         assert_eq!(emu.v_reg[0xF], 0);
@@ -1398,7 +3332,7 @@ mod tests {
         emu.v_reg[0x1] = 0x00;
         emu.v_reg[0x0] = 0b1000_0001; // MSB = 1
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x1], 0b0000_0010);
         assert_eq!(emu.v_reg[0xF], 1);
@@ -1414,7 +3348,7 @@ mod tests {
         emu.v_reg[0x2] = 0x00;
         emu.v_reg[0x0] = 0b0111_1111; // MSB = 0
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x2], 0b1111_1110);
         assert_eq!(emu.v_reg[0xF], 0);
@@ -1431,7 +3365,7 @@ mod tests {
         emu.v_reg[0x0] = 10;
         emu.v_reg[0x1] = 5;
 
-        emu.execute(0xD012);
+        emu.execute(0xD012).unwrap();
 
         let base1 = 5 * SCREEN_WIDTH + 10;
         assert!(emu.screen[base1]);
@@ -1463,7 +3397,7 @@ mod tests {
         emu.screen[idx1] = true;
         emu.screen[idx3] = true;
 
-        emu.execute(0xD011);
+        emu.execute(0xD011).unwrap();
 
         assert_eq!(emu.v_reg[0xF], 1);
 
@@ -1480,7 +3414,7 @@ mod tests {
         emu.v_reg[0x0] = (SCREEN_WIDTH - 4) as u8; // X start
         emu.v_reg[0x1] = 15; // Y
 
-        emu.execute(0xD011);
+        emu.execute(0xD011).unwrap();
 
         // after wrap -- pixels at the line start (X=0 to 3)
         #[allow(clippy::identity_op)]
@@ -1510,7 +3444,7 @@ mod tests {
         emu.v_reg[0x0] = 10;
         emu.v_reg[0x1] = 31;
 
-        emu.execute(0xD012);
+        emu.execute(0xD012).unwrap();
 
         #[allow(clippy::erasing_op)]
         #[allow(clippy::identity_op)]
@@ -1538,7 +3472,7 @@ mod tests {
         emu.v_reg[0x4] = (64 - 1) as u8;
         emu.v_reg[0x5] = (32 - 1) as u8;
 
-        emu.execute(0xD452);
+        emu.execute(0xD452).unwrap();
 
         assert!(emu.screen[0]);
         assert!(emu.screen[64 * 31]);
@@ -1554,7 +3488,7 @@ mod tests {
         for i in [0, 2, 4, 6] {
             emu.screen[10 * 64 + 10 + i] = true;
         }
-        emu.execute(0xD011);
+        emu.execute(0xD011).unwrap();
         assert_eq!(emu.v_reg[0xF], 1);
     }
 
@@ -1565,7 +3499,7 @@ mod tests {
         emu.ram[0x50] = 0xFF;
         emu.v_reg[0x0] = 20;
         emu.v_reg[0x1] = 20;
-        emu.execute(0xD010);
+        emu.execute(0xD010).unwrap();
         assert!(emu.screen.iter().all(|&p| !p));
         assert_eq!(emu.v_reg[0xF], 0);
     }
@@ -1577,7 +3511,7 @@ mod tests {
         emu.ram[0x50] = 0b1111_1111;
         emu.v_reg[0x0] = 58;
         emu.v_reg[0x1] = 10;
-        emu.execute(0xD011);
+        emu.execute(0xD011).unwrap();
         for i in 0..6 {
             assert!(emu.screen[10 * 64 + 58 + i]);
         }
@@ -1587,11 +3521,38 @@ mod tests {
         assert_eq!(emu.v_reg[0xF], 0);
     }
 
+    #[test]
+    fn test_opcode_dxyn_sprite_past_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        emu.i_reg = (RAM_SIZE - 2) as u16;
+
+        assert_eq!(
+            emu.execute(0xD015),
+            Err(EmuError::MemoryOutOfBounds {
+                addr: 0x200,
+                access: RAM_SIZE
+            })
+        );
+        assert!(emu.screen.iter().all(|&p| !p));
+    }
+
+    #[test]
+    fn test_opcode_dxyn_sprite_ends_at_last_byte() {
+        let mut emu = Emu::new();
+        emu.i_reg = (RAM_SIZE - 1) as u16;
+        emu.ram[RAM_SIZE - 1] = 0x80;
+
+        emu.execute(0xD011).unwrap();
+
+        assert!(emu.screen[0]);
+    }
+
     #[test]
     fn test_opcode_fx07_load_dt_basic() {
         let mut emu = Emu::new();
         emu.dt = 42;
-        emu.execute(0xF207);
+        emu.execute(0xF207).unwrap();
         assert_eq!(emu.v_reg[0x2], 42);
     }
 
@@ -1599,7 +3560,7 @@ mod tests {
     fn test_opcode_fx07_load_dt_zero() {
         let mut emu = Emu::new();
         emu.dt = 0;
-        emu.execute(0xF007);
+        emu.execute(0xF007).unwrap();
         assert_eq!(emu.v_reg[0x0], 0);
     }
 
@@ -1607,7 +3568,7 @@ mod tests {
     fn test_opcode_fx07_load_dt_max() {
         let mut emu = Emu::new();
         emu.dt = 255;
-        emu.execute(0xFF07);
+        emu.execute(0xFF07).unwrap();
         assert_eq!(emu.v_reg[0xF], 255);
     }
 
@@ -1615,22 +3576,22 @@ mod tests {
     fn test_opcode_fx07_different_registers() {
         let mut emu = Emu::new();
         emu.dt = 100;
-        emu.execute(0xF107);
+        emu.execute(0xF107).unwrap();
         assert_eq!(emu.v_reg[0x1], 100);
-        emu.execute(0xFA07);
+        emu.execute(0xFA07).unwrap();
         assert_eq!(emu.v_reg[0xA], 100);
     }
 
     #[test]
-    fn test_opcode_fx07_with_tick_cycle() {
+    fn test_opcode_fx07_with_step() {
         let mut emu = Emu::new();
         emu.dt = 5;
         emu.ram[0x200] = 0xF3;
         emu.ram[0x201] = 0x07;
         emu.pc = 0x200;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x3], 5);
-        assert_eq!(emu.dt, 4);
+        assert_eq!(emu.dt, 5); // step leaves the timers alone
         assert_eq!(emu.pc, 0x202);
     }
 
@@ -1643,10 +3604,11 @@ mod tests {
         emu.ram[0x202] = 0xF2;
         emu.ram[0x203] = 0x07;
         emu.pc = 0x200;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x1], 60);
+        emu.tick_timers();
         assert_eq!(emu.dt, 59);
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.v_reg[0x2], 59);
         assert_eq!(emu.pc, 0x204);
     }
@@ -1655,7 +3617,7 @@ mod tests {
     fn test_opcode_fx15_set_dt_basic() {
         let mut emu = Emu::new();
         emu.v_reg[0x3] = 42;
-        emu.execute(0xF315);
+        emu.execute(0xF315).unwrap();
         assert_eq!(emu.dt, 42);
     }
 
@@ -1663,7 +3625,7 @@ mod tests {
     fn test_opcode_fx15_set_dt_zero() {
         let mut emu = Emu::new();
         emu.v_reg[0x0] = 0;
-        emu.execute(0xF015);
+        emu.execute(0xF015).unwrap();
         assert_eq!(emu.dt, 0);
     }
 
@@ -1671,7 +3633,7 @@ mod tests {
     fn test_opcode_fx15_set_dt_max() {
         let mut emu = Emu::new();
         emu.v_reg[0xF] = 255;
-        emu.execute(0xFF15);
+        emu.execute(0xFF15).unwrap();
         assert_eq!(emu.dt, 255);
     }
 
@@ -1680,24 +3642,24 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0x1] = 100;
         emu.v_reg[0xA] = 200;
-        emu.execute(0xF115);
+        emu.execute(0xF115).unwrap();
         assert_eq!(emu.dt, 100);
-        emu.execute(0xFA15);
+        emu.execute(0xFA15).unwrap();
         assert_eq!(emu.dt, 200);
     }
 
     #[test]
-    fn test_opcode_fx15_with_tick_cycle() {
+    fn test_opcode_fx15_with_step() {
         let mut emu = Emu::new();
         emu.v_reg[0x4] = 10;
         emu.ram[0x200] = 0xF4;
         emu.ram[0x201] = 0x15;
         emu.pc = 0x200;
-        emu.tick();
-        assert_eq!(emu.dt, 9); // tick decrements dt
+        emu.step().unwrap();
+        assert_eq!(emu.dt, 10); // step leaves the timers alone
         assert_eq!(emu.pc, 0x202);
-        emu.tick();
-        assert_eq!(emu.dt, 8); // tick decrements dt
+        emu.tick_timers();
+        assert_eq!(emu.dt, 9);
     }
 
     #[test]
@@ -1710,10 +3672,10 @@ mod tests {
         emu.pc = 0x200;
         emu.v_reg[0x1] = 60;
         emu.v_reg[0x2] = 30;
-        emu.tick();
-        assert_eq!(emu.dt, 59); // tick decrements dt
-        emu.tick();
-        assert_eq!(emu.dt, 29);
+        emu.step().unwrap();
+        assert_eq!(emu.dt, 60);
+        emu.step().unwrap();
+        assert_eq!(emu.dt, 30);
         assert_eq!(emu.pc, 0x204);
     }
 
@@ -1721,7 +3683,7 @@ mod tests {
     fn test_opcode_cxkk_rand_basic() {
         let mut emu = Emu::new();
         emu.v_reg[0x0] = 0;
-        emu.execute(0xC012);
+        emu.execute(0xC012).unwrap();
         assert!(emu.v_reg[0x0] <= 0x12);
         assert_eq!(emu.v_reg[0x0] & 0xED, 0);
     }
@@ -1730,7 +3692,7 @@ mod tests {
     fn test_opcode_cxkk_rand_zero_mask() {
         let mut emu = Emu::new();
         emu.v_reg[0x1] = 0xFF;
-        emu.execute(0xC100);
+        emu.execute(0xC100).unwrap();
         assert_eq!(emu.v_reg[0x1], 0);
     }
 
@@ -1738,51 +3700,98 @@ mod tests {
     fn test_opcode_cxkk_rand_full_mask() {
         let mut emu = Emu::new();
         emu.v_reg[0x2] = 0;
-        emu.execute(0xC2FF);
+        emu.execute(0xC2FF).unwrap();
         assert_eq!(emu.v_reg[0x2], emu.v_reg[0x2]);
     }
 
     #[test]
     fn test_opcode_cxkk_rand_different_registers() {
         let mut emu = Emu::new();
-        emu.execute(0xC388);
+        emu.execute(0xC388).unwrap();
         assert!(emu.v_reg[0x3] <= 0x88);
-        emu.execute(0xCA55);
+        emu.execute(0xCA55).unwrap();
         assert!(emu.v_reg[0xA] <= 0x55);
     }
 
     #[test]
     fn test_opcode_cxkk_rand_multiple_times_same_register() {
-        let mut emu = Emu::new();
+        let mut emu = Emu::with_seed(1);
         let first = {
-            emu.execute(0xC4AA);
+            emu.execute(0xC4AA).unwrap();
             emu.v_reg[0x4]
         };
         let second = {
             emu.v_reg[0x4] = 0;
-            emu.execute(0xC4AA);
+            emu.execute(0xC4AA).unwrap();
             emu.v_reg[0x4]
         };
         assert_ne!(first, second);
     }
 
     #[test]
-    fn test_opcode_cxkk_rand_with_tick_cycle() {
+    fn test_opcode_cxkk_rand_with_step() {
         let mut emu = Emu::new();
         emu.ram[0x200] = 0xC5;
         emu.ram[0x201] = 0x3F;
         emu.pc = 0x200;
-        emu.tick();
+        emu.step().unwrap();
         assert!(emu.v_reg[0x5] <= 0x3F);
         assert_eq!(emu.pc, 0x202);
     }
 
+    #[test]
+    fn test_opcode_cxkk_same_seed_same_results() {
+        let run = |seed| {
+            let mut emu = Emu::with_seed(seed);
+            (0..16)
+                .map(|_| {
+                    emu.execute(0xC0FF).unwrap();
+                    emu.v_reg[0]
+                })
+                .collect::<Vec<u8>>()
+        };
+
+        assert_eq!(run(1234), run(1234));
+        assert_ne!(run(1234), run(4321));
+    }
+
+    #[test]
+    fn test_reseed_restarts_sequence() {
+        let mut emu = Emu::with_seed(99);
+        emu.execute(0xC0FF).unwrap();
+        let first = emu.v_reg[0];
+        emu.execute(0xC0FF).unwrap();
+
+        emu.seed(99);
+        emu.execute(0xC0FF).unwrap();
+
+        assert_eq!(emu.v_reg[0], first);
+    }
+
+    struct Fixed(u8);
+
+    impl RandomSource for Fixed {
+        fn next_byte(&mut self) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_opcode_cxkk_custom_source() {
+        let mut emu = Emu::new();
+        emu.set_rng(Box::new(Fixed(0b1011_0110)));
+
+        emu.execute(0xC30F).unwrap();
+
+        assert_eq!(emu.v_reg[0x3], 0b0000_0110);
+    }
+
     #[test]
     fn test_opcode_fx33_bcd_basic() {
         let mut emu = Emu::new();
         emu.v_reg[0x5] = 123;
         emu.i_reg = 0x300;
-        emu.execute(0xF533);
+        emu.execute(0xF533).unwrap();
         assert_eq!(emu.ram[0x300], 1);
         assert_eq!(emu.ram[0x301], 2);
         assert_eq!(emu.ram[0x302], 3);
@@ -1793,7 +3802,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0x0] = 0;
         emu.i_reg = 0x400;
-        emu.execute(0xF033);
+        emu.execute(0xF033).unwrap();
         assert_eq!(emu.ram[0x400], 0);
         assert_eq!(emu.ram[0x401], 0);
         assert_eq!(emu.ram[0x402], 0);
@@ -1804,7 +3813,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0xF] = 255;
         emu.i_reg = 0x500;
-        emu.execute(0xFF33);
+        emu.execute(0xFF33).unwrap();
         assert_eq!(emu.ram[0x500], 2);
         assert_eq!(emu.ram[0x501], 5);
         assert_eq!(emu.ram[0x502], 5);
@@ -1815,7 +3824,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0xA] = 7;
         emu.i_reg = 0x600;
-        emu.execute(0xFA33);
+        emu.execute(0xFA33).unwrap();
         assert_eq!(emu.ram[0x600], 0);
         assert_eq!(emu.ram[0x601], 0);
         assert_eq!(emu.ram[0x602], 7);
@@ -1826,7 +3835,7 @@ mod tests {
         let mut emu = Emu::new();
         emu.v_reg[0x1] = 10;
         emu.i_reg = 0x700;
-        emu.execute(0xF133);
+        emu.execute(0xF133).unwrap();
         assert_eq!(emu.ram[0x700], 0);
         assert_eq!(emu.ram[0x701], 1);
         assert_eq!(emu.ram[0x702], 0);
@@ -1838,17 +3847,34 @@ mod tests {
         emu.i_reg = 0x800;
         emu.v_reg[0x2] = 45;
         emu.v_reg[0x3] = 67;
-        emu.execute(0xF233);
+        emu.execute(0xF233).unwrap();
         assert_eq!(emu.ram[0x800], 0);
         assert_eq!(emu.ram[0x801], 4);
         assert_eq!(emu.ram[0x802], 5);
         emu.i_reg = 0x900;
-        emu.execute(0xF333);
+        emu.execute(0xF333).unwrap();
         assert_eq!(emu.ram[0x900], 0);
         assert_eq!(emu.ram[0x901], 6);
         assert_eq!(emu.ram[0x902], 7);
     }
 
+    #[test]
+    fn test_opcode_fx33_bcd_past_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        emu.i_reg = (RAM_SIZE - 2) as u16;
+        emu.v_reg[0x0] = 123;
+
+        assert_eq!(
+            emu.execute(0xF033),
+            Err(EmuError::MemoryOutOfBounds {
+                addr: 0x200,
+                access: RAM_SIZE
+            })
+        );
+        assert_eq!(emu.ram[RAM_SIZE - 2], 0); // nothing partially written
+    }
+
     #[test]
     fn test_opcode_fx55_store_registers_basic() {
         let mut emu = Emu::new();
@@ -1856,7 +3882,7 @@ mod tests {
         for i in 0..=0x3 {
             emu.v_reg[i] = i as u8 * 10;
         }
-        emu.execute(0xF355);
+        emu.execute(0xF355).unwrap();
         assert_eq!(emu.ram[0x300], 0);
         assert_eq!(emu.ram[0x301], 10);
         assert_eq!(emu.ram[0x302], 20);
@@ -1867,7 +3893,7 @@ mod tests {
     fn test_opcode_fx55_store_zero_registers() {
         let mut emu = Emu::new();
         emu.i_reg = 0x400;
-        emu.execute(0xF055);
+        emu.execute(0xF055).unwrap();
         assert_eq!(emu.ram[0x400], 0);
     }
 
@@ -1878,7 +3904,7 @@ mod tests {
         for i in 0..=0xF {
             emu.v_reg[i] = i as u8 * 17;
         }
-        emu.execute(0xFF55);
+        emu.execute(0xFF55).unwrap();
         for i in 0..=0xF {
             assert_eq!(emu.ram[0x500 + i], i as u8 * 17);
         }
@@ -1892,7 +3918,7 @@ mod tests {
         emu.v_reg[0x0] = 1;
         emu.v_reg[0x1] = 2;
         emu.v_reg[0x2] = 3;
-        emu.execute(0xF255);
+        emu.execute(0xF255).unwrap();
         assert_eq!(emu.ram[0x600], 1);
         assert_eq!(emu.ram[0x601], 2);
         assert_eq!(emu.ram[0x602], 3);
@@ -1900,14 +3926,14 @@ mod tests {
     }
 
     #[test]
-    fn test_opcode_fx55_store_with_tick_cycle() {
+    fn test_opcode_fx55_store_with_step() {
         let mut emu = Emu::new();
         emu.i_reg = 0x200;
         emu.v_reg[0x4] = 100;
         emu.ram[0x200] = 0xF4;
         emu.ram[0x201] = 0x55;
         emu.pc = 0x200;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.ram[0x204], 100);
         assert_eq!(emu.pc, 0x202);
     }
@@ -1923,13 +3949,62 @@ mod tests {
         emu.ram[0x202] = 0xF2;
         emu.ram[0x203] = 0x55;
         emu.pc = 0x200;
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.ram[0x701], 11);
-        emu.tick();
+        emu.step().unwrap();
         assert_eq!(emu.ram[0x702], 22);
         assert_eq!(emu.pc, 0x204);
     }
 
+    #[test]
+    fn test_opcode_fx55_store_past_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        emu.i_reg = 0xFFF;
+        emu.v_reg[0x0] = 1;
+        emu.v_reg[0x1] = 2;
+
+        assert_eq!(
+            emu.execute(0xF155),
+            Err(EmuError::MemoryOutOfBounds {
+                addr: 0x200,
+                access: RAM_SIZE
+            })
+        );
+        assert_eq!(emu.ram[0xFFF], 0);
+    }
+
+    #[test]
+    fn test_opcode_fx55_i_beyond_ram() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        emu.i_reg = 0xFFFF;
+
+        assert_eq!(
+            emu.execute(0xF055),
+            Err(EmuError::MemoryOutOfBounds {
+                addr: 0x200,
+                access: 0xFFFF
+            })
+        );
+    }
+
+    #[test]
+    fn test_fx65_load_past_end_of_ram() {
+        let mut emu = Emu::new();
+        emu.pc = 0x202;
+        emu.i_reg = 0xFF1;
+
+        assert_eq!(
+            emu.execute(0xFF65),
+            Err(EmuError::MemoryOutOfBounds {
+                addr: 0x200,
+                access: RAM_SIZE
+            })
+        );
+        assert!(emu.v_reg.iter().all(|&v| v == 0));
+    }
+
     #[test]
     fn test_fx65_load_registers_basic() {
         let mut emu = Emu::new();
@@ -1939,7 +4014,7 @@ mod tests {
         emu.ram[0x302] = 30;
         emu.ram[0x303] = 40;
 
-        emu.execute(0xF365); // read V0-V3
+        emu.execute(0xF365).unwrap(); // read V0-V3
 
         assert_eq!(emu.v_reg[0x0], 10);
         assert_eq!(emu.v_reg[0x1], 20);
@@ -1956,7 +4031,7 @@ mod tests {
             emu.ram[0x500 + i] = i as u8 * 10;
         }
 
-        emu.execute(0xFF65);
+        emu.execute(0xFF65).unwrap();
 
         for i in 0..=0xF {
             assert_eq!(emu.v_reg[i], i as u8 * 10);
@@ -1971,7 +4046,7 @@ mod tests {
         emu.ram[0x600] = 99;
         emu.ram[0x601] = 88;
 
-        emu.execute(0xF165);
+        emu.execute(0xF165).unwrap();
 
         assert_eq!(emu.v_reg[0x0], 99);
         assert_eq!(emu.v_reg[0x1], 88);
@@ -1980,7 +4055,7 @@ mod tests {
     }
 
     #[test]
-    fn test_fx65_with_tick_cycle() {
+    fn test_fx65_with_step() {
         let mut emu = Emu::new();
         emu.i_reg = 0x200;
         emu.ram[0x200] = 50;
@@ -1991,7 +4066,7 @@ mod tests {
         emu.ram[0x301] = 0x65;
         emu.pc = 0x300;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.v_reg[0x0], 50);
         assert_eq!(emu.v_reg[0x1], 60);
@@ -2009,7 +4084,7 @@ mod tests {
         emu.v_reg[0xA] = 0x5;
         emu.keys[5] = false;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x204); // skip
     }
@@ -2023,7 +4098,7 @@ mod tests {
         emu.v_reg[0x4] = 0xC;
         emu.keys[0xC] = true;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x302); // no skip
     }
@@ -2036,7 +4111,7 @@ mod tests {
         emu.ram[0x401] = 0xA1;
         emu.v_reg[0x1] = 0xFF;
 
-        emu.tick();
+        emu.step().unwrap();
 
         assert_eq!(emu.pc, 0x402);
     }
@@ -2047,7 +4122,7 @@ mod tests {
         emu.i_reg = 0x100;
         emu.v_reg[0x3] = 0x45;
 
-        emu.execute(0xF31E);
+        emu.execute(0xF31E).unwrap();
 
         assert_eq!(emu.i_reg, 0x145);
     }
@@ -2058,7 +4133,7 @@ mod tests {
         emu.i_reg = 0x200;
         emu.v_reg[0x0] = 0x00;
 
-        emu.execute(0xF01E);
+        emu.execute(0xF01E).unwrap();
 
         assert_eq!(emu.i_reg, 0x200);
     }
@@ -2069,7 +4144,7 @@ mod tests {
         emu.i_reg = 0xF00;
         emu.v_reg[0xF] = 0xFF;
 
-        emu.execute(0xFF1E);
+        emu.execute(0xFF1E).unwrap();
 
         assert_eq!(emu.i_reg, 0xFFF); // wrap-around
     }
@@ -2081,10 +4156,10 @@ mod tests {
         emu.v_reg[0x1] = 0x10;
         emu.v_reg[0x2] = 0x20;
 
-        emu.execute(0xF11E);
+        emu.execute(0xF11E).unwrap();
         assert_eq!(emu.i_reg, 0x310);
 
-        emu.execute(0xF21E);
+        emu.execute(0xF21E).unwrap();
         assert_eq!(emu.i_reg, 0x330);
     }
 
@@ -2092,7 +4167,7 @@ mod tests {
     fn test_fx29_font_address_digit_0() {
         let mut emu = Emu::new();
         emu.v_reg[0x0] = 0x0;
-        emu.execute(0xF029);
+        emu.execute(0xF029).unwrap();
         assert_eq!(emu.i_reg, 0x0);
     }
 
@@ -2100,7 +4175,7 @@ mod tests {
     fn test_fx29_font_address_digit_5() {
         let mut emu = Emu::new();
         emu.v_reg[0x5] = 0x5;
-        emu.execute(0xF529);
+        emu.execute(0xF529).unwrap();
         assert_eq!(emu.i_reg, 25); // 5 * 5 = 25
     }
 
@@ -2108,7 +4183,7 @@ mod tests {
     fn test_fx29_font_address_digit_f() {
         let mut emu = Emu::new();
         emu.v_reg[0xF] = 0xF;
-        emu.execute(0xFF29);
+        emu.execute(0xFF29).unwrap();
         assert_eq!(emu.i_reg, 75); // 15 * 5 = 75
     }
 
@@ -2116,11 +4191,11 @@ mod tests {
     fn test_fx29_multiple_digits() {
         let mut emu = Emu::new();
         emu.v_reg[0x3] = 0x3;
-        emu.execute(0xF329);
+        emu.execute(0xF329).unwrap();
         assert_eq!(emu.i_reg, 15);
 
         emu.v_reg[0xB] = 0xB;
-        emu.execute(0xFB29);
+        emu.execute(0xFB29).unwrap();
         assert_eq!(emu.i_reg, 55);
     }
 }
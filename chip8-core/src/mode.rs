@@ -0,0 +1,74 @@
+use crate::{Disassembly, MAX_ROM_SIZE, Quirks, START_ADDR};
+
+/// The instruction set and machine layout the emulator presents to a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Plain CHIP-8: 64x32 display, 35 opcodes.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: adds the 128x64 hires display, scrolling, 16x16
+    /// sprites, the big hex font and RPL flag registers.
+    SuperChip,
+    /// XO-CHIP: SUPER-CHIP plus 64 KiB of memory, a second display plane,
+    /// programmable audio and a handful of new opcodes.
+    XoChip,
+}
+
+impl Mode {
+    /// Quirks a ROM written for this platform expects.
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Mode::Chip8 => Quirks::default(),
+            Mode::SuperChip => Quirks::SCHIP_1_1,
+            Mode::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    /// Size of the address space in bytes.
+    pub fn ram_size(self) -> usize {
+        match self {
+            Mode::Chip8 | Mode::SuperChip => crate::RAM_SIZE,
+            Mode::XoChip => crate::XO_RAM_SIZE,
+        }
+    }
+
+    /// Guesses the platform a ROM was written for from the instructions its
+    /// reachable code uses. Images too big for 4 KiB of RAM are XO-CHIP.
+    pub fn detect(rom: &[u8]) -> Mode {
+        if rom.len() > MAX_ROM_SIZE {
+            return Mode::XoChip;
+        }
+        let dis = Disassembly::new(rom, Mode::XoChip);
+        (0..rom.len())
+            .filter_map(|offset| dis.instruction_at(START_ADDR + offset as u16))
+            .map(|ins| ins.platform())
+            .max_by_key(|mode| *mode as u8)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        // CLS; JP 0x202
+        assert_eq!(Mode::detect(&[0x00, 0xE0, 0x12, 0x02]), Mode::Chip8);
+        // HIGH; EXIT
+        assert_eq!(Mode::detect(&[0x00, 0xFF, 0x00, 0xFD]), Mode::SuperChip);
+        // EXIT, then an unreachable PLANE 1
+        assert_eq!(Mode::detect(&[0x00, 0xFD, 0xF1, 0x01]), Mode::SuperChip);
+        // PLANE 3; JP 0x202
+        assert_eq!(Mode::detect(&[0xF3, 0x01, 0x12, 0x02]), Mode::XoChip);
+        assert_eq!(Mode::detect(&vec![0; MAX_ROM_SIZE + 1]), Mode::XoChip);
+    }
+
+    #[test]
+    fn test_detect_pong2() {
+        assert_eq!(
+            Mode::detect(include_bytes!("../../roms/PONG2")),
+            Mode::Chip8
+        );
+    }
+}
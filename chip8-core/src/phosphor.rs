@@ -0,0 +1,179 @@
+//! Display persistence to hide the flicker of XOR-drawn sprites.
+//!
+//! Games move a sprite by erasing it and drawing it again, so between frames
+//! it is often missing and a plain display flickers. `Phosphor` runs after
+//! each frame and turns the display into per-pixel intensities from 0 (off)
+//! to 255 (fully lit) that remember recent frames, either by fading pixels
+//! out over a few frames as a CRT's phosphor would or by OR-ing the last two
+//! frames. It only uses integer arithmetic, so the same frames always give
+//! the same intensities.
+//!
+//! Intensities are grayscale: a pixel lit on either XO-CHIP plane counts as
+//! lit. `Renderer::render_intensity` turns them into an image.
+
+use crate::Emu;
+
+/// How long pixels stay visible after they are turned off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Persistence {
+    /// Pixels go dark as soon as they are cleared.
+    #[default]
+    Off,
+    /// Pixels lit in this frame or the one before are fully lit.
+    Blend,
+    /// Cleared pixels fade out linearly over this many frames.
+    Decay(u8),
+}
+
+/// Per-pixel intensities of the display with persistence applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Phosphor {
+    persistence: Persistence,
+    width: usize,
+    intensity: Vec<u8>,
+    /// Whether each pixel was lit in the last frame, for `Blend`.
+    lit: Vec<bool>,
+}
+
+impl Phosphor {
+    pub fn new(persistence: Persistence) -> Self {
+        Self {
+            persistence,
+            width: 0,
+            intensity: Vec::new(),
+            lit: Vec::new(),
+        }
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.persistence
+    }
+
+    /// Changes the persistence, keeping the current intensities.
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.persistence = persistence;
+    }
+
+    /// Forgets earlier frames.
+    pub fn reset(&mut self) {
+        self.width = 0;
+        self.intensity.clear();
+        self.lit.clear();
+    }
+
+    /// Takes the display of `emu` at the end of a frame.
+    pub fn update(&mut self, emu: &Emu) {
+        let pixels: Vec<u8> = emu.pixels().collect();
+        self.update_pixels(&pixels, emu.screen_width());
+    }
+
+    /// Takes a frame of rows of `width` 2-bit pixels, as from `Emu::pixels`.
+    /// A change of size, e.g. to SUPER-CHIP hires, starts afresh.
+    pub fn update_pixels(&mut self, pixels: &[u8], width: usize) {
+        if width != self.width || pixels.len() != self.intensity.len() {
+            self.width = width;
+            self.intensity = vec![0; pixels.len()];
+            self.lit = vec![false; pixels.len()];
+        }
+        let fade = match self.persistence {
+            Persistence::Decay(frames) => 255u8.div_ceil(frames.max(1)),
+            _ => 255,
+        };
+        for ((&pixel, intensity), was_lit) in
+            pixels.iter().zip(&mut self.intensity).zip(&mut self.lit)
+        {
+            let lit = pixel != 0;
+            *intensity = match self.persistence {
+                _ if lit => 255,
+                Persistence::Blend if *was_lit => 255,
+                Persistence::Decay(_) => intensity.saturating_sub(fade),
+                _ => 0,
+            };
+            *was_lit = lit;
+        }
+    }
+
+    /// Width of the last frame in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Intensity of each pixel of the last frame, in display order.
+    pub fn intensity(&self) -> &[u8] {
+        &self.intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::crc32;
+
+    #[test]
+    fn test_off_follows_display() {
+        let mut phosphor = Phosphor::new(Persistence::Off);
+        phosphor.update_pixels(&[1, 0, 2], 3);
+        phosphor.update_pixels(&[0, 3, 2], 3);
+
+        assert_eq!(phosphor.intensity(), [0, 255, 255]);
+    }
+
+    #[test]
+    fn test_blend_keeps_last_frame() {
+        let mut phosphor = Phosphor::new(Persistence::Blend);
+        phosphor.update_pixels(&[1, 0], 2);
+        phosphor.update_pixels(&[0, 1], 2);
+        assert_eq!(phosphor.intensity(), [255, 255]);
+
+        phosphor.update_pixels(&[0, 0], 2);
+        assert_eq!(phosphor.intensity(), [0, 255]);
+    }
+
+    #[test]
+    fn test_decay_fades_out() {
+        let mut phosphor = Phosphor::new(Persistence::Decay(3));
+        phosphor.update_pixels(&[1], 1);
+        let mut levels = Vec::new();
+        for _ in 0..4 {
+            phosphor.update_pixels(&[0], 1);
+            levels.push(phosphor.intensity()[0]);
+        }
+
+        assert_eq!(levels, [170, 85, 0, 0]);
+    }
+
+    #[test]
+    fn test_resize_starts_afresh() {
+        let mut phosphor = Phosphor::new(Persistence::Decay(8));
+        phosphor.update_pixels(&[1; 4], 2);
+        phosphor.update_pixels(&[0; 8], 4);
+
+        assert_eq!(phosphor.width(), 4);
+        assert_eq!(phosphor.intensity(), [0; 8]);
+    }
+
+    /// Runs PONG2 for two seconds and checksums the final intensities, so any
+    /// change to the output shows up as a new checksum.
+    fn pong2_golden(persistence: Persistence) -> u32 {
+        let mut emu = Emu::new();
+        emu.seed(7);
+        emu.load_rom(include_bytes!("../../roms/PONG2")).unwrap();
+        let mut phosphor = Phosphor::new(persistence);
+        for _ in 0..120 {
+            emu.run_frame().unwrap();
+            phosphor.update(&emu);
+        }
+        crc32(phosphor.intensity())
+    }
+
+    #[test]
+    fn test_pong2_golden() {
+        let sums = [
+            pong2_golden(Persistence::Off),
+            pong2_golden(Persistence::Blend),
+            pong2_golden(Persistence::Decay(4)),
+        ];
+
+        assert_eq!(sums, [0x1690_8F0F, 0xBDDD_8C8A, 0x0377_FA74]);
+    }
+}
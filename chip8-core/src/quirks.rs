@@ -0,0 +1,85 @@
+/// How `FX55`/`FX65` leave the I register after copying registers to or from memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryIncrement {
+    /// I keeps its value (SUPER-CHIP 1.1).
+    Unchanged,
+    /// I is advanced by X (CHIP-48).
+    ByX,
+    /// I is advanced by X + 1, pointing past the last byte touched (COSMAC VIP, XO-CHIP).
+    ByXPlusOne,
+}
+
+/// Interpretations of opcodes whose behaviour differs between CHIP-8 platforms.
+///
+/// `Quirks::default()` matches what `Emu::new` has always done; the named
+/// presets reproduce the platforms most ROMs were written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VX in place instead of shifting VY into VX.
+    pub shift_vx: bool,
+    /// What `FX55`/`FX65` do to I.
+    pub memory_increment: MemoryIncrement,
+    /// `BNNN` behaves as `BXNN`: jump to XNN + VX instead of NNN + V0.
+    pub jump_vx: bool,
+    /// `8XY1`/`8XY2`/`8XY3` clear VF.
+    pub vf_reset: bool,
+    /// `DXYN` clips sprites at the screen edges instead of wrapping them around.
+    pub clipping: bool,
+    /// `DXYN` waits for the next frame before execution continues.
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original CHIP-8 interpreter on the RCA COSMAC VIP.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_vx: false,
+        memory_increment: MemoryIncrement::ByXPlusOne,
+        jump_vx: false,
+        vf_reset: true,
+        clipping: true,
+        display_wait: true,
+    };
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub const CHIP48: Quirks = Quirks {
+        shift_vx: true,
+        memory_increment: MemoryIncrement::ByX,
+        jump_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+    };
+
+    /// SUPER-CHIP 1.1.
+    pub const SCHIP_1_1: Quirks = Quirks {
+        shift_vx: true,
+        memory_increment: MemoryIncrement::Unchanged,
+        jump_vx: true,
+        vf_reset: false,
+        clipping: true,
+        display_wait: false,
+    };
+
+    /// XO-CHIP as implemented by Octo.
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_vx: false,
+        memory_increment: MemoryIncrement::ByXPlusOne,
+        jump_vx: false,
+        vf_reset: false,
+        clipping: false,
+        display_wait: false,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_vx: false,
+            memory_increment: MemoryIncrement::Unchanged,
+            jump_vx: false,
+            vf_reset: false,
+            clipping: false,
+            display_wait: false,
+        }
+    }
+}
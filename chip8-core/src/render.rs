@@ -0,0 +1,310 @@
+//! Software rendering of the display into RGBA8 images.
+//!
+//! A `Renderer` maps each 2-bit pixel of `Emu::pixels` through a `Palette`,
+//! scales it up to a square block of `scale` x `scale` output pixels and
+//! optionally draws effects over the blocks:
+//!
+//! - grid lines along the right and bottom edge of every block,
+//! - scanlines, darkening the bottom row of every block.
+//!
+//! Effects need a block of at least 2x2 to leave the pixel visible, so they
+//! are skipped at scale 1.
+
+use crate::Emu;
+
+/// An RGBA colour.
+pub type Rgba = [u8; 4];
+
+/// Colours for pixel values 0-3: background, first plane, XO-CHIP second
+/// plane, both planes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette(pub [Rgba; 4]);
+
+impl Palette {
+    /// White on black, with greys for the XO-CHIP planes.
+    pub const MONOCHROME: Palette = Palette([
+        [0x00, 0x00, 0x00, 0xFF],
+        [0xFF, 0xFF, 0xFF, 0xFF],
+        [0xAA, 0xAA, 0xAA, 0xFF],
+        [0x55, 0x55, 0x55, 0xFF],
+    ]);
+
+    /// Green phosphor.
+    pub const CLASSIC_GREEN: Palette = Palette([
+        [0x00, 0x1A, 0x00, 0xFF],
+        [0x33, 0xFF, 0x33, 0xFF],
+        [0x1A, 0x80, 0x1A, 0xFF],
+        [0x99, 0xFF, 0x99, 0xFF],
+    ]);
+
+    /// Amber phosphor.
+    pub const AMBER: Palette = Palette([
+        [0x1A, 0x0F, 0x00, 0xFF],
+        [0xFF, 0xB0, 0x00, 0xFF],
+        [0x80, 0x58, 0x00, 0xFF],
+        [0xFF, 0xD8, 0x80, 0xFF],
+    ]);
+
+    /// Octo's default XO-CHIP colours.
+    pub const XO_CHIP: Palette = Palette([
+        [0x99, 0x66, 0x00, 0xFF],
+        [0xFF, 0xCC, 0x00, 0xFF],
+        [0xFF, 0x66, 0x00, 0xFF],
+        [0x66, 0x22, 0x00, 0xFF],
+    ]);
+
+    /// The colour of a pixel value; only its low two bits count.
+    pub fn color(&self, pixel: u8) -> Rgba {
+        self.0[pixel as usize & 3]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::MONOCHROME
+    }
+}
+
+/// An RGBA8 image: rows top to bottom, four bytes per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    /// The colour at (x, y). Panics outside the image.
+    pub fn pixel(&self, x: usize, y: usize) -> Rgba {
+        let at = (y * self.width + x) * 4;
+        [
+            self.data[at],
+            self.data[at + 1],
+            self.data[at + 2],
+            self.data[at + 3],
+        ]
+    }
+}
+
+/// Turns the display into an `Image`.
+///
+/// ```
+/// use chip8_core::{Emu, Palette, Renderer};
+///
+/// let renderer = Renderer::new(Palette::AMBER).scale(4).scanlines(0x80);
+/// let image = renderer.render(&Emu::new());
+/// assert_eq!((image.width, image.height), (256, 128));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Renderer {
+    palette: Palette,
+    scale: usize,
+    grid: Option<Rgba>,
+    scanlines: Option<u8>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::new(Palette::default())
+    }
+}
+
+impl Renderer {
+    /// Renders at scale 1 without effects.
+    pub fn new(palette: Palette) -> Self {
+        Self {
+            palette,
+            scale: 1,
+            grid: None,
+            scanlines: None,
+        }
+    }
+
+    /// Makes each display pixel a `scale` x `scale` block. Values below 1 count as 1.
+    pub fn scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /// Draws grid lines in `color` between display pixels.
+    pub fn grid(mut self, color: Rgba) -> Self {
+        self.grid = Some(color);
+        self
+    }
+
+    /// Darkens the bottom row of each block by `strength`, from 0 (no
+    /// change) to 255 (black).
+    pub fn scanlines(mut self, strength: u8) -> Self {
+        self.scanlines = Some(strength);
+        self
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// The size of the image for a `width` x `height` display.
+    pub fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        (width * self.scale, height * self.scale)
+    }
+
+    /// Renders the current display of `emu`.
+    pub fn render(&self, emu: &Emu) -> Image {
+        let pixels: Vec<u8> = emu.pixels().collect();
+        self.render_pixels(&pixels, emu.screen_width())
+    }
+
+    /// Renders rows of `width` 2-bit pixels, as from `Emu::pixels`.
+    pub fn render_pixels(&self, pixels: &[u8], width: usize) -> Image {
+        let colors: Vec<Rgba> = pixels.iter().map(|&p| self.palette.color(p)).collect();
+        self.render_colors(&colors, width)
+    }
+
+    /// Renders rows of `width` intensities, as from `Phosphor::intensity`,
+    /// mixing the background and first plane colours.
+    pub fn render_intensity(&self, intensity: &[u8], width: usize) -> Image {
+        let [off, on] = [self.palette.color(0), self.palette.color(1)];
+        let colors: Vec<Rgba> = intensity
+            .iter()
+            .map(|&level| {
+                let mix = |i: usize| {
+                    let (off, on, level) = (off[i] as u32, on[i] as u32, level as u32);
+                    ((off * (255 - level) + on * level + 127) / 255) as u8
+                };
+                [mix(0), mix(1), mix(2), mix(3)]
+            })
+            .collect();
+        self.render_colors(&colors, width)
+    }
+
+    fn render_colors(&self, colors: &[Rgba], width: usize) -> Image {
+        let height = colors.len().checked_div(width).unwrap_or(0);
+        let (out_width, out_height) = self.output_size(width, height);
+        let mut data = Vec::with_capacity(out_width * out_height * 4);
+        let mut line = Vec::with_capacity(out_width * 4);
+        let last = self.scale - 1;
+
+        for row in colors.chunks_exact(width.max(1)).take(height) {
+            for dy in 0..self.scale {
+                line.clear();
+                for &color in row {
+                    for dx in 0..self.scale {
+                        line.extend(self.effect(color, dx, dy, last));
+                    }
+                }
+                data.extend_from_slice(&line);
+            }
+        }
+        Image {
+            width: out_width,
+            height: out_height,
+            data,
+        }
+    }
+
+    /// The colour of output pixel (dx, dy) within a block of `color`, whose
+    /// last row and column are at `last`.
+    fn effect(&self, color: Rgba, dx: usize, dy: usize, last: usize) -> Rgba {
+        if last == 0 {
+            return color;
+        }
+        if let Some(grid) = self.grid
+            && (dx == last || dy == last)
+        {
+            return grid;
+        }
+        match self.scanlines {
+            Some(strength) if dy == last => {
+                let keep = 255 - strength as u16;
+                let [r, g, b, a] = color;
+                let dim = |c: u8| (c as u16 * keep / 255) as u8;
+                [dim(r), dim(g), dim(b), a]
+            }
+            _ => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgba = [0x00, 0x00, 0x00, 0xFF];
+    const WHITE: Rgba = [0xFF, 0xFF, 0xFF, 0xFF];
+
+    #[test]
+    fn test_palette_colors() {
+        let image = Renderer::new(Palette::XO_CHIP).render_pixels(&[0, 1, 2, 3], 4);
+
+        assert_eq!((image.width, image.height), (4, 1));
+        assert_eq!(image.data.len(), 16);
+        for x in 0..4 {
+            assert_eq!(image.pixel(x, 0), Palette::XO_CHIP.0[x]);
+        }
+    }
+
+    #[test]
+    fn test_scale() {
+        // 2x2 checkerboard scaled 3x.
+        let image = Renderer::default().scale(3).render_pixels(&[1, 0, 0, 1], 2);
+
+        assert_eq!((image.width, image.height), (6, 6));
+        assert_eq!(image.pixel(0, 0), WHITE);
+        assert_eq!(image.pixel(2, 2), WHITE);
+        assert_eq!(image.pixel(3, 2), BLACK);
+        assert_eq!(image.pixel(2, 3), BLACK);
+        assert_eq!(image.pixel(5, 5), WHITE);
+    }
+
+    #[test]
+    fn test_grid_and_scanlines() {
+        let red = [0xFF, 0x00, 0x00, 0xFF];
+        let image = Renderer::default()
+            .scale(3)
+            .grid(red)
+            .render_pixels(&[1], 1);
+        assert_eq!(image.pixel(1, 1), WHITE);
+        assert_eq!(image.pixel(2, 0), red);
+        assert_eq!(image.pixel(0, 2), red);
+
+        let image = Renderer::default()
+            .scale(2)
+            .scanlines(0x80)
+            .render_pixels(&[1], 1);
+        assert_eq!(image.pixel(1, 0), WHITE);
+        assert_eq!(image.pixel(1, 1), [0x7F, 0x7F, 0x7F, 0xFF]);
+    }
+
+    #[test]
+    fn test_render_intensity() {
+        let image = Renderer::new(Palette::AMBER).render_intensity(&[0, 0x80, 0xFF], 3);
+
+        assert_eq!(image.pixel(0, 0), Palette::AMBER.0[0]);
+        assert_eq!(image.pixel(1, 0), [0x8D, 0x60, 0x00, 0xFF]);
+        assert_eq!(image.pixel(2, 0), Palette::AMBER.0[1]);
+    }
+
+    #[test]
+    fn test_effects_skipped_at_scale_1() {
+        let renderer = Renderer::default().grid(BLACK).scanlines(0xFF);
+
+        assert_eq!(renderer.render_pixels(&[1], 1).pixel(0, 0), WHITE);
+    }
+
+    #[test]
+    fn test_render_emu() {
+        let mut emu = Emu::new();
+        // Draw the font's "0" at the top left corner.
+        emu.load_rom(&[0xD0, 0x05]).unwrap();
+        emu.step().unwrap();
+        let image = Renderer::new(Palette::CLASSIC_GREEN).scale(2).render(&emu);
+
+        assert_eq!((image.width, image.height), (128, 64));
+        assert_eq!(image.pixel(0, 0), Palette::CLASSIC_GREEN.0[1]);
+        assert_eq!(image.pixel(8, 0), Palette::CLASSIC_GREEN.0[0]);
+    }
+}
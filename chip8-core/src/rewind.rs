@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use crate::{Emu, StateError};
+
+/// A bounded history of save states for stepping a running game backwards.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the
+/// run-length encoded XOR against its successor, so a frame where a few
+/// registers and pixels changed costs a handful of bytes.
+///
+/// A frontend pushes once per frame while the game runs and calls
+/// `step_back` once per frame while the rewind key is held.
+pub struct Rewind {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    /// Backward deltas, oldest first: applying the last one to `latest`
+    /// yields the snapshot pushed before it.
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    /// Creates a buffer holding at most `capacity` snapshots (at least one).
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Records the current state of `emu`, dropping the oldest snapshot when full.
+    pub fn push(&mut self, emu: &Emu) {
+        let state = emu.save_state();
+        if let Some(prev) = self.latest.take() {
+            self.deltas.push_back(encode_delta(&state, &prev));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Discards the newest snapshot and restores `emu` to the one before it.
+    ///
+    /// Returns `Ok(false)`, leaving `emu` alone, once only the oldest snapshot
+    /// is left. If the snapshot does not load, `emu` and the history are left
+    /// as they were.
+    pub fn step_back(&mut self, emu: &mut Emu) -> Result<bool, StateError> {
+        let (Some(latest), Some(delta)) = (self.latest.as_ref(), self.deltas.back()) else {
+            return Ok(false);
+        };
+        let prev = apply_delta(latest, delta);
+        emu.load_state(&prev)?;
+        self.deltas.pop_back();
+        self.latest = Some(prev);
+        Ok(true)
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.latest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes used by the stored snapshots.
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+}
+
+// Delta layout: target length, then (zero run, literal length, literals) records
+// covering `base XOR target`, with both buffers padded with zeros to the longer length.
+
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let len = base.len().max(target.len());
+    let xor = |i: usize| base.get(i).copied().unwrap_or(0) ^ target.get(i).copied().unwrap_or(0);
+
+    let mut out = Vec::new();
+    write_varint(&mut out, target.len());
+    let mut i = 0;
+    while i < len {
+        let run_start = i;
+        while i < len && xor(i) == 0 {
+            i += 1;
+        }
+        let literal_start = i;
+        while i < len && xor(i) != 0 {
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - run_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor));
+    }
+    out
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let target_len = read_varint(delta, &mut pos);
+    let mut out = base.to_vec();
+    out.resize(out.len().max(target_len), 0);
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+        for &byte in &delta[pos..pos + literals] {
+            out[i] ^= byte;
+            i += 1;
+        }
+        pos += literals;
+    }
+    out.truncate(target_len);
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mode;
+
+    fn counting_emu() -> Emu {
+        let mut emu = Emu::with_seed(3);
+        // V0 += 1; DRW V0, V0, 5 (font 0); JP 0x200
+        emu.load_rom(&[0x70, 0x01, 0xD0, 0x05, 0x12, 0x00]).unwrap();
+        emu
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let base = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let target = vec![1, 2, 9, 4, 5, 6, 7, 0, 0, 42];
+
+        assert_eq!(apply_delta(&base, &encode_delta(&base, &target)), target);
+        assert_eq!(apply_delta(&target, &encode_delta(&target, &base)), base);
+    }
+
+    #[test]
+    fn test_delta_of_identical_buffers_is_tiny() {
+        let state = counting_emu().save_state();
+
+        assert!(encode_delta(&state, &state).len() <= 8);
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        let mut out = Vec::new();
+        for value in [0, 1, 127, 128, 300, 65536] {
+            write_varint(&mut out, value);
+        }
+        let mut pos = 0;
+
+        for value in [0, 1, 127, 128, 300, 65536] {
+            assert_eq!(read_varint(&out, &mut pos), value);
+        }
+    }
+
+    #[test]
+    fn test_step_back_restores_previous_frames() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(10);
+        let mut history = Vec::new();
+        for _ in 0..5 {
+            emu.step().unwrap();
+            emu.step().unwrap();
+            history.push(emu.save_state());
+            rewind.push(&emu);
+        }
+        history.pop();
+
+        while let Some(expected) = history.pop() {
+            assert_eq!(rewind.step_back(&mut emu), Ok(true));
+            assert_eq!(emu.save_state(), expected);
+        }
+        assert_eq!(rewind.step_back(&mut emu), Ok(false));
+        assert_eq!(rewind.len(), 1);
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(3);
+        for _ in 0..10 {
+            emu.step().unwrap();
+            rewind.push(&emu);
+        }
+
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.step_back(&mut emu), Ok(true));
+        assert_eq!(rewind.step_back(&mut emu), Ok(true));
+        assert_eq!(rewind.step_back(&mut emu), Ok(false));
+        assert_eq!(emu.v_reg[0], 3);
+    }
+
+    #[test]
+    fn test_step_back_across_mode_change() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(4);
+        rewind.push(&emu);
+        let before = emu.save_state();
+        emu = Emu::with_mode(Mode::XoChip);
+        rewind.push(&emu);
+
+        assert_eq!(rewind.step_back(&mut emu), Ok(true));
+        assert_eq!(emu.mode(), Mode::Chip8);
+        assert_eq!(emu.save_state(), before);
+    }
+
+    #[test]
+    fn test_step_back_paused_machine() {
+        let mut emu = counting_emu();
+        emu.set_speed(crate::Speed::ips(0));
+        let mut rewind = Rewind::new(4);
+        rewind.push(&emu);
+        let before = emu.save_state();
+        emu.step().unwrap();
+        rewind.push(&emu);
+
+        assert_eq!(rewind.step_back(&mut emu), Ok(true));
+        assert_eq!(emu.save_state(), before);
+    }
+
+    #[test]
+    fn test_step_back_reports_bad_snapshot() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(4);
+        rewind.push(&emu);
+        emu.step().unwrap();
+        rewind.push(&emu);
+        let now = emu.save_state();
+        // Corrupt the older snapshot by breaking the delta that rebuilds it.
+        rewind.deltas[0] = encode_delta(&now, b"not a save state");
+
+        assert_eq!(rewind.step_back(&mut emu), Err(StateError::BadMagic));
+        assert_eq!(emu.save_state(), now);
+        assert_eq!(rewind.len(), 2);
+    }
+
+    #[test]
+    fn test_empty_and_clear() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(0);
+
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.capacity(), 1);
+        assert_eq!(rewind.step_back(&mut emu), Ok(false));
+
+        rewind.push(&emu);
+        rewind.push(&emu);
+        assert_eq!(rewind.len(), 1);
+
+        rewind.clear();
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.memory_usage(), 0);
+    }
+
+    #[test]
+    fn test_deltas_are_compact() {
+        let mut emu = counting_emu();
+        let mut rewind = Rewind::new(100);
+        for _ in 0..100 {
+            emu.step().unwrap();
+            rewind.push(&emu);
+        }
+
+        assert!(rewind.memory_usage() < emu.save_state().len() * 2);
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Source of the random bytes used by `CXKK`.
+///
+/// Implementations that can expose their internal state make replays and save
+/// states reproducible; the rest are simply skipped when state is captured.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// The generator state, if it fits in a `u64` and can be restored.
+    fn state(&self) -> Option<u64> {
+        None
+    }
+
+    /// Restores a state previously returned by `state`.
+    fn set_state(&mut self, _state: u64) {}
+}
+
+/// The default generator: xorshift64*, fast and fully determined by its seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads small seeds over all bits and never yields the
+        // all-zero state xorshift cannot leave
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// Seeds from the per-process randomness std uses for `HashMap`.
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl RandomSource for XorShift64 {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn state(&self) -> Option<u64> {
+        Some(self.state)
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.state = if state == 0 { 1 } else { state };
+    }
+}
+
+/// Draws from the `rand` crate's thread-local generator. Not reproducible.
+#[cfg(feature = "rand")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadRandom;
+
+#[cfg(feature = "rand")]
+impl RandomSource for ThreadRandom {
+    fn next_byte(&mut self) -> u8 {
+        use rand::Rng;
+        rand::rng().random()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = XorShift64::new(42);
+        let mut b = XorShift64::new(42);
+
+        let seq_a: Vec<u8> = (0..32).map(|_| a.next_byte()).collect();
+        let seq_b: Vec<u8> = (0..32).map(|_| b.next_byte()).collect();
+
+        assert_eq!(seq_a, seq_b);
+    }
+
+    #[test]
+    fn test_different_seeds_differ() {
+        let mut a = XorShift64::new(1);
+        let mut b = XorShift64::new(2);
+
+        let seq_a: Vec<u8> = (0..32).map(|_| a.next_byte()).collect();
+        let seq_b: Vec<u8> = (0..32).map(|_| b.next_byte()).collect();
+
+        assert_ne!(seq_a, seq_b);
+    }
+
+    #[test]
+    fn test_zero_seed_is_usable() {
+        let mut rng = XorShift64::new(0);
+
+        let bytes: Vec<u8> = (0..64).map(|_| rng.next_byte()).collect();
+
+        assert!(bytes.iter().any(|&b| b != bytes[0]));
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut rng = XorShift64::new(7);
+        rng.next_byte();
+        let saved = rng.state().unwrap();
+        let expected: Vec<u8> = (0..8).map(|_| rng.next_byte()).collect();
+
+        let mut restored = XorShift64::new(0);
+        restored.set_state(saved);
+        let replayed: Vec<u8> = (0..8).map(|_| restored.next_byte()).collect();
+
+        assert_eq!(replayed, expected);
+    }
+
+    #[test]
+    fn test_bytes_cover_full_range() {
+        let mut rng = XorShift64::new(3);
+        let mut seen = [false; 256];
+
+        for _ in 0..10_000 {
+            seen[rng.next_byte() as usize] = true;
+        }
+
+        assert!(seen.iter().all(|&s| s));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_thread_random_varies() {
+        let mut rng = ThreadRandom;
+
+        let bytes: Vec<u8> = (0..64).map(|_| rng.next_byte()).collect();
+
+        assert!(bytes.iter().any(|&b| b != bytes[0]));
+        assert_eq!(rng.state(), None);
+    }
+}
@@ -0,0 +1,540 @@
+//! Save states: a snapshot of the complete machine as a byte blob.
+//!
+//! # Format (version 1)
+//!
+//! All integers are little-endian.
+//!
+//! | Field           | Size          | Notes                                              |
+//! |-----------------|---------------|----------------------------------------------------|
+//! | magic           | 4             | `C8ST`                                             |
+//! | version         | 2             | currently 1                                        |
+//! | mode            | 1             | 0 CHIP-8, 1 SUPER-CHIP, 2 XO-CHIP                  |
+//! | quirk flags     | 1             | bit 0 shift_vx, 1 jump_vx, 2 vf_reset, 3 clipping, 4 display wait |
+//! | memory quirk    | 1             | 0 unchanged, 1 by X, 2 by X + 1                    |
+//! | speed           | 4             | instructions per second                            |
+//! | cycle_acc       | 4             | instructions owed to the next frame, in 1/60ths    |
+//! | pc, i           | 2 + 2         |                                                    |
+//! | sp              | 1             |                                                    |
+//! | v0-vf           | 16            |                                                    |
+//! | stack           | 16 x 2        |                                                    |
+//! | dt, st          | 1 + 1         |                                                    |
+//! | keys            | 2             | bit n = key n held                                 |
+//! | hires, halted   | 1 + 1         | booleans                                           |
+//! | plane mask      | 1             |                                                    |
+//! | pitch           | 1             |                                                    |
+//! | audio pattern   | 16            |                                                    |
+//! | RPL flags       | 16            |                                                    |
+//! | rng present     | 1             | 0 if the random source cannot be captured          |
+//! | rng state       | 8             |                                                    |
+//! | ram length      | 4             | must match the mode                                |
+//! | ram             | ram length    |                                                    |
+//! | screen planes   | 2 x w x h / 8 | one bit per pixel, MSB first, plane 1 then plane 2 |
+//! | rom length      | 4             |                                                    |
+//! | rom             | rom length    | the image `reset` restores                         |
+//! | checksum        | 4             | CRC-32 (IEEE) of every preceding byte              |
+//!
+//! The `SysPolicy` is not part of the state; it stays whatever the restoring
+//! machine has configured.
+
+use crate::{
+    AUDIO_PATTERN_SIZE, Emu, HIRES_HEIGHT, HIRES_WIDTH, MemoryIncrement, Mode, NUM_FLAGS, NUM_KEYS,
+    NUM_REGS, Quirks, SCREEN_HEIGHT, SCREEN_WIDTH, STACK_SIZE, Speed, StateError, TIMER_HZ,
+};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+const VERSION: u16 = 1;
+
+impl Emu {
+    /// Captures the whole machine as a versioned, checksummed blob (layout in `state.rs`).
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer(Vec::with_capacity(self.ram.len() + 1024));
+        w.bytes(MAGIC);
+        w.u16(VERSION);
+
+        w.u8(match self.mode {
+            Mode::Chip8 => 0,
+            Mode::SuperChip => 1,
+            Mode::XoChip => 2,
+        });
+        let q = self.quirks;
+        w.u8(q.shift_vx as u8
+            | (q.jump_vx as u8) << 1
+            | (q.vf_reset as u8) << 2
+            | (q.clipping as u8) << 3
+            | (q.display_wait as u8) << 4);
+        w.u8(match q.memory_increment {
+            MemoryIncrement::Unchanged => 0,
+            MemoryIncrement::ByX => 1,
+            MemoryIncrement::ByXPlusOne => 2,
+        });
+        w.u32(self.speed.instructions_per_second());
+        w.u32(self.cycle_acc);
+
+        w.u16(self.pc);
+        w.u16(self.i_reg);
+        w.u8(self.sp as u8);
+        w.bytes(&self.v_reg);
+        for &addr in &self.stack {
+            w.u16(addr);
+        }
+        w.u8(self.dt);
+        w.u8(self.st);
+        let keys = (0..NUM_KEYS).fold(0u16, |mask, k| mask | (self.keys[k] as u16) << k);
+        w.u16(keys);
+
+        w.u8(self.hires as u8);
+        w.u8(self.halted as u8);
+        w.u8(self.plane_mask);
+        w.u8(self.pitch);
+        w.bytes(&self.audio_pattern);
+        w.bytes(&self.flags);
+
+        let rng = self.rng.state();
+        w.u8(rng.is_some() as u8);
+        w.u64(rng.unwrap_or(0));
+
+        w.u32(self.ram.len() as u32);
+        w.bytes(&self.ram);
+        w.bytes(&pack_bits(&self.screen));
+        w.bytes(&pack_bits(&self.plane2));
+        w.u32(self.rom.len() as u32);
+        w.bytes(&self.rom);
+
+        let checksum = crc32(&w.0);
+        w.u32(checksum);
+        w.0
+    }
+
+    /// Restores a state produced by `save_state`.
+    ///
+    /// The data is fully validated first; on error the machine is left untouched.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < MAGIC.len() || &data[..MAGIC.len()] != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        if data.len() < MAGIC.len() + 2 + 4 {
+            return Err(StateError::Truncated);
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        let (body, checksum) = data.split_at(data.len() - 4);
+        if crc32(body) != u32::from_le_bytes(checksum.try_into().unwrap()) {
+            return Err(StateError::ChecksumMismatch);
+        }
+
+        let mut r = Reader {
+            data: body,
+            pos: MAGIC.len() + 2,
+        };
+        let mode = match r.u8()? {
+            0 => Mode::Chip8,
+            1 => Mode::SuperChip,
+            2 => Mode::XoChip,
+            _ => return Err(StateError::Invalid("mode")),
+        };
+        let flags = r.u8()?;
+        let memory_increment = match r.u8()? {
+            0 => MemoryIncrement::Unchanged,
+            1 => MemoryIncrement::ByX,
+            2 => MemoryIncrement::ByXPlusOne,
+            _ => return Err(StateError::Invalid("memory increment quirk")),
+        };
+        let quirks = Quirks {
+            shift_vx: flags & 1 != 0,
+            jump_vx: flags & 2 != 0,
+            vf_reset: flags & 4 != 0,
+            clipping: flags & 8 != 0,
+            display_wait: flags & 16 != 0,
+            memory_increment,
+        };
+        let speed = Speed::ips(r.u32()?);
+        let cycle_acc = r.u32()?;
+        if cycle_acc >= TIMER_HZ {
+            return Err(StateError::Invalid("cycle accumulator"));
+        }
+
+        let pc = r.u16()?;
+        let i_reg = r.u16()?;
+        let sp = r.u8()? as u16;
+        if sp as usize > STACK_SIZE {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        let v_reg: [u8; NUM_REGS] = r.array()?;
+        let mut stack = [0; STACK_SIZE];
+        for slot in stack.iter_mut() {
+            *slot = r.u16()?;
+        }
+        let dt = r.u8()?;
+        let st = r.u8()?;
+        let keys_mask = r.u16()?;
+
+        let hires = r.bool()?;
+        let halted = r.bool()?;
+        let plane_mask = r.u8()?;
+        if plane_mask > 0b11 {
+            return Err(StateError::Invalid("plane mask"));
+        }
+        let pitch = r.u8()?;
+        let audio_pattern: [u8; AUDIO_PATTERN_SIZE] = r.array()?;
+        let rpl: [u8; NUM_FLAGS] = r.array()?;
+
+        let has_rng = r.bool()?;
+        let rng_state = r.u64()?;
+
+        let ram_len = r.u32()? as usize;
+        if ram_len != mode.ram_size() {
+            return Err(StateError::Invalid("ram size"));
+        }
+        let ram = r.take(ram_len)?.to_vec();
+        let pixels = if hires {
+            HIRES_WIDTH * HIRES_HEIGHT
+        } else {
+            SCREEN_WIDTH * SCREEN_HEIGHT
+        };
+        let screen = unpack_bits(r.take(pixels / 8)?);
+        let plane2 = unpack_bits(r.take(pixels / 8)?);
+        let rom_len = r.u32()? as usize;
+        if rom_len > ram_len - crate::START_ADDR as usize {
+            return Err(StateError::Invalid("rom size"));
+        }
+        let rom = r.take(rom_len)?.to_vec();
+        if r.pos != body.len() {
+            return Err(StateError::Invalid("trailing data"));
+        }
+
+        self.mode = mode;
+        self.quirks = quirks;
+        self.speed = speed;
+        self.cycle_acc = cycle_acc;
+        self.pc = pc;
+        self.i_reg = i_reg;
+        self.sp = sp;
+        self.v_reg = v_reg;
+        self.stack = stack;
+        self.dt = dt;
+        self.st = st;
+        self.set_keys(keys_mask);
+        self.hires = hires;
+        self.halted = halted;
+        self.plane_mask = plane_mask;
+        self.pitch = pitch;
+        self.audio_pattern = audio_pattern;
+        self.flags = rpl;
+        if has_rng {
+            self.rng.set_state(rng_state);
+        }
+        self.ram = ram;
+        self.screen = screen;
+        self.plane2 = plane2;
+        self.rom = rom;
+        Ok(())
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        self.0.extend_from_slice(v);
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos.checked_add(len).ok_or(StateError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(StateError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("boolean")),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
+
+fn pack_bits(pixels: &[bool]) -> Vec<u8> {
+    pixels
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |byte, (i, &lit)| byte | (lit as u8) << (7 - i))
+        })
+        .collect()
+}
+
+fn unpack_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes
+        .iter()
+        .flat_map(|&byte| (0..8).map(move |i| byte & (0x80 >> i) != 0))
+        .collect()
+}
+
+/// CRC-32 with the IEEE polynomial, as used by zip and PNG.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn busy_emu() -> Emu {
+        let mut emu = Emu::with_seed(5);
+        // V0 := random 0xFF; V1 += 1; I := 0x300; DRW V1, V1, 2; CALL 0x20C; JP 0x200; RET
+        emu.load_rom(&[
+            0xC0, 0xFF, 0x71, 0x01, 0xA3, 0x00, 0xD1, 0x12, 0x22, 0x0C, 0x12, 0x00, 0x00, 0xEE,
+        ])
+        .unwrap();
+        emu.ram[0x300] = 0xF0;
+        emu.ram[0x301] = 0x0F;
+        for _ in 0..5 {
+            emu.step().unwrap();
+        }
+        emu.dt = 17;
+        emu.st = 3;
+        emu.key_down(0xB);
+        emu
+    }
+
+    #[test]
+    fn test_crc32_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_save_state_header() {
+        let state = Emu::new().save_state();
+
+        assert_eq!(&state[..4], b"C8ST");
+        assert_eq!(state[4..6], [1, 0]);
+    }
+
+    #[test]
+    fn test_round_trip_restores_everything() {
+        let emu = busy_emu();
+        let state = emu.save_state();
+
+        let mut restored = Emu::new();
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(restored.pc, emu.pc);
+        assert_eq!(restored.i_reg, emu.i_reg);
+        assert_eq!(restored.v_reg, emu.v_reg);
+        assert_eq!(restored.sp, emu.sp);
+        assert_eq!(restored.stack, emu.stack);
+        assert_eq!(restored.dt, 17);
+        assert_eq!(restored.st, 3);
+        assert_eq!(restored.keys, emu.keys);
+        assert_eq!(restored.ram, emu.ram);
+        assert_eq!(restored.screen, emu.screen);
+        assert_eq!(restored.save_state(), state);
+    }
+
+    #[test]
+    fn test_round_trip_continues_identically() {
+        let mut emu = busy_emu();
+        let state = emu.save_state();
+        let mut restored = Emu::with_seed(12345);
+        restored.load_state(&state).unwrap();
+
+        for _ in 0..20 {
+            emu.step().unwrap();
+            restored.step().unwrap();
+        }
+
+        assert_eq!(restored.save_state(), emu.save_state());
+    }
+
+    #[test]
+    fn test_round_trip_quirks_speed_and_mode() {
+        let mut emu = Emu::with_mode(Mode::XoChip);
+        emu.set_quirks(Quirks::CHIP48);
+        emu.set_speed(Speed::FAST);
+        emu.execute(0x00FF).unwrap();
+        emu.execute(0xF201).unwrap();
+        emu.plane2[HIRES_WIDTH * 10 + 3] = true;
+        emu.pitch = 100;
+        emu.audio_pattern[0] = 0xAA;
+        emu.flags[15] = 0x77;
+        emu.halted = true;
+
+        let mut restored = Emu::new();
+        restored.load_state(&emu.save_state()).unwrap();
+
+        assert_eq!(restored.mode(), Mode::XoChip);
+        assert_eq!(restored.quirks(), Quirks::CHIP48);
+        assert_eq!(restored.speed(), Speed::FAST);
+        assert!(restored.is_hires());
+        assert!(restored.is_halted());
+        assert_eq!(restored.plane_mask, 0b10);
+        assert!(restored.plane2[HIRES_WIDTH * 10 + 3]);
+        assert_eq!(restored.ram.len(), crate::XO_RAM_SIZE);
+        assert_eq!(restored.pitch(), 100);
+        assert_eq!(restored.audio_pattern()[0], 0xAA);
+        assert_eq!(restored.flags[15], 0x77);
+    }
+
+    #[test]
+    fn test_reset_after_load_restores_saved_rom() {
+        let emu = busy_emu();
+        let mut restored = Emu::new();
+        restored.load_rom(&[0x12, 0x00]).unwrap();
+
+        restored.load_state(&emu.save_state()).unwrap();
+        restored.reset();
+
+        assert_eq!(restored.ram[0x200..0x202], [0xC0, 0xFF]);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut emu = Emu::new();
+
+        assert_eq!(emu.load_state(b"NOPE"), Err(StateError::BadMagic));
+        assert_eq!(emu.load_state(b""), Err(StateError::BadMagic));
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut state = Emu::new().save_state();
+        state[4] = 9;
+
+        assert_eq!(
+            Emu::new().load_state(&state),
+            Err(StateError::UnsupportedVersion(9))
+        );
+    }
+
+    #[test]
+    fn test_corruption_detected() {
+        let mut state = busy_emu().save_state();
+        state[100] ^= 0x01;
+        let mut emu = Emu::new();
+
+        assert_eq!(emu.load_state(&state), Err(StateError::ChecksumMismatch));
+        assert_eq!(emu.pc, crate::START_ADDR); // untouched
+    }
+
+    #[test]
+    fn test_truncated_state() {
+        let state = busy_emu().save_state();
+        let mut cut = state[..state.len() - 50].to_vec();
+        let checksum = crc32(&cut);
+        cut.extend_from_slice(&checksum.to_le_bytes());
+
+        assert_eq!(Emu::new().load_state(&cut), Err(StateError::Truncated));
+        assert_eq!(
+            Emu::new().load_state(&state[..7]),
+            Err(StateError::Truncated)
+        );
+    }
+
+    /// A fresh save state with `bytes` written at `offset` and the checksum
+    /// fixed up, so only the field check can reject it.
+    fn patched_state(offset: usize, bytes: &[u8]) -> Vec<u8> {
+        let mut state = Emu::new().save_state();
+        state[offset..offset + bytes.len()].copy_from_slice(bytes);
+        let body_len = state.len() - 4;
+        let checksum = crc32(&state[..body_len]);
+        state[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        state
+    }
+
+    #[test]
+    fn test_invalid_field_rejected() {
+        let state = patched_state(6, &[7]); // mode
+
+        assert_eq!(
+            Emu::new().load_state(&state),
+            Err(StateError::Invalid("mode"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_cycle_acc_rejected() {
+        let state = patched_state(13, &TIMER_HZ.to_le_bytes());
+        assert_eq!(
+            Emu::new().load_state(&state),
+            Err(StateError::Invalid("cycle accumulator"))
+        );
+        let state = patched_state(13, &(TIMER_HZ - 1).to_le_bytes());
+        assert!(Emu::new().load_state(&state).is_ok());
+    }
+
+    #[test]
+    fn test_round_trip_paused_machine() {
+        let mut emu = busy_emu();
+        emu.set_speed(Speed::ips(0));
+
+        let mut restored = Emu::new();
+        restored.load_state(&emu.save_state()).unwrap();
+
+        assert_eq!(restored.speed(), Speed::ips(0));
+        assert_eq!(restored.save_state(), emu.save_state());
+    }
+
+    #[test]
+    fn test_pack_bits_round_trip() {
+        let pixels: Vec<bool> = (0..64).map(|i| i % 3 == 0).collect();
+
+        assert_eq!(unpack_bits(&pack_bits(&pixels)), pixels);
+    }
+}
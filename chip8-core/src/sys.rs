@@ -0,0 +1,31 @@
+use crate::EmuError;
+
+/// The parts of the machine a `SysHandler` may read and modify.
+pub struct SysContext<'a> {
+    pub v_reg: &'a mut [u8; 16],
+    pub i_reg: &'a mut u16,
+    pub ram: &'a mut [u8],
+    pub dt: &'a mut u8,
+    pub st: &'a mut u8,
+}
+
+/// Native stand-ins for the 1802 machine code routines a ROM calls with `0NNN`.
+///
+/// Hybrid COSMAC VIP programs jump into hand-written machine code; a handler
+/// reproduces what that code did to the interpreter state.
+pub trait SysHandler {
+    /// Runs the routine at `addr`. Returning an error stops the emulator.
+    fn call(&mut self, addr: u16, ctx: SysContext<'_>) -> Result<(), EmuError>;
+}
+
+/// What the emulator does when a program executes `0NNN` (SYS addr).
+#[derive(Default)]
+pub enum SysPolicy {
+    /// Treat the call as a no-op, as most modern interpreters do.
+    Ignore,
+    /// Stop with `EmuError::UnsupportedSys`.
+    #[default]
+    Error,
+    /// Hand the call to a user-supplied implementation.
+    Handler(Box<dyn SysHandler>),
+}
@@ -0,0 +1,550 @@
+//! Execution tracing: a record of every instruction the machine runs.
+//!
+//! Install a `Tracer` with `Emu::set_tracer`. Each executed instruction then
+//! produces a `TraceEntry` holding its address, encoding, mnemonic and what
+//! it changed, which goes to a ring buffer, a text log or JSON lines. With no
+//! tracer installed `step` only pays for one `Option` check.
+//!
+//! The text format is one instruction per line, meant for diffing:
+//!
+//! ```text
+//!        0  200  6A02      LD VA, 0x02          VA=02
+//!        1  202  A300      LD I, 0x300          I=0300
+//!        2  204  FA33      LD B, VA             [300]=00 [301]=00 [302]=02
+//! ```
+//!
+//! JSON lines carry the same fields, e.g.
+//! `{"n":2,"pc":516,"op":"FA33","asm":"LD B, VA","regs":{},"writes":[[768,0,0],...]}`,
+//! with register changes and writes given as `[old, new]` and `[addr, old, new]`.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::{Access, Emu, EmuError, Instruction, NUM_REGS, Register, StepOutcome};
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Number of instructions executed before this one since the tracer was
+    /// installed, counting those the filter skipped.
+    pub index: u64,
+    pub pc: u16,
+    pub instruction: Instruction,
+    /// Registers whose value changed, in `Register` order.
+    pub registers: Vec<RegisterChange>,
+    /// RAM bytes written through I, in address order. Unchanged bytes are included.
+    pub writes: Vec<MemoryWrite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: Register,
+    pub old: u16,
+    pub new: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl TraceEntry {
+    /// The entry as a line of the text log, without the newline.
+    pub fn to_text(&self) -> String {
+        let bytes: String = self
+            .instruction
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let mut line = format!(
+            "{:>8}  {:03X}  {:<8}  {:<20}",
+            self.index,
+            self.pc,
+            bytes,
+            self.instruction.to_string()
+        );
+        for change in &self.registers {
+            let _ = match change.register {
+                Register::I => write!(line, " I={:04X}", change.new),
+                register => write!(line, " {}={:02X}", register, change.new),
+            };
+        }
+        for write in &self.writes {
+            let _ = write!(line, " [{:03X}]={:02X}", write.addr, write.new);
+        }
+        line.trim_end().to_string()
+    }
+
+    /// The entry as a JSON object on one line.
+    pub fn to_json(&self) -> String {
+        let op: String = self
+            .instruction
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let mut json = format!(
+            "{{\"n\":{},\"pc\":{},\"op\":\"{}\",\"asm\":\"{}\",\"regs\":{{",
+            self.index, self.pc, op, self.instruction
+        );
+        for (i, change) in self.registers.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            let _ = write!(
+                json,
+                "{}\"{}\":[{},{}]",
+                sep, change.register, change.old, change.new
+            );
+        }
+        json.push_str("},\"writes\":[");
+        for (i, write) in self.writes.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{}[{},{},{}]", sep, write.addr, write.old, write.new);
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+/// Which instructions get recorded. The default records everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    ranges: Vec<Range<u16>>,
+    // bit n set = record opcodes whose top nibble is n; zero records all
+    classes: u16,
+}
+
+impl TraceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records instructions whose address is in `range`. Can be given several
+    /// times; without it every address is recorded.
+    pub fn pc_range(mut self, range: Range<u16>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    /// Records opcodes whose first hex digit is `class`, e.g. `0xD` for
+    /// drawing. Can be given several times; without it every class is recorded.
+    pub fn opcode_class(mut self, class: u8) -> Self {
+        self.classes |= 1 << (class & 0xF);
+        self
+    }
+
+    pub fn matches(&self, pc: u16, op: u16) -> bool {
+        (self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&pc)))
+            && (self.classes == 0 || self.classes & (1 << (op >> 12)) != 0)
+    }
+}
+
+enum Sink {
+    Ring {
+        capacity: usize,
+        entries: VecDeque<TraceEntry>,
+    },
+    Text(Box<dyn Write>),
+    JsonLines(Box<dyn Write>),
+}
+
+/// Receives a `TraceEntry` for each instruction the filter lets through.
+pub struct Tracer {
+    sink: Sink,
+    filter: TraceFilter,
+    executed: u64,
+    // first write error; the log stops there
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// Keeps the last `capacity` entries (at least one) in memory.
+    pub fn ring(capacity: usize) -> Self {
+        Self::with_sink(Sink::Ring {
+            capacity: capacity.max(1),
+            entries: VecDeque::new(),
+        })
+    }
+
+    /// Writes one line of text per entry.
+    pub fn text(out: impl Write + 'static) -> Self {
+        Self::with_sink(Sink::Text(Box::new(out)))
+    }
+
+    /// Writes one JSON object per line per entry.
+    pub fn json_lines(out: impl Write + 'static) -> Self {
+        Self::with_sink(Sink::JsonLines(Box::new(out)))
+    }
+
+    fn with_sink(sink: Sink) -> Self {
+        Self {
+            sink,
+            filter: TraceFilter::default(),
+            executed: 0,
+            error: None,
+        }
+    }
+
+    pub fn with_filter(mut self, filter: TraceFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn filter(&self) -> &TraceFilter {
+        &self.filter
+    }
+
+    /// Number of instructions executed since the tracer was installed.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// The entries held by a ring buffer tracer, oldest first. Empty for the
+    /// other kinds.
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        let entries = match &self.sink {
+            Sink::Ring { entries, .. } => Some(entries.iter()),
+            _ => None,
+        };
+        entries.into_iter().flatten()
+    }
+
+    /// Flushes the log, reporting the first error writing it hit.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        match &mut self.sink {
+            Sink::Ring { .. } => Ok(()),
+            Sink::Text(out) | Sink::JsonLines(out) => out.flush(),
+        }
+    }
+
+    fn record(&mut self, entry: TraceEntry) {
+        if self.error.is_some() {
+            return;
+        }
+        let result = match &mut self.sink {
+            Sink::Ring { capacity, entries } => {
+                if entries.len() == *capacity {
+                    entries.pop_front();
+                }
+                entries.push_back(entry);
+                Ok(())
+            }
+            Sink::Text(out) => writeln!(out, "{}", entry.to_text()),
+            Sink::JsonLines(out) => writeln!(out, "{}", entry.to_json()),
+        };
+        self.error = result.err();
+    }
+}
+
+/// Register values before an instruction, to diff against afterwards.
+struct Snapshot {
+    v_reg: [u8; NUM_REGS],
+    i_reg: u16,
+    sp: u16,
+    dt: u8,
+    st: u8,
+}
+
+impl Snapshot {
+    fn of(emu: &Emu) -> Self {
+        Self {
+            v_reg: emu.v_reg,
+            i_reg: emu.i_reg,
+            sp: emu.sp,
+            dt: emu.dt,
+            st: emu.st,
+        }
+    }
+
+    fn changes(&self, emu: &Emu) -> Vec<RegisterChange> {
+        let v = (0..NUM_REGS).map(|x| {
+            (
+                Register::V(x as u8),
+                self.v_reg[x] as u16,
+                emu.v_reg[x] as u16,
+            )
+        });
+        let others = [
+            (Register::I, self.i_reg, emu.i_reg),
+            (Register::Sp, self.sp, emu.sp),
+            (Register::Dt, self.dt as u16, emu.dt as u16),
+            (Register::St, self.st as u16, emu.st as u16),
+        ];
+        v.chain(others)
+            .filter(|(_, old, new)| old != new)
+            .map(|(register, old, new)| RegisterChange { register, old, new })
+            .collect()
+    }
+}
+
+impl Emu {
+    /// Installs `tracer`, or removes the current one with `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    pub fn tracer_mut(&mut self) -> Option<&mut Tracer> {
+        self.tracer.as_mut()
+    }
+
+    /// Removes and returns the tracer, e.g. to flush and inspect it.
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// `step` with a tracer installed: runs the instruction and records it if
+    /// the filter accepts it.
+    pub(crate) fn step_traced(&mut self) -> Result<StepOutcome, EmuError> {
+        let Some(mut tracer) = self.tracer.take() else {
+            return self.step_untraced();
+        };
+        let pc = self.pc;
+        let index = tracer.executed;
+        let instruction = self
+            .read_word(pc)
+            .filter(|&op| tracer.filter.matches(pc, op))
+            .and_then(|_| Instruction::read(self.ram.get(pc as usize..)?, self.mode));
+
+        let result = match instruction {
+            Some(instruction) => {
+                let before = Snapshot::of(self);
+                let written = match self.memory_access(instruction) {
+                    Some((range, Access::Write)) => {
+                        let range = range.start.min(self.ram.len())..range.end.min(self.ram.len());
+                        Some((range.start, self.ram[range].to_vec()))
+                    }
+                    _ => None,
+                };
+                let result = self.step_untraced();
+                if completed(&result) {
+                    let writes = written
+                        .map(|(start, old)| {
+                            old.iter()
+                                .enumerate()
+                                .map(|(offset, &old)| MemoryWrite {
+                                    addr: (start + offset) as u16,
+                                    old,
+                                    new: self.ram[start + offset],
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    tracer.record(TraceEntry {
+                        index,
+                        pc,
+                        instruction,
+                        registers: before.changes(self),
+                        writes,
+                    });
+                }
+                result
+            }
+            None => self.step_untraced(),
+        };
+        if completed(&result) {
+            tracer.executed += 1;
+        }
+        self.tracer = Some(tracer);
+        result
+    }
+}
+
+/// Whether a step ran its instruction to the end. `FX0A` waiting for a key
+/// runs again on the next step, so only its last attempt counts.
+fn completed(result: &Result<StepOutcome, EmuError>) -> bool {
+    matches!(result, Ok(outcome) if *outcome != StepOutcome::WaitingForKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A `Write` whose contents the test can read after the tracer took it.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // LD VA, 2; LD I, 0x300; LD B, VA; JP 0x206
+    const PROGRAM: [u8; 8] = [0x6A, 0x02, 0xA3, 0x00, 0xFA, 0x33, 0x12, 0x06];
+
+    fn traced(tracer: Tracer) -> Emu {
+        let mut emu = Emu::with_seed(1);
+        emu.load_rom(&PROGRAM).unwrap();
+        emu.set_tracer(Some(tracer));
+        for _ in 0..4 {
+            emu.step().unwrap();
+        }
+        emu
+    }
+
+    #[test]
+    fn test_ring_records_registers_and_writes() {
+        let emu = traced(Tracer::ring(10));
+        let entries: Vec<_> = emu.tracer().unwrap().entries().cloned().collect();
+
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].pc, 0x200);
+        assert_eq!(
+            entries[0].registers,
+            [RegisterChange {
+                register: Register::V(0xA),
+                old: 0,
+                new: 2
+            }]
+        );
+        assert_eq!(entries[1].instruction, Instruction::LdI(0x300));
+        assert_eq!(
+            entries[2].writes,
+            [
+                MemoryWrite {
+                    addr: 0x300,
+                    old: 0,
+                    new: 0
+                },
+                MemoryWrite {
+                    addr: 0x301,
+                    old: 0,
+                    new: 0
+                },
+                MemoryWrite {
+                    addr: 0x302,
+                    old: 0,
+                    new: 2
+                },
+            ]
+        );
+        assert!(entries[3].registers.is_empty());
+    }
+
+    #[test]
+    fn test_ring_keeps_the_newest_entries() {
+        let emu = traced(Tracer::ring(2));
+        let tracer = emu.tracer().unwrap();
+        let indices: Vec<_> = tracer.entries().map(|entry| entry.index).collect();
+
+        assert_eq!(indices, [2, 3]);
+        assert_eq!(tracer.executed(), 4);
+    }
+
+    #[test]
+    fn test_text_log() {
+        let buf = SharedBuf::default();
+        let mut emu = traced(Tracer::text(buf.clone()));
+        emu.tracer_mut().unwrap().flush().unwrap();
+
+        let text = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            "       0  200  6A02      LD VA, 0x02          VA=02"
+        );
+        assert_eq!(
+            lines[2],
+            "       2  204  FA33      LD B, VA             [300]=00 [301]=00 [302]=02"
+        );
+        assert_eq!(lines[3], "       3  206  1206      JP 0x206");
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let buf = SharedBuf::default();
+        traced(Tracer::json_lines(buf.clone()));
+
+        let text = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(
+            lines[0],
+            r#"{"n":0,"pc":512,"op":"6A02","asm":"LD VA, 0x02","regs":{"VA":[0,2]},"writes":[]}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"n":1,"pc":514,"op":"A300","asm":"LD I, 0x300","regs":{"I":[0,768]},"writes":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_filter_by_range_and_class() {
+        let filter = TraceFilter::new().pc_range(0x202..0x206);
+        let emu = traced(Tracer::ring(10).with_filter(filter));
+        let pcs: Vec<_> = emu.tracer().unwrap().entries().map(|e| e.pc).collect();
+        assert_eq!(pcs, [0x202, 0x204]);
+
+        let filter = TraceFilter::new().opcode_class(0x6).opcode_class(0x1);
+        let emu = traced(Tracer::ring(10).with_filter(filter));
+        let indices: Vec<_> = emu.tracer().unwrap().entries().map(|e| e.index).collect();
+        assert_eq!(indices, [0, 3]);
+    }
+
+    #[test]
+    fn test_tracing_does_not_change_execution() {
+        let rom = include_bytes!("../../roms/PONG2");
+        let mut plain = Emu::with_seed(9);
+        plain.load_rom(rom).unwrap();
+        let mut traced = Emu::with_seed(9);
+        traced.load_rom(rom).unwrap();
+        traced.set_tracer(Some(Tracer::ring(16)));
+
+        for _ in 0..30 {
+            plain.run_frame().unwrap();
+            traced.run_frame().unwrap();
+        }
+
+        assert_eq!(plain.save_state(), traced.save_state());
+        assert!(traced.take_tracer().unwrap().executed() > 0);
+    }
+
+    #[test]
+    fn test_waiting_for_key_is_recorded_once() {
+        let mut emu = Emu::new();
+        // LD VA, K / JP 0x202
+        emu.load_rom(&[0xFA, 0x0A, 0x12, 0x02]).unwrap();
+        emu.set_tracer(Some(Tracer::ring(10)));
+
+        for _ in 0..5 {
+            assert_eq!(emu.step().unwrap(), StepOutcome::WaitingForKey);
+        }
+        assert_eq!(emu.tracer().unwrap().executed(), 0);
+        emu.key_down(3);
+        emu.step().unwrap();
+        emu.step().unwrap();
+
+        let tracer = emu.tracer().unwrap();
+        let entries: Vec<_> = tracer.entries().map(|e| (e.index, e.pc)).collect();
+        assert_eq!(entries, [(0, 0x200), (1, 0x202)]);
+        assert_eq!(tracer.executed(), 2);
+    }
+
+    #[test]
+    fn test_failed_instructions_are_not_recorded() {
+        let mut emu = Emu::new();
+        // RET with an empty stack
+        emu.load_rom(&[0x00, 0xEE]).unwrap();
+        emu.set_tracer(Some(Tracer::ring(4)));
+
+        assert!(emu.step().is_err());
+        assert_eq!(emu.tracer().unwrap().entries().count(), 0);
+    }
+}
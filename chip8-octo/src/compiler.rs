@@ -0,0 +1,892 @@
+use std::collections::HashMap;
+
+use chip8_core::{Instruction, Mode, START_ADDR};
+
+use crate::CompileError;
+use crate::lexer::{Token, tokenize};
+
+/// Guards against macros that expand into themselves.
+const MAX_MACRO_EXPANSIONS: usize = 10_000;
+
+/// How a forward reference is written once its label is known.
+#[derive(Debug, Clone, Copy)]
+enum Patch {
+    /// The low 12 bits of the instruction word at the offset.
+    Addr12,
+    /// A big-endian 16-bit address (`i := long`, `:pointer`).
+    Long,
+    /// The low nibble of the byte at the offset (`:unpack` high part).
+    UnpackHigh,
+    /// The byte at the offset (`:unpack` low part).
+    UnpackLow,
+}
+
+struct Fixup {
+    addr: usize,
+    patch: Patch,
+    name: String,
+    line: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A value operand: known now, or a label defined further down.
+enum Value {
+    Known(i64),
+    Label(String),
+}
+
+/// The condition of `if` and `while`.
+#[derive(Clone, Copy)]
+enum Cond {
+    Eq(u8, Operand),
+    Ne(u8, Operand),
+    Key(u8),
+    NotKey(u8),
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Reg(u8),
+    Byte(u8),
+}
+
+impl Cond {
+    /// The instruction that skips the next one when the condition holds.
+    fn skip_if(self) -> Instruction {
+        match self {
+            Cond::Eq(x, Operand::Reg(y)) => Instruction::SeReg { x, y },
+            Cond::Eq(x, Operand::Byte(kk)) => Instruction::Se { x, kk },
+            Cond::Ne(x, Operand::Reg(y)) => Instruction::SneReg { x, y },
+            Cond::Ne(x, Operand::Byte(kk)) => Instruction::Sne { x, kk },
+            Cond::Key(x) => Instruction::Skp { x },
+            Cond::NotKey(x) => Instruction::Sknp { x },
+        }
+    }
+
+    /// The instruction that skips the next one unless the condition holds.
+    fn skip_unless(self) -> Instruction {
+        let negated = match self {
+            Cond::Eq(x, rhs) => Cond::Ne(x, rhs),
+            Cond::Ne(x, rhs) => Cond::Eq(x, rhs),
+            Cond::Key(x) => Cond::NotKey(x),
+            Cond::NotKey(x) => Cond::Key(x),
+        };
+        negated.skip_if()
+    }
+}
+
+pub(crate) struct Compiler {
+    mode: Mode,
+    /// Tokens still to compile, reversed so `pop` yields the next one.
+    tokens: Vec<Token>,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    /// Start address and pending `while` exits of each open `loop`.
+    loops: Vec<(usize, Vec<usize>)>,
+    /// Addresses of the jumps an open `begin`/`else` still has to patch.
+    branches: Vec<usize>,
+    expansions: usize,
+}
+
+impl Compiler {
+    pub(crate) fn new(source: &str, mode: Mode) -> Self {
+        let mut tokens = tokenize(source);
+        tokens.reverse();
+        Compiler {
+            mode,
+            tokens,
+            line: 1,
+            rom: Vec::new(),
+            here: START_ADDR as usize,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    pub(crate) fn compile(mut self) -> Result<Vec<u8>, CompileError> {
+        // Execution starts at 0x200; jump to `main` unless it is already there
+        let starts_with_main = self.tokens.len() >= 2
+            && self.tokens[self.tokens.len() - 1].text == ":"
+            && self.tokens[self.tokens.len() - 2].text == "main";
+        if !starts_with_main {
+            self.emit_addr(Instruction::Jp, Value::Label("main".into()))?;
+        }
+
+        while let Some(token) = self.next() {
+            self.statement(token)?;
+        }
+        if !self.loops.is_empty() {
+            return Err(self.error("`loop` without `again`"));
+        }
+        if !self.branches.is_empty() {
+            return Err(self.error("`begin` without `end`"));
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let addr = *self.labels.get(&fixup.name).ok_or_else(|| CompileError {
+                line: fixup.line,
+                message: format!("undefined name `{}`", fixup.name),
+            })?;
+            self.line = fixup.line;
+            self.patch(fixup.addr, fixup.patch, addr)?;
+        }
+        Ok(self.rom)
+    }
+
+    fn error(&self, message: impl Into<String>) -> CompileError {
+        CompileError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.pop()?;
+        self.line = token.line;
+        Some(token)
+    }
+
+    fn expect(&mut self, what: &str) -> Result<Token, CompileError> {
+        self.next()
+            .ok_or_else(|| self.error(format!("expected {}, found end of file", what)))
+    }
+
+    fn expect_text(&mut self, text: &str) -> Result<(), CompileError> {
+        let token = self.expect(&format!("`{}`", text))?;
+        if token.text != text {
+            return Err(self.error(format!("expected `{}`, found `{}`", text, token.text)));
+        }
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.last().map(|t| t.text.as_str())
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), CompileError> {
+        let text = token.text.as_str();
+        match text {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.insert(name.clone(), self.here as u16).is_some() {
+                    return Err(self.error(format!("`{}` is already defined", name)));
+                }
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+                self.aliases.insert(name, reg);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.known_value()?;
+                self.constants.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let addr = self.known_value()?;
+                if !(START_ADDR as i64..=0xFFFF).contains(&addr) {
+                    return Err(self.error(format!(":org {:#X} is outside program memory", addr)));
+                }
+                self.here = addr as usize;
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.calc()?
+                } else {
+                    self.known_value()?
+                };
+                let byte = self.byte(value)?;
+                self.write(&[byte])?;
+            }
+            ":pointer" => {
+                let value = self.value()?;
+                self.emit_long(value, 0)?;
+            }
+            ":call" => {
+                let value = self.value()?;
+                self.emit_addr(Instruction::Call, value)?;
+            }
+            ":unpack" => {
+                let nibble = self.known_value()?;
+                if !(0..=0xF).contains(&nibble) {
+                    return Err(self.error(format!("{} does not fit in a nibble", nibble)));
+                }
+                let high = (nibble as u8) << 4;
+                match self.value()? {
+                    Value::Known(addr) => {
+                        let addr = self.addr(addr)?;
+                        self.emit(Instruction::Ld {
+                            x: 0,
+                            kk: high | (addr >> 8) as u8,
+                        })?;
+                        self.emit(Instruction::Ld {
+                            x: 1,
+                            kk: addr as u8,
+                        })?;
+                    }
+                    Value::Label(name) => {
+                        self.fixup(self.here + 1, Patch::UnpackHigh, name.clone());
+                        self.emit(Instruction::Ld { x: 0, kk: high })?;
+                        self.fixup(self.here + 1, Patch::UnpackLow, name);
+                        self.emit(Instruction::Ld { x: 1, kk: 0 })?;
+                    }
+                }
+            }
+            ":proto" | ":breakpoint" => {
+                self.expect("a name")?;
+            }
+            ":monitor" => {
+                self.expect("an address")?;
+                self.expect("a length")?;
+            }
+            "clear" => self.emit(Instruction::Cls)?,
+            "return" | ";" => self.emit(Instruction::Ret)?,
+            "exit" => self.emit(Instruction::Exit)?,
+            "hires" => self.emit(Instruction::High)?,
+            "lores" => self.emit(Instruction::Low)?,
+            "scroll-left" => self.emit(Instruction::Scl)?,
+            "scroll-right" => self.emit(Instruction::Scr)?,
+            "audio" => self.emit(Instruction::Audio)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Scd(n))?;
+            }
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Scu(n))?;
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(Instruction::Plane(n))?;
+            }
+            "jump" => {
+                let value = self.value()?;
+                self.emit_addr(Instruction::Jp, value)?;
+            }
+            "jump0" => {
+                let value = self.value()?;
+                self.emit_addr(Instruction::JpV0, value)?;
+            }
+            "native" => {
+                let value = self.value()?;
+                self.emit_addr(Instruction::Sys, value)?;
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdB { x })?;
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdRVx { x })?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LdVxR { x })?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                let ins = if self.peek() == Some("-") {
+                    self.next();
+                    let y = self.register()?;
+                    if text == "save" {
+                        Instruction::LdIVxVy { x, y }
+                    } else {
+                        Instruction::LdVxVyI { x, y }
+                    }
+                } else if text == "save" {
+                    Instruction::LdIVx { x }
+                } else {
+                    Instruction::LdVxI { x }
+                };
+                self.emit(ins)?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(Instruction::Drw { x, y, n })?;
+            }
+            "i" => self.assign_i()?,
+            "delay" | "buzzer" | "pitch" => {
+                self.expect_text(":=")?;
+                let x = self.register()?;
+                self.emit(match text {
+                    "delay" => Instruction::LdDtVx { x },
+                    "buzzer" => Instruction::LdStVx { x },
+                    _ => Instruction::Pitch { x },
+                })?;
+            }
+            "if" => {
+                let cond = self.condition()?;
+                match self.expect("`then` or `begin`")?.text.as_str() {
+                    "then" => self.emit(cond.skip_unless())?,
+                    "begin" => {
+                        self.emit(cond.skip_if())?;
+                        self.branches.push(self.here);
+                        self.emit(Instruction::Jp(START_ADDR))?;
+                    }
+                    other => {
+                        return Err(
+                            self.error(format!("expected `then` or `begin`, found `{}`", other))
+                        );
+                    }
+                }
+            }
+            "else" => {
+                let pending = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| self.error("`else` without `begin`"))?;
+                self.branches.push(self.here);
+                self.emit(Instruction::Jp(START_ADDR))?;
+                self.patch(pending, Patch::Addr12, self.here as u16)?;
+            }
+            "end" => {
+                let pending = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| self.error("`end` without `begin`"))?;
+                self.patch(pending, Patch::Addr12, self.here as u16)?;
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                let cond = self.condition()?;
+                self.emit(cond.skip_if())?;
+                let exit = self.here;
+                match self.loops.last_mut() {
+                    Some((_, exits)) => exits.push(exit),
+                    None => return Err(self.error("`while` outside of a loop")),
+                }
+                self.emit(Instruction::Jp(START_ADDR))?;
+            }
+            "again" => {
+                let (start, exits) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| self.error("`again` without `loop`"))?;
+                self.emit_addr(Instruction::Jp, Value::Known(start as i64))?;
+                for exit in exits {
+                    self.patch(exit, Patch::Addr12, self.here as u16)?;
+                }
+            }
+            _ => {
+                if let Some(x) = self.lookup_register(text) {
+                    return self.assign(x);
+                }
+                if self.macros.contains_key(text) {
+                    return self.expand(text);
+                }
+                if text.starts_with(':') {
+                    return Err(self.error(format!("unsupported directive `{}`", text)));
+                }
+                match self.to_value(&token)? {
+                    Value::Known(value) if !self.labels.contains_key(text) => {
+                        let byte = self.byte(value)?;
+                        self.write(&[byte])?;
+                    }
+                    value => self.emit_addr(Instruction::Call, value)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn assign_i(&mut self) -> Result<(), CompileError> {
+        let op = self.expect("`:=` or `+=`")?;
+        match op.text.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next();
+                    let x = self.register()?;
+                    self.emit(Instruction::LdF { x })
+                }
+                Some("bighex") => {
+                    self.next();
+                    let x = self.register()?;
+                    self.emit(Instruction::LdHf { x })
+                }
+                Some("long") => {
+                    self.next();
+                    let value = self.value()?;
+                    self.emit_long(value, 0xF000)
+                }
+                _ => {
+                    let value = self.value()?;
+                    self.emit_addr(Instruction::LdI, value)
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(Instruction::AddI { x })
+            }
+            other => Err(self.error(format!("unknown operator `i {}`", other))),
+        }
+    }
+
+    fn assign(&mut self, x: u8) -> Result<(), CompileError> {
+        let op = self.expect("an operator")?.text;
+        let ins = match op.as_str() {
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next();
+                    let kk = self.byte_value()?;
+                    Instruction::Rnd { x, kk }
+                }
+                Some("key") => {
+                    self.next();
+                    Instruction::LdVxK { x }
+                }
+                Some("delay") => {
+                    self.next();
+                    Instruction::LdVxDt { x }
+                }
+                _ => match self.operand()? {
+                    Operand::Reg(y) => Instruction::LdReg { x, y },
+                    Operand::Byte(kk) => Instruction::Ld { x, kk },
+                },
+            },
+            "+=" => match self.operand()? {
+                Operand::Reg(y) => Instruction::AddReg { x, y },
+                Operand::Byte(kk) => Instruction::Add { x, kk },
+            },
+            "-=" => match self.operand()? {
+                Operand::Reg(y) => Instruction::Sub { x, y },
+                Operand::Byte(kk) => Instruction::Add {
+                    x,
+                    kk: kk.wrapping_neg(),
+                },
+            },
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let y = self.register()?;
+                match op.as_str() {
+                    "=-" => Instruction::Subn { x, y },
+                    "|=" => Instruction::Or { x, y },
+                    "&=" => Instruction::And { x, y },
+                    "^=" => Instruction::Xor { x, y },
+                    ">>=" => Instruction::Shr { x, y },
+                    _ => Instruction::Shl { x, y },
+                }
+            }
+            other => return Err(self.error(format!("unknown operator `{}`", other))),
+        };
+        self.emit(ins)
+    }
+
+    fn condition(&mut self) -> Result<Cond, CompileError> {
+        let x = self.register()?;
+        let op = self.expect("a comparison")?.text;
+        match op.as_str() {
+            "==" => Ok(Cond::Eq(x, self.operand()?)),
+            "!=" => Ok(Cond::Ne(x, self.operand()?)),
+            "key" => Ok(Cond::Key(x)),
+            "-key" => Ok(Cond::NotKey(x)),
+            "<" | ">" | "<=" | ">=" => self.compare(x, &op),
+            other => Err(self.error(format!("unknown comparison `{}`", other))),
+        }
+    }
+
+    /// Lowers an ordered comparison into a test of vf, like Octo: vf gets the
+    /// right-hand side and then the borrow flag of subtracting vx from it
+    /// (`-=`) or it from vx (`=-`).
+    fn compare(&mut self, x: u8, op: &str) -> Result<Cond, CompileError> {
+        if x == 0xF {
+            return Err(self.error(format!("comparison `{}` cannot use vf on the left", op)));
+        }
+        match self.operand()? {
+            Operand::Reg(y) => self.emit(Instruction::LdReg { x: 0xF, y })?,
+            Operand::Byte(kk) => self.emit(Instruction::Ld { x: 0xF, kk })?,
+        }
+        // vf is 1 after `vf =- vx` if vx >= rhs, and after `vf -= vx` if vx <= rhs.
+        let (ins, flag) = match op {
+            "<" => (Instruction::Subn { x: 0xF, y: x }, 0),
+            ">=" => (Instruction::Subn { x: 0xF, y: x }, 1),
+            ">" => (Instruction::Sub { x: 0xF, y: x }, 0),
+            _ => (Instruction::Sub { x: 0xF, y: x }, 1),
+        };
+        self.emit(ins)?;
+        Ok(Cond::Eq(0xF, Operand::Byte(flag)))
+    }
+
+    fn define_macro(&mut self) -> Result<(), CompileError> {
+        let name = self.name()?;
+        let mut params = Vec::new();
+        loop {
+            let token = self.expect("`{`")?;
+            if token.text == "{" {
+                break;
+            }
+            params.push(token.text);
+        }
+        let body = self.block()?;
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    /// Collects the tokens up to the `}` matching an already consumed `{`.
+    fn block(&mut self) -> Result<Vec<Token>, CompileError> {
+        let mut depth = 1;
+        let mut body = Vec::new();
+        loop {
+            let token = self.expect("`}`")?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(token);
+        }
+    }
+
+    fn expand(&mut self, name: &str) -> Result<(), CompileError> {
+        self.expansions += 1;
+        if self.expansions > MAX_MACRO_EXPANSIONS {
+            return Err(self.error(format!("macro `{}` expands without end", name)));
+        }
+        let line = self.line;
+        let params = self.macros[name].params.clone();
+        let mut args = HashMap::new();
+        for param in params {
+            let arg = self.expect(&format!("macro argument `{}`", param))?;
+            args.insert(param, arg.text);
+        }
+        let body: Vec<Token> = self.macros[name]
+            .body
+            .iter()
+            .rev()
+            .map(|token| Token {
+                text: args.get(&token.text).unwrap_or(&token.text).clone(),
+                line,
+            })
+            .collect();
+        self.tokens.extend(body);
+        Ok(())
+    }
+
+    /// Evaluates a `{ ... }` expression.
+    fn calc(&mut self) -> Result<i64, CompileError> {
+        self.expect_text("{")?;
+        let tokens = self.block()?;
+        let mut expr = Expr {
+            compiler: self,
+            tokens: &tokens,
+            pos: 0,
+        };
+        let value = expr.binary(0)?;
+        if let Some(token) = tokens.get(expr.pos) {
+            return Err(self.error(format!("unexpected `{}` in expression", token.text)));
+        }
+        Ok(value)
+    }
+
+    fn name(&mut self) -> Result<String, CompileError> {
+        let token = self.expect("a name")?;
+        if number(&token.text).is_some() || self.lookup_register(&token.text).is_some() {
+            return Err(self.error(format!("`{}` is not a valid name", token.text)));
+        }
+        Ok(token.text)
+    }
+
+    fn lookup_register(&self, text: &str) -> Option<u8> {
+        if let Some(&reg) = self.aliases.get(text) {
+            return Some(reg);
+        }
+        let digit = text.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn register(&mut self) -> Result<u8, CompileError> {
+        let token = self.expect("a register")?;
+        self.lookup_register(&token.text)
+            .ok_or_else(|| self.error(format!("expected a register, found `{}`", token.text)))
+    }
+
+    fn operand(&mut self) -> Result<Operand, CompileError> {
+        if let Some(reg) = self.peek().and_then(|t| self.lookup_register(t)) {
+            self.next();
+            return Ok(Operand::Reg(reg));
+        }
+        Ok(Operand::Byte(self.byte_value()?))
+    }
+
+    fn to_value(&self, token: &Token) -> Result<Value, CompileError> {
+        let text = token.text.as_str();
+        if let Some(value) = number(text)
+            .or_else(|| self.constants.get(text).copied())
+            .or_else(|| self.labels.get(text).map(|&a| a as i64))
+        {
+            return Ok(Value::Known(value));
+        }
+        if self.lookup_register(text).is_some() || text.starts_with(':') {
+            return Err(self.error(format!("expected a value, found `{}`", text)));
+        }
+        Ok(Value::Label(text.to_string()))
+    }
+
+    fn value(&mut self) -> Result<Value, CompileError> {
+        let token = self.expect("a value")?;
+        self.to_value(&token)
+    }
+
+    fn known_value(&mut self) -> Result<i64, CompileError> {
+        match self.value()? {
+            Value::Known(value) => Ok(value),
+            Value::Label(name) => Err(self.error(format!("undefined name `{}`", name))),
+        }
+    }
+
+    fn byte_value(&mut self) -> Result<u8, CompileError> {
+        let value = self.known_value()?;
+        self.byte(value)
+    }
+
+    fn nibble(&mut self) -> Result<u8, CompileError> {
+        let value = self.known_value()?;
+        if !(0..=0xF).contains(&value) {
+            return Err(self.error(format!("{} does not fit in a nibble", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn byte(&self, value: i64) -> Result<u8, CompileError> {
+        if !(-0x80..=0xFF).contains(&value) {
+            return Err(self.error(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn addr(&self, value: i64) -> Result<u16, CompileError> {
+        if !(0..=0xFFF).contains(&value) {
+            return Err(self.error(format!("address {:#X} does not fit in 12 bits", value)));
+        }
+        Ok(value as u16)
+    }
+
+    fn fixup(&mut self, addr: usize, patch: Patch, name: String) {
+        self.fixups.push(Fixup {
+            addr,
+            patch,
+            name,
+            line: self.line,
+        });
+    }
+
+    /// Emits an instruction taking a 12-bit address, deferring unknown labels.
+    fn emit_addr(
+        &mut self,
+        make: fn(u16) -> Instruction,
+        value: Value,
+    ) -> Result<(), CompileError> {
+        match value {
+            Value::Known(addr) => {
+                let addr = self.addr(addr)?;
+                self.emit(make(addr))
+            }
+            Value::Label(name) => {
+                self.fixup(self.here, Patch::Addr12, name);
+                self.emit(make(START_ADDR))
+            }
+        }
+    }
+
+    /// Emits `prefix` (if non-zero) followed by a 16-bit address.
+    fn emit_long(&mut self, value: Value, prefix: u16) -> Result<(), CompileError> {
+        let addr = match value {
+            Value::Known(addr) if (0..=0xFFFF).contains(&addr) => addr as u16,
+            Value::Known(addr) => {
+                return Err(self.error(format!("address {:#X} does not fit in 16 bits", addr)));
+            }
+            Value::Label(name) => {
+                let at = self.here + if prefix == 0 { 0 } else { 2 };
+                self.fixup(at, Patch::Long, name);
+                0
+            }
+        };
+        if prefix == 0 {
+            self.write(&addr.to_be_bytes())
+        } else {
+            self.emit(Instruction::LdILong(addr))
+        }
+    }
+
+    /// Writes an instruction after checking the target platform has it.
+    fn emit(&mut self, ins: Instruction) -> Result<(), CompileError> {
+        let available = |mode| match ins {
+            Instruction::LdILong(_) => mode == Mode::XoChip,
+            _ => Instruction::decode(ins.encode(), mode) == Some(ins),
+        };
+        if !available(self.mode) {
+            let platform = if available(Mode::SuperChip) {
+                "SUPER-CHIP"
+            } else {
+                "XO-CHIP"
+            };
+            return Err(self.error(format!("`{}` needs {}", ins, platform)));
+        }
+        self.write(&ins.to_bytes())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), CompileError> {
+        let offset = self.here - START_ADDR as usize;
+        let end = offset + bytes.len();
+        if START_ADDR as usize + end > self.mode.ram_size() {
+            return Err(self.error("program does not fit in memory"));
+        }
+        if self.rom.len() < end {
+            self.rom.resize(end, 0);
+        }
+        self.rom[offset..end].copy_from_slice(bytes);
+        self.here += bytes.len();
+        Ok(())
+    }
+
+    fn patch(&mut self, addr: usize, patch: Patch, target: u16) -> Result<(), CompileError> {
+        let offset = addr - START_ADDR as usize;
+        match patch {
+            Patch::Addr12 => {
+                let target = self.addr(target as i64)?;
+                self.rom[offset] = (self.rom[offset] & 0xF0) | (target >> 8) as u8;
+                self.rom[offset + 1] = target as u8;
+            }
+            Patch::Long => self.rom[offset..offset + 2].copy_from_slice(&target.to_be_bytes()),
+            Patch::UnpackHigh => {
+                let target = self.addr(target as i64)?;
+                self.rom[offset] |= (target >> 8) as u8;
+            }
+            Patch::UnpackLow => self.rom[offset] = target as u8,
+        }
+        Ok(())
+    }
+}
+
+/// Parses decimal, `0x` hex and `0b` binary literals, optionally negative.
+fn number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.chars().all(|c| c.is_ascii_digit()) && !digits.is_empty() {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Precedence climbing over the tokens of a `:calc` expression.
+struct Expr<'a> {
+    compiler: &'a Compiler,
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+const BINARY_OPS: [(&str, u8); 11] = [
+    ("|", 1),
+    ("^", 2),
+    ("&", 3),
+    ("<<", 4),
+    (">>", 4),
+    ("+", 5),
+    ("-", 5),
+    ("*", 6),
+    ("/", 6),
+    ("%", 6),
+    ("pow", 7),
+];
+
+impl Expr<'_> {
+    fn binary(&mut self, min_prec: u8) -> Result<i64, CompileError> {
+        let mut lhs = self.unary()?;
+        while let Some(&(op, prec)) = self
+            .tokens
+            .get(self.pos)
+            .and_then(|t| BINARY_OPS.iter().find(|(op, _)| *op == t.text))
+        {
+            if prec < min_prec {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(prec + 1)?;
+            let as_u32 = || u32::try_from(rhs).ok();
+            lhs = match op {
+                "|" => Some(lhs | rhs),
+                "^" => Some(lhs ^ rhs),
+                "&" => Some(lhs & rhs),
+                "<<" => as_u32().and_then(|rhs| lhs.checked_shl(rhs)),
+                ">>" => as_u32().and_then(|rhs| lhs.checked_shr(rhs)),
+                "+" => lhs.checked_add(rhs),
+                "-" => lhs.checked_sub(rhs),
+                "*" => lhs.checked_mul(rhs),
+                "pow" => as_u32().and_then(|rhs| lhs.checked_pow(rhs)),
+                _ if rhs == 0 => return Err(self.compiler.error("division by zero")),
+                "/" => lhs.checked_div(rhs),
+                _ => lhs.checked_rem(rhs),
+            }
+            .ok_or_else(|| self.compiler.error(format!("`{}` out of range", op)))?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, CompileError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| self.compiler.error("expression ends early"))?;
+        self.pos += 1;
+        match token.text.as_str() {
+            "-" => self
+                .unary()?
+                .checked_neg()
+                .ok_or_else(|| self.compiler.error("`-` out of range")),
+            "~" => Ok(!self.unary()?),
+            "(" => {
+                let value = self.binary(0)?;
+                match self.tokens.get(self.pos) {
+                    Some(t) if t.text == ")" => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err(self.compiler.error("missing `)`")),
+                }
+            }
+            "HERE" => Ok(self.compiler.here as i64),
+            text => number(text)
+                .or_else(|| self.compiler.constants.get(text).copied())
+                .or_else(|| self.compiler.labels.get(text).map(|&a| a as i64))
+                .ok_or_else(|| self.compiler.error(format!("undefined name `{}`", text))),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+/// A whitespace-separated Octo token and the line it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    pub text: String,
+    pub line: usize,
+}
+
+/// Splits source into tokens, dropping `#` comments.
+pub(crate) fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap();
+        tokens.extend(code.split_whitespace().map(|text| Token {
+            text: text.to_string(),
+            line: index + 1,
+        }));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_skips_comments_and_tracks_lines() {
+        let tokens = tokenize(": main # entry\n  v0 := 1\n# nothing\nclear");
+        let texts: Vec<_> = tokens.iter().map(|t| (t.text.as_str(), t.line)).collect();
+
+        assert_eq!(
+            texts,
+            [
+                (":", 1),
+                ("main", 1),
+                ("v0", 2),
+                (":=", 2),
+                ("1", 2),
+                ("clear", 4)
+            ]
+        );
+    }
+}
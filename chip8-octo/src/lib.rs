@@ -0,0 +1,344 @@
+//! A compiler for Octo, the assembly language most modern CHIP-8,
+//! SUPER-CHIP and XO-CHIP homebrew is written in.
+//!
+//! Supported: `: label`, `:alias`, `:const`, `:calc`, `:macro`, `:org`,
+//! `:byte`, `:pointer`, `:call`, `:unpack`, `loop`/`while`/`again`,
+//! `if ... then` and `if ... begin ... else ... end`, and every statement
+//! that maps to a chip8-core `Instruction`. Like in Octo, the comparisons
+//! `<`, `>`, `<=` and `>=` overwrite vf.
+//!
+//! Each instruction is checked against the target `Mode` by decoding it
+//! back with `Instruction::decode`, so a ROM that compiles runs on that mode.
+
+mod compiler;
+mod error;
+mod lexer;
+
+pub use error::CompileError;
+
+use chip8_core::Mode;
+
+use compiler::Compiler;
+
+/// Compiles Octo source into a program image loaded at `START_ADDR`.
+pub fn compile(source: &str, mode: Mode) -> Result<Vec<u8>, CompileError> {
+    Compiler::new(source, mode).compile()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::{Disassembly, Emu, Instruction, Syntax};
+
+    fn octo(source: &str) -> Vec<u8> {
+        compile(source, Mode::XoChip).unwrap()
+    }
+
+    fn error(source: &str, mode: Mode) -> (usize, String) {
+        let err = compile(source, mode).unwrap_err();
+        (err.line, err.message)
+    }
+
+    #[test]
+    fn test_statements() {
+        let rom = octo(
+            ": main
+                clear
+                v1 := 0x2A  v2 := v1  v3 += 5  v3 -= 1  v4 := random 0x0F
+                v5 |= v6  v5 &= v6  v5 ^= v6  v5 -= v6  v5 =- v6  v5 >>= v6  v5 <<= v6
+                i := 0x300  i += v1  i := hex v2  i := bighex v2  i := long 0xBEEF
+                sprite v1 v2 5  bcd v3  save v4  load v4  save v1 - v3  load v1 - v3
+                delay := v1  buzzer := v1  pitch := v1  v1 := delay  v1 := key
+                saveflags v2  loadflags v2  plane 3  audio
+                hires lores scroll-down 2 scroll-up 3 scroll-left scroll-right
+                exit return ;",
+        );
+        let expected: Vec<u8> = [
+            0x00E0u16, 0x612A, 0x8210, 0x7305, 0x73FF, 0xC40F, //
+            0x8561, 0x8562, 0x8563, 0x8565, 0x8567, 0x8566, 0x856E, //
+            0xA300, 0xF11E, 0xF229, 0xF230, 0xF000, 0xBEEF, //
+            0xD125, 0xF333, 0xF455, 0xF465, 0x5132, 0x5133, //
+            0xF115, 0xF118, 0xF13A, 0xF107, 0xF10A, //
+            0xF275, 0xF285, 0xF301, 0xF002, //
+            0x00FF, 0x00FE, 0x00C2, 0x00D3, 0x00FC, 0x00FB, //
+            0x00FD, 0x00EE, 0x00EE,
+        ]
+        .iter()
+        .flat_map(|w| w.to_be_bytes())
+        .collect();
+
+        assert_eq!(rom, expected);
+    }
+
+    #[test]
+    fn test_main_jump_and_forward_calls() {
+        let rom = compile(
+            ": helper return
+             : main helper :call helper jump main",
+            Mode::Chip8,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rom,
+            [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02, 0x22, 0x02, 0x12, 0x04]
+        );
+    }
+
+    #[test]
+    fn test_alias_const_calc() {
+        let rom = octo(
+            ": main
+             :alias px v3
+             :const SPEED 4
+             :calc DOUBLE { SPEED * 2 + ( 1 << 4 ) }
+             px := SPEED  px += DOUBLE
+             :byte { DOUBLE - 1 }  SPEED  0xFF",
+        );
+
+        assert_eq!(rom, [0x63, 0x04, 0x73, 0x18, 0x17, 0x04, 0xFF]);
+    }
+
+    #[test]
+    fn test_loop_while_again() {
+        let rom = octo(
+            ": main
+             loop
+               v0 += 1
+               while v0 != 10
+             again",
+        );
+
+        // 200: ADD; 202: SNE V0, 10; 204: JP 208; 206: JP 200
+        assert_eq!(rom, [0x70, 0x01, 0x40, 0x0A, 0x12, 0x08, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn test_if_then_and_blocks() {
+        let rom = octo(
+            ": main
+             if v0 == 3 then v1 := 1
+             if v0 key begin
+               v2 := 1
+             else
+               v2 := 2
+             end
+             if v1 != v2 then clear",
+        );
+
+        assert_eq!(
+            rom,
+            [
+                0x40, 0x03, 0x61, 0x01, // if v0 == 3 then
+                0xE0, 0x9E, 0x12, 0x0C, 0x62, 0x01, 0x12, 0x0E, 0x62, 0x02, // begin/else/end
+                0x51, 0x20, 0x00, 0xE0, // if v1 != v2 then
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ordered_comparisons() {
+        let rom = octo(
+            ": main
+             if v1 < 5 then clear
+             if v1 >= v2 then clear
+             if v1 > v2 then clear
+             if v1 <= 5 then clear",
+        );
+
+        assert_eq!(
+            rom,
+            [
+                0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x00, 0x00, 0xE0, // if v1 < 5 then
+                0x8F, 0x20, 0x8F, 0x17, 0x4F, 0x01, 0x00, 0xE0, // if v1 >= v2 then
+                0x8F, 0x20, 0x8F, 0x15, 0x4F, 0x00, 0x00, 0xE0, // if v1 > v2 then
+                0x6F, 0x05, 0x8F, 0x15, 0x4F, 0x01, 0x00, 0xE0, // if v1 <= 5 then
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macros() {
+        let rom = octo(
+            ":macro move reg amount { reg += amount }
+             :macro twice x { move x 1 move x 1 }
+             : main twice v4",
+        );
+
+        // `main` is not at 0x200, so the ROM opens with a jump to it
+        assert_eq!(rom, [0x12, 0x02, 0x74, 0x01, 0x74, 0x01]);
+    }
+
+    #[test]
+    fn test_recursive_macro_is_reported() {
+        let (line, message) = error(":macro m { m } : main m", Mode::Chip8);
+
+        assert_eq!(line, 1);
+        assert!(message.contains("expands without end"));
+    }
+
+    #[test]
+    fn test_data_labels_org_and_unpack() {
+        let rom = octo(
+            ": main
+             i := sprite
+             :unpack 0xA sprite
+             i := long far
+             :org 0x210
+             : sprite 0x3C 0x42
+             : far :pointer sprite",
+        );
+
+        assert_eq!(
+            &rom[..10],
+            [0xA2, 0x10, 0x60, 0xA2, 0x61, 0x10, 0xF0, 0x00, 0x02, 0x12]
+        );
+        assert_eq!(&rom[0x10..], [0x3C, 0x42, 0x02, 0x10]);
+    }
+
+    #[test]
+    fn test_errors_point_at_the_line() {
+        assert_eq!(
+            error(": main\n  v0 := 300", Mode::Chip8),
+            (2, "300 does not fit in a byte".into())
+        );
+        assert_eq!(
+            error(": main\n\n  jump nowhere", Mode::Chip8),
+            (3, "undefined name `nowhere`".into())
+        );
+        assert_eq!(
+            error(": main\n  hires", Mode::Chip8),
+            (2, "`HIGH` needs SUPER-CHIP".into())
+        );
+        assert_eq!(
+            error(": main\n  plane 1", Mode::SuperChip),
+            (2, "`PLANE 1` needs XO-CHIP".into())
+        );
+        assert_eq!(
+            error(": main\n  loop\n  v0 += 1", Mode::Chip8),
+            (3, "`loop` without `again`".into())
+        );
+        assert_eq!(
+            error(": main\n  if vf < v1 then clear", Mode::Chip8),
+            (2, "comparison `<` cannot use vf on the left".into())
+        );
+        assert_eq!(
+            error(": a : a", Mode::Chip8),
+            (1, "`a` is already defined".into())
+        );
+        assert_eq!(
+            compile(": main :stringmode", Mode::Chip8)
+                .unwrap_err()
+                .to_string(),
+            "line 1: unsupported directive `:stringmode`"
+        );
+    }
+
+    #[test]
+    fn test_calc_out_of_range() {
+        let calc = |expr: &str| error(&format!(":calc X {{ {} }}", expr), Mode::Chip8).1;
+
+        assert_eq!(calc("1 << 64"), "`<<` out of range");
+        assert_eq!(calc("1 >> -1"), "`>>` out of range");
+        assert_eq!(calc("0x7FFFFFFFFFFFFFFF + 1"), "`+` out of range");
+        assert_eq!(calc("-0x7FFFFFFFFFFFFFFF - 2"), "`-` out of range");
+        assert_eq!(calc("0x100000000 * 0x100000000"), "`*` out of range");
+        assert_eq!(calc("2 pow 64"), "`pow` out of range");
+        assert_eq!(calc("2 pow -1"), "`pow` out of range");
+        assert_eq!(calc("1 / 0"), "division by zero");
+        assert_eq!(calc("( -0x7FFFFFFFFFFFFFFF - 1 ) / -1"), "`/` out of range");
+        assert_eq!(calc("( -0x7FFFFFFFFFFFFFFF - 1 ) % -1"), "`%` out of range");
+        assert_eq!(calc("- ( -0x7FFFFFFFFFFFFFFF - 1 )"), "`-` out of range");
+    }
+
+    #[test]
+    fn test_every_instruction_round_trips_through_disassembler() {
+        for op in 0..=u16::MAX {
+            let [high, low] = op.to_be_bytes();
+            // `F000` takes its address from the word after it
+            let Some(ins) = Instruction::read(&[high, low, 0xAB, 0xCD], Mode::XoChip) else {
+                continue;
+            };
+            let mut bytes = ins.to_bytes();
+            if matches!(ins, Instruction::Se { .. } | Instruction::Sne { .. })
+                || matches!(ins, Instruction::SeReg { .. } | Instruction::SneReg { .. })
+                || matches!(ins, Instruction::Skp { .. } | Instruction::Sknp { .. })
+            {
+                // Octo needs a statement after `then`
+                bytes.extend([0x00, 0xE0]);
+            }
+            let source = Disassembly::new(&bytes, Mode::XoChip).listing(Syntax::Octo);
+
+            assert_eq!(compile(&source, Mode::XoChip).unwrap(), bytes, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_pong2_round_trips_through_disassembler() {
+        let rom = include_bytes!("../../roms/PONG2");
+        let source = Disassembly::new(rom, Mode::Chip8).listing(Syntax::Octo);
+
+        assert_eq!(compile(&source, Mode::Chip8).unwrap(), rom);
+    }
+
+    #[test]
+    fn test_compiled_program_runs() {
+        let rom = compile(
+            ": main
+               v0 := 0
+               v1 := 10
+               loop
+                 v0 += 3
+                 v1 += -1
+                 while v1 != 0
+               again
+               delay := v0
+             : done jump done",
+            Mode::Chip8,
+        )
+        .unwrap();
+        let mut emu = Emu::new();
+        emu.load_rom(&rom).unwrap();
+
+        for _ in 0..100 {
+            emu.step().unwrap();
+        }
+
+        assert_eq!(emu.delay_timer(), 30);
+    }
+
+    #[test]
+    fn test_ordered_comparisons_run() {
+        for (a, b) in [(3, 7), (7, 7), (7, 3)] {
+            let rom = compile(
+                &format!(
+                    ": main
+                       v0 := {}
+                       v1 := {}
+                       v2 := 0
+                       if v0 < v1 then v2 += 1
+                       if v0 > v1 then v2 += 2
+                       if v0 <= v1 then v2 += 4
+                       if v0 >= v1 then v2 += 8
+                     : done jump done",
+                    a, b
+                ),
+                Mode::Chip8,
+            )
+            .unwrap();
+            let mut emu = Emu::new();
+            emu.load_rom(&rom).unwrap();
+
+            for _ in 0..100 {
+                emu.step().unwrap();
+            }
+
+            let expected = match a.cmp(&b) {
+                std::cmp::Ordering::Less => 1 + 4,
+                std::cmp::Ordering::Equal => 4 + 8,
+                std::cmp::Ordering::Greater => 2 + 8,
+            };
+            assert_eq!(emu.v_reg()[2], expected, "{} vs {}", a, b);
+        }
+    }
+}
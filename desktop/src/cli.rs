@@ -0,0 +1,470 @@
+use std::path::PathBuf;
+
+use chip8_core::{MemoryIncrement, Mode, Palette, Persistence, Quirks, Speed, Syntax, TraceFilter};
+
+pub const USAGE: &str = "\
+Usage: chips-and-rust <command> <rom> [options]
+
+Commands:
+  play <rom>         Play in the terminal (Esc quits, F5 restarts)
+  debug <rom>        Debug in the terminal with breakpoints and stepping
+  run <rom>          Run without a window and print the final screen
+  disasm <rom>       Print a disassembly
+  trace <rom>        Print every instruction executed
+  screenshot <rom>   Save the screen as a PNG image (needs --out)
+  record <rom>       Record the frames as an animated GIF (needs --out)
+  audio <rom>        Record the beeper as a WAV file (needs --out)
+  info <rom>         Print size, SHA-1 and detected platform
+  help               Print this message
+
+Options:
+  --mode <mode>      chip8, schip or xochip (default: detected from the ROM)
+  --speed <ips>      Instructions per second (default: 700)
+  --quirks <list>    Comma-separated quirks on top of the platform's own:
+                     a preset (vip, chip48, schip, xochip), a quirk to turn
+                     on (shift, jump, vf-reset, clip, display-wait), the same
+                     with a no- prefix to turn it off, or memory=<i|x|x+1>
+  --seed <n>         Seed for the random number generator
+  --frames <n>       60 Hz frames to run (default: 600 for run, record and
+                     audio, 60 otherwise)
+  --out <file>       Write the output to a file instead of stdout
+  --palette <name>   mono, green, amber or xochip (default: mono)
+  --colors <list>    Background and foreground as hex RGB over the palette,
+                     optionally followed by the XO-CHIP plane 2 and overlap
+                     colours, e.g. 000000,ffffff
+  --phosphor <mode>  play, screenshot, record: reduce flicker by keeping pixels lit
+                     for the next frame (blend) or fading them out over
+                     <n> frames (default: off)
+  --scale <n>        screenshot, record: size of each pixel (default: 1)
+  --tone <hz>        audio: beeper pitch (default: 440)
+  --volume <percent> audio: beeper volume (default: 25)
+  --syntax <syntax>  disasm: classic or octo (default: classic)
+  --json             trace: JSON lines instead of text
+  --range <from-to>  trace: only addresses in this hex range, e.g. 200-2ff
+  --class <digit>    trace: only opcodes starting with this hex digit
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Play,
+    Debug,
+    Run,
+    Disasm,
+    Trace,
+    Screenshot,
+    Record,
+    Audio,
+    Info,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub rom: PathBuf,
+    pub mode: Option<Mode>,
+    pub speed: Option<Speed>,
+    /// `--quirks` items, applied in order on top of the platform's defaults.
+    pub quirks: Vec<String>,
+    pub seed: Option<u64>,
+    pub frames: Option<u32>,
+    pub out: Option<PathBuf>,
+    pub syntax: Syntax,
+    pub json: bool,
+    pub filter: TraceFilter,
+    pub palette: Palette,
+    pub scale: usize,
+    pub persistence: Persistence,
+    /// Beeper pitch in Hz.
+    pub tone: Option<u16>,
+    /// Beeper volume in percent.
+    pub volume: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Invocation {
+    Help,
+    Command(Command, Options),
+}
+
+/// Parses the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Invocation, String> {
+    let mut args = args.into_iter();
+    let command = match args.next().as_deref() {
+        None | Some("help" | "--help" | "-h") => return Ok(Invocation::Help),
+        Some("play") => Command::Play,
+        Some("debug") => Command::Debug,
+        Some("run") => Command::Run,
+        Some("disasm") => Command::Disasm,
+        Some("trace") => Command::Trace,
+        Some("screenshot") => Command::Screenshot,
+        Some("record") => Command::Record,
+        Some("audio") => Command::Audio,
+        Some("info") => Command::Info,
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+    };
+
+    let mut rom = None;
+    let mut options = Options {
+        rom: PathBuf::new(),
+        mode: None,
+        speed: None,
+        quirks: Vec::new(),
+        seed: None,
+        frames: None,
+        out: None,
+        syntax: Syntax::Classic,
+        json: false,
+        filter: TraceFilter::new(),
+        palette: Palette::default(),
+        scale: 1,
+        persistence: Persistence::Off,
+        tone: None,
+        volume: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("`{}` needs a value", name));
+        match arg.as_str() {
+            "--mode" => options.mode = Some(parse_mode(&value("--mode")?)?),
+            "--speed" => {
+                let ips = parse_number("--speed", &value("--speed")?)?;
+                if !(1..=1_000_000).contains(&ips) {
+                    return Err("`--speed` must be between 1 and 1000000".to_string());
+                }
+                options.speed = Some(Speed::ips(ips));
+            }
+            "--quirks" => {
+                for item in value("--quirks")?.split(',') {
+                    apply_quirk(&mut Quirks::default(), item)?;
+                    options.quirks.push(item.to_string());
+                }
+            }
+            "--seed" => options.seed = Some(parse_number("--seed", &value("--seed")?)?),
+            "--frames" => options.frames = Some(parse_number("--frames", &value("--frames")?)?),
+            "--out" => options.out = Some(PathBuf::from(value("--out")?)),
+            "--syntax" => {
+                options.syntax = match value("--syntax")?.as_str() {
+                    "classic" => Syntax::Classic,
+                    "octo" => Syntax::Octo,
+                    other => return Err(format!("unknown syntax `{}`", other)),
+                }
+            }
+            "--json" => options.json = true,
+            "--palette" => {
+                options.palette = match value("--palette")?.as_str() {
+                    "mono" => Palette::MONOCHROME,
+                    "green" => Palette::CLASSIC_GREEN,
+                    "amber" => Palette::AMBER,
+                    "xochip" | "xo-chip" => Palette::XO_CHIP,
+                    other => return Err(format!("unknown palette `{}`", other)),
+                }
+            }
+            "--colors" => options.palette = parse_palette(options.palette, &value("--colors")?)?,
+            "--phosphor" => {
+                options.persistence = match value("--phosphor")?.as_str() {
+                    "off" => Persistence::Off,
+                    "blend" => Persistence::Blend,
+                    frames => match frames.parse() {
+                        Ok(frames) if frames > 0 => Persistence::Decay(frames),
+                        _ => {
+                            return Err(format!(
+                                "bad phosphor `{}`, expected off, blend or 1-255 frames",
+                                frames
+                            ));
+                        }
+                    },
+                }
+            }
+            "--tone" => {
+                let tone = parse_number("--tone", &value("--tone")?)?;
+                if !(20..=20_000).contains(&tone) {
+                    return Err("`--tone` must be between 20 and 20000 Hz".to_string());
+                }
+                options.tone = Some(tone);
+            }
+            "--volume" => {
+                let volume = parse_number("--volume", &value("--volume")?)?;
+                if volume > 100 {
+                    return Err("`--volume` must be at most 100".to_string());
+                }
+                options.volume = Some(volume);
+            }
+            "--scale" => {
+                options.scale = parse_number("--scale", &value("--scale")?)?;
+                if !(1..=32).contains(&options.scale) {
+                    return Err("`--scale` must be between 1 and 32".to_string());
+                }
+            }
+            "--range" => {
+                let range = value("--range")?;
+                let (from, to) = range
+                    .split_once('-')
+                    .and_then(|(from, to)| Some((parse_hex(from)?, parse_hex(to)?)))
+                    .ok_or(format!("bad range `{}`, expected e.g. 200-2ff", range))?;
+                options.filter = options.filter.pc_range(from..to.saturating_add(1));
+            }
+            "--class" => {
+                let class = value("--class")?;
+                let digit = parse_hex(&class)
+                    .filter(|digit| *digit < 0x10)
+                    .ok_or(format!(
+                        "bad opcode class `{}`, expected a hex digit",
+                        class
+                    ))?;
+                options.filter = options.filter.opcode_class(digit as u8);
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option `{}`", flag)),
+            path if rom.is_none() => rom = Some(PathBuf::from(path)),
+            extra => return Err(format!("unexpected argument `{}`", extra)),
+        }
+    }
+
+    options.rom = rom.ok_or("missing ROM path")?;
+    match command {
+        Command::Screenshot if options.out.is_none() => {
+            return Err("`screenshot` needs `--out <file>`".to_string());
+        }
+        Command::Record if options.out.is_none() => {
+            return Err("`record` needs `--out <file>`".to_string());
+        }
+        Command::Audio if options.out.is_none() => {
+            return Err("`audio` needs `--out <file>`".to_string());
+        }
+        _ => {}
+    }
+    Ok(Invocation::Command(command, options))
+}
+
+pub fn parse_mode(name: &str) -> Result<Mode, String> {
+    match name {
+        "chip8" | "chip-8" => Ok(Mode::Chip8),
+        "schip" | "superchip" | "super-chip" => Ok(Mode::SuperChip),
+        "xochip" | "xo-chip" => Ok(Mode::XoChip),
+        _ => Err(format!("unknown mode `{}`", name)),
+    }
+}
+
+pub fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Chip8 => "CHIP-8",
+        Mode::SuperChip => "SUPER-CHIP",
+        Mode::XoChip => "XO-CHIP",
+    }
+}
+
+/// Applies one `--quirks` item to `quirks`.
+pub fn apply_quirk(quirks: &mut Quirks, item: &str) -> Result<(), String> {
+    let (name, on) = match item.strip_prefix("no-") {
+        Some(name) => (name, false),
+        None => (item, true),
+    };
+    match (name, on) {
+        ("vip", true) => *quirks = Quirks::COSMAC_VIP,
+        ("chip48", true) => *quirks = Quirks::CHIP48,
+        ("schip", true) => *quirks = Quirks::SCHIP_1_1,
+        ("xochip", true) => *quirks = Quirks::XO_CHIP,
+        ("shift", _) => quirks.shift_vx = on,
+        ("jump", _) => quirks.jump_vx = on,
+        ("vf-reset", _) => quirks.vf_reset = on,
+        ("clip", _) => quirks.clipping = on,
+        ("display-wait", _) => quirks.display_wait = on,
+        ("memory=i", true) => quirks.memory_increment = MemoryIncrement::Unchanged,
+        ("memory=x", true) => quirks.memory_increment = MemoryIncrement::ByX,
+        ("memory=x+1", true) => quirks.memory_increment = MemoryIncrement::ByXPlusOne,
+        _ => return Err(format!("unknown quirk `{}`", item)),
+    }
+    Ok(())
+}
+
+/// Parses 2 or 4 comma-separated `RRGGBB` colours over `palette`.
+fn parse_palette(mut palette: Palette, list: &str) -> Result<Palette, String> {
+    let colors: Vec<&str> = list.split(',').collect();
+    if colors.len() != 2 && colors.len() != 4 {
+        return Err(format!(
+            "`--colors` expects 2 or 4 colours, got {}",
+            colors.len()
+        ));
+    }
+    for (slot, color) in palette.0.iter_mut().zip(colors) {
+        let hex = color.trim_start_matches('#');
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or(format!("bad colour `{}`, expected RRGGBB", color))?;
+        let [_, r, g, b] = rgb.to_be_bytes();
+        *slot = [r, g, b, 0xFF];
+    }
+    Ok(palette)
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` expects a number, got `{}`", name, value))
+}
+
+fn parse_hex(value: &str) -> Option<u16> {
+    let value = value.trim_start_matches("0x");
+    u16::from_str_radix(value, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn options(line: &str) -> (Command, Options) {
+        match parse(args(line)) {
+            Ok(Invocation::Command(command, options)) => (command, options),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!(parse(args("")), Ok(Invocation::Help));
+        assert_eq!(parse(args("--help")), Ok(Invocation::Help));
+    }
+
+    #[test]
+    fn test_run_options() {
+        let (command, opts) = options("run game.ch8 --speed 1000 --mode schip --frames 5");
+
+        assert_eq!(command, Command::Run);
+        assert_eq!(opts.rom, PathBuf::from("game.ch8"));
+        assert_eq!(opts.speed, Some(Speed::FAST));
+        assert_eq!(opts.mode, Some(Mode::SuperChip));
+        assert_eq!(opts.frames, Some(5));
+    }
+
+    #[test]
+    fn test_quirks() {
+        let (_, opts) = options("run game.ch8 --quirks vip,no-display-wait,memory=x");
+        let mut quirks = Quirks::default();
+        for item in &opts.quirks {
+            apply_quirk(&mut quirks, item).unwrap();
+        }
+
+        assert_eq!(
+            quirks,
+            Quirks {
+                display_wait: false,
+                memory_increment: MemoryIncrement::ByX,
+                ..Quirks::COSMAC_VIP
+            }
+        );
+        assert_eq!(
+            parse(args("run game.ch8 --quirks wrap")),
+            Err("unknown quirk `wrap`".to_string())
+        );
+        assert_eq!(
+            parse(args("run game.ch8 --quirks no-vip")),
+            Err("unknown quirk `no-vip`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trace_filter() {
+        let (_, opts) = options("trace game.ch8 --range 200-2ff --class d --json");
+
+        assert!(opts.json);
+        assert_eq!(
+            opts.filter,
+            TraceFilter::new().pc_range(0x200..0x300).opcode_class(0xD)
+        );
+    }
+
+    #[test]
+    fn test_colors() {
+        let (command, opts) = options("play game.ch8 --colors 996600,#FFCC00");
+
+        assert_eq!(command, Command::Play);
+        assert_eq!(opts.palette.0[0], [0x99, 0x66, 0x00, 0xFF]);
+        assert_eq!(opts.palette.0[1], [0xFF, 0xCC, 0x00, 0xFF]);
+        assert_eq!(opts.palette.0[2], Palette::default().0[2]);
+
+        let (_, opts) = options("play game.ch8 --palette amber --colors 000000,ffffff");
+        assert_eq!(opts.palette.0[1], [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(opts.palette.0[2], Palette::AMBER.0[2]);
+        assert_eq!(
+            parse(args("play game.ch8 --palette blue")),
+            Err("unknown palette `blue`".to_string())
+        );
+        assert_eq!(
+            parse(args("play game.ch8 --colors 000000")),
+            Err("`--colors` expects 2 or 4 colours, got 1".to_string())
+        );
+        assert_eq!(
+            parse(args("play game.ch8 --colors 000000,fff")),
+            Err("bad colour `fff`, expected RRGGBB".to_string())
+        );
+    }
+
+    #[test]
+    fn test_phosphor() {
+        assert_eq!(options("play game.ch8").1.persistence, Persistence::Off);
+        assert_eq!(
+            options("play game.ch8 --phosphor blend").1.persistence,
+            Persistence::Blend
+        );
+        assert_eq!(
+            options("play game.ch8 --phosphor 6").1.persistence,
+            Persistence::Decay(6)
+        );
+        assert_eq!(
+            parse(args("play game.ch8 --phosphor 0")),
+            Err("bad phosphor `0`, expected off, blend or 1-255 frames".to_string())
+        );
+    }
+
+    #[test]
+    fn test_audio_options() {
+        let (command, opts) = options("audio game.ch8 --out beep.wav --tone 880 --volume 50");
+
+        assert_eq!(command, Command::Audio);
+        assert_eq!(opts.tone, Some(880));
+        assert_eq!(opts.volume, Some(50));
+        assert_eq!(
+            parse(args("audio game.ch8 --out beep.wav --volume 150")),
+            Err("`--volume` must be at most 100".to_string())
+        );
+        assert_eq!(
+            parse(args("audio game.ch8")),
+            Err("`audio` needs `--out <file>`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse(args("launch game.ch8")),
+            Err("unknown command `launch`".to_string())
+        );
+        assert_eq!(parse(args("run")), Err("missing ROM path".to_string()));
+        assert_eq!(
+            parse(args("run game.ch8 --frames")),
+            Err("`--frames` needs a value".to_string())
+        );
+        assert_eq!(
+            parse(args("run game.ch8 --frames many")),
+            Err("`--frames` expects a number, got `many`".to_string())
+        );
+        assert_eq!(
+            parse(args("screenshot game.ch8")),
+            Err("`screenshot` needs `--out <file>`".to_string())
+        );
+        assert_eq!(
+            parse(args("run game.ch8 --speed 4294967295")),
+            Err("`--speed` must be between 1 and 1000000".to_string())
+        );
+        assert_eq!(
+            parse(args("record game.ch8 --out a.gif --scale 600")),
+            Err("`--scale` must be between 1 and 32".to_string())
+        );
+        assert_eq!(
+            parse(args("run a.ch8 b.ch8")),
+            Err("unexpected argument `b.ch8`".to_string())
+        );
+    }
+}
@@ -0,0 +1,281 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use chip8_core::{
+    Beeper, Disassembly, Emu, GifRecorder, Image, Mode, Persistence, Phosphor, Renderer,
+    StepOutcome, Tracer, WavWriter,
+};
+
+use crate::cli::{self, Command, Options};
+use crate::{debugger, sha1, tui};
+
+const RUN_FRAMES: u32 = 600;
+const DEFAULT_FRAMES: u32 = 60;
+const SAMPLE_RATE: u32 = 44_100;
+
+pub fn execute(command: Command, options: &Options) -> Result<(), String> {
+    let rom = fs::read(&options.rom)
+        .map_err(|err| format!("cannot read {}: {}", options.rom.display(), err))?;
+    let mode = options.mode.unwrap_or_else(|| Mode::detect(&rom));
+    match command {
+        Command::Play => {
+            let emu = machine(&rom, mode, options)?;
+            return tui::play(emu, options.palette, options.persistence);
+        }
+        Command::Debug => return debugger::debug(machine(&rom, mode, options)?, options.palette),
+        Command::Audio => return record_audio(machine(&rom, mode, options)?, options),
+        _ => {}
+    }
+    let mut out = output(options)?;
+
+    match command {
+        Command::Run => {
+            let mut emu = machine(&rom, mode, options)?;
+            run_frames(&mut emu, options.frames.unwrap_or(RUN_FRAMES), |_| {})?;
+            write_screen(&emu, &mut out)
+        }
+        Command::Disasm => {
+            let listing = Disassembly::new(&rom, mode).listing(options.syntax);
+            out.write_all(listing.as_bytes())
+        }
+        Command::Trace => {
+            let mut emu = machine(&rom, mode, options)?;
+            let tracer = if options.json {
+                Tracer::json_lines(out)
+            } else {
+                Tracer::text(out)
+            };
+            emu.set_tracer(Some(tracer.with_filter(options.filter.clone())));
+            let result = run_frames(&mut emu, options.frames.unwrap_or(DEFAULT_FRAMES), |_| {});
+            let flushed = emu
+                .take_tracer()
+                .map_or(Ok(()), |mut tracer| tracer.flush());
+            result?;
+            return flushed.map_err(|err| err.to_string());
+        }
+        Command::Screenshot => {
+            let mut emu = machine(&rom, mode, options)?;
+            let mut phosphor = Phosphor::new(options.persistence);
+            run_frames(&mut emu, options.frames.unwrap_or(DEFAULT_FRAMES), |emu| {
+                phosphor.update(emu)
+            })?;
+            let renderer = Renderer::new(options.palette).scale(options.scale);
+            render(&emu, &renderer, &phosphor)
+                .to_png()
+                .and_then(|png| out.write_all(&png))
+        }
+        Command::Record => {
+            let mut emu = machine(&rom, mode, options)?;
+            let mut phosphor = Phosphor::new(options.persistence);
+            let renderer = Renderer::new(options.palette).scale(options.scale);
+            let mut recorder = GifRecorder::new(out, renderer.clone());
+            let mut captured = Ok(());
+            run_frames(&mut emu, options.frames.unwrap_or(RUN_FRAMES), |emu| {
+                phosphor.update(emu);
+                if captured.is_ok() {
+                    captured = recorder.capture_image(render(emu, &renderer, &phosphor));
+                }
+            })?;
+            return captured
+                .and_then(|_| recorder.finish())
+                .map(drop)
+                .map_err(|err| err.to_string());
+        }
+        Command::Info => write_info(&rom, mode, &mut out),
+        Command::Play | Command::Debug | Command::Audio => unreachable!("handled above"),
+    }
+    .and_then(|_| out.flush())
+    .map_err(|err| err.to_string())
+}
+
+/// Where the command's output goes: the `--out` file or stdout.
+fn output(options: &Options) -> Result<Box<dyn Write>, String> {
+    match &options.out {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|err| format!("cannot create {}: {}", path.display(), err))?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+fn machine(rom: &[u8], mode: Mode, options: &Options) -> Result<Emu, String> {
+    let mut emu = Emu::with_mode(mode);
+    let mut quirks = emu.quirks();
+    for item in &options.quirks {
+        cli::apply_quirk(&mut quirks, item)?;
+    }
+    emu.set_quirks(quirks);
+    if let Some(speed) = options.speed {
+        emu.set_speed(speed);
+    }
+    if let Some(seed) = options.seed {
+        emu.seed(seed);
+    }
+    emu.load_rom(rom).map_err(|err| err.to_string())?;
+    Ok(emu)
+}
+
+/// Runs up to `frames` frames, stopping early if the program exits, and
+/// calls `after_frame` at the end of each one.
+fn run_frames(emu: &mut Emu, frames: u32, mut after_frame: impl FnMut(&Emu)) -> Result<(), String> {
+    for _ in 0..frames {
+        if emu.run_frame().map_err(|err| err.to_string())? == StepOutcome::Halted {
+            break;
+        }
+        after_frame(emu);
+    }
+    Ok(())
+}
+
+/// Prints the display as text: `.` for background, `#` for the first plane,
+/// and `+`/`@` for the XO-CHIP second plane and both planes.
+fn write_screen(emu: &Emu, out: &mut dyn Write) -> io::Result<()> {
+    let pixels: Vec<u8> = emu.pixels().collect();
+    for row in pixels.chunks(emu.screen_width()) {
+        let line: String = row
+            .iter()
+            .map(|&p| ['.', '#', '+', '@'][p as usize])
+            .collect();
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Runs the ROM and writes what the beeper plays to the `--out` WAV file.
+fn record_audio(mut emu: Emu, options: &Options) -> Result<(), String> {
+    let path = options.out.as_ref().ok_or("`audio` needs `--out <file>`")?;
+    let file =
+        File::create(path).map_err(|err| format!("cannot create {}: {}", path.display(), err))?;
+    let mut beeper = Beeper::new(SAMPLE_RATE);
+    if let Some(tone) = options.tone {
+        beeper = beeper.tone(tone as f32);
+    }
+    if let Some(volume) = options.volume {
+        beeper = beeper.volume(volume as f32 / 100.0);
+    }
+
+    let mut wav =
+        WavWriter::new(BufWriter::new(file), SAMPLE_RATE).map_err(|err| err.to_string())?;
+    let mut samples = Vec::new();
+    let mut written = Ok(());
+    run_frames(&mut emu, options.frames.unwrap_or(RUN_FRAMES), |emu| {
+        samples.clear();
+        beeper.frame(emu, &mut samples);
+        if written.is_ok() {
+            written = wav.write_samples(&samples);
+        }
+    })?;
+    written
+        .and_then(|_| wav.finish())
+        .map(drop)
+        .map_err(|err| err.to_string())
+}
+
+/// Renders the display, through `phosphor` unless persistence is off.
+fn render(emu: &Emu, renderer: &Renderer, phosphor: &Phosphor) -> Image {
+    match phosphor.persistence() {
+        Persistence::Off => renderer.render(emu),
+        _ => renderer.render_intensity(phosphor.intensity(), phosphor.width()),
+    }
+}
+
+fn write_info(rom: &[u8], mode: Mode, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "size:     {} bytes", rom.len())?;
+    writeln!(out, "sha1:     {}", sha1::to_hex(&sha1::sha1(rom)))?;
+    writeln!(out, "platform: {}", cli::mode_name(mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn pong2() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../roms/PONG2")
+    }
+
+    /// Runs a command line with `--out` pointing at a fresh temporary file,
+    /// returning what was written.
+    fn run(line: &str) -> Vec<u8> {
+        let out = std::env::temp_dir().join(format!(
+            "chips-and-rust-{}-{}",
+            std::process::id(),
+            line.replace([' ', '/'], "_")
+        ));
+        let mut args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        args.insert(1, pong2().display().to_string());
+        args.extend(["--out".to_string(), out.display().to_string()]);
+
+        let cli::Invocation::Command(command, options) = cli::parse(args).unwrap() else {
+            panic!("not a command");
+        };
+        execute(command, &options).unwrap();
+        let written = fs::read(&out).unwrap();
+        fs::remove_file(&out).unwrap();
+        written
+    }
+
+    #[test]
+    fn test_info() {
+        let text = String::from_utf8(run("info")).unwrap();
+
+        assert!(text.starts_with("size:     264 bytes\nsha1:     "));
+        assert!(text.ends_with("platform: CHIP-8\n"));
+    }
+
+    #[test]
+    fn test_run_prints_screen() {
+        let text = String::from_utf8(run("run --frames 30 --seed 1")).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+
+        assert_eq!(lines.len(), 32);
+        assert!(lines.iter().all(|line| line.len() == 64));
+        assert!(text.contains('#'));
+    }
+
+    #[test]
+    fn test_screenshot_is_png() {
+        let image = run("screenshot --frames 30 --seed 1 --scale 2 --palette amber");
+
+        assert!(image.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&image[12..16], b"IHDR");
+        assert_eq!(image[16..24], [0, 0, 0, 128, 0, 0, 0, 64]);
+        assert!(image.ends_with(b"IEND\xae\x42\x60\x82"));
+    }
+
+    #[test]
+    fn test_record_gif() {
+        let gif = run("record --frames 120 --seed 1 --phosphor blend");
+
+        assert!(gif.starts_with(b"GIF89a\x40\x00\x20\x00"));
+        assert_eq!(gif.last(), Some(&0x3B));
+        let frames = gif.windows(3).filter(|w| *w == [0x21, 0xF9, 0x04]).count();
+        assert!(frames > 1 && frames < 120, "{} frames", frames);
+    }
+
+    #[test]
+    fn test_audio_wav() {
+        let wav = run("audio --frames 60 --seed 1");
+
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..12], b"WAVE");
+        assert_eq!(wav.len(), 44 + 2 * SAMPLE_RATE as usize);
+    }
+
+    #[test]
+    fn test_trace_and_disasm() {
+        let trace = String::from_utf8(run("trace --frames 1 --json")).unwrap();
+        assert!(
+            trace
+                .lines()
+                .next()
+                .unwrap()
+                .starts_with(r#"{"n":0,"pc":512,"#)
+        );
+
+        let listing = String::from_utf8(run("disasm --syntax octo")).unwrap();
+        assert!(listing.contains(": main"));
+    }
+}
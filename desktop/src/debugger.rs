@@ -0,0 +1,661 @@
+//! Debugs a ROM in the terminal.
+//!
+//! The screen is split into panes around a `chip8_core::Debugger`:
+//!
+//! ```text
+//! status
+//! disassembly    registers   screen
+//! (around PC)    stack
+//!
+//! memory (hex and ASCII)
+//! key help
+//! ```
+//!
+//! While paused, keys drive the debugger; while running they go to the
+//! keypad as in `play`, and Esc pauses again. Registers that the last
+//! command changed are highlighted.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use chip8_core::{Breakpoint, Debugger, Emu, Instruction, Palette, Rgba, StopReason, TIMER_HZ};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+
+use crate::tui::{self, Keypad, Terminal};
+
+const CODE_ROWS: usize = 16;
+const CODE_WIDTH: usize = 34;
+const REGS_X: u16 = 36;
+const REGS_WIDTH: usize = 22;
+const SCREEN_X: u16 = 60;
+const MEMORY_Y: u16 = CODE_ROWS as u16 + 2;
+const MEMORY_ROWS: usize = 8;
+const BYTES_PER_ROW: usize = 16;
+const WIDTH: u16 = SCREEN_X + 64;
+const HEIGHT: u16 = MEMORY_Y + MEMORY_ROWS as u16 + 2;
+const HELP: &str = " s step  n over  o out  c continue  Esc pause  b breakpoint  \
+                    g run to cursor  . to PC  Tab pane  Enter edit  r reset  q quit";
+
+/// How a piece of text is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Title,
+    /// The instruction at PC.
+    Pc,
+    /// The cursor of the active pane.
+    Cursor,
+    /// A value the last command changed, or a breakpoint marker.
+    Changed,
+}
+
+/// A line of text made of styled pieces.
+type Line = Vec<(String, Style)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Code,
+    Memory,
+}
+
+/// The registers shown in the register pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Registers {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u16,
+    dt: u8,
+    st: u8,
+}
+
+impl Registers {
+    fn of(emu: &Emu) -> Self {
+        Self {
+            v: *emu.v_reg(),
+            i: emu.i_reg(),
+            pc: emu.pc(),
+            sp: emu.sp(),
+            dt: emu.delay_timer(),
+            st: emu.sound_timer(),
+        }
+    }
+}
+
+/// The debugger's state between key presses.
+struct DebugView {
+    dbg: Debugger,
+    running: bool,
+    pane: Pane,
+    code_cursor: u16,
+    mem_cursor: u16,
+    /// Memory editing: `Some(None)` waits for a high nibble, `Some(Some(n))`
+    /// for the low nibble to go with `n`.
+    edit: Option<Option<u8>>,
+    /// The registers before the last command, to highlight what it changed.
+    before: Registers,
+    /// The temporary breakpoint of "run to cursor".
+    run_to: Option<usize>,
+    status: String,
+    keypad: Keypad,
+    releases: bool,
+    quit: bool,
+}
+
+impl DebugView {
+    fn new(emu: Emu, releases: bool) -> Self {
+        let before = Registers::of(&emu);
+        Self {
+            code_cursor: emu.pc(),
+            mem_cursor: emu.i_reg(),
+            dbg: Debugger::new(emu),
+            running: false,
+            pane: Pane::Code,
+            edit: None,
+            before,
+            run_to: None,
+            status: "paused".to_string(),
+            keypad: Keypad::default(),
+            releases,
+            quit: false,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.quit = true;
+            return;
+        }
+        if self.running {
+            if key.code == KeyCode::Esc && key.kind == KeyEventKind::Press {
+                self.pause();
+            } else {
+                self.keypad.handle(&mut self.dbg, &key, self.releases);
+            }
+            return;
+        }
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        if let Some(high) = self.edit {
+            self.edit_key(key.code, high);
+            return;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Char('s') => self.command(Debugger::step_into),
+            KeyCode::Char('n') => self.command(Debugger::step_over),
+            KeyCode::Char('o') => self.command(Debugger::step_out),
+            KeyCode::Char('c') => self.resume(),
+            KeyCode::Char('b') => self.toggle_breakpoint(self.code_cursor),
+            KeyCode::Char('g') => self.run_to_cursor(),
+            KeyCode::Char('.') => self.code_cursor = self.dbg.emu().pc(),
+            KeyCode::Char('r') => {
+                self.before = Registers::of(self.dbg.emu());
+                self.dbg.emu_mut().reset();
+                self.code_cursor = self.dbg.emu().pc();
+                self.status = "reset".to_string();
+            }
+            KeyCode::Tab => {
+                self.pane = match self.pane {
+                    Pane::Code => Pane::Memory,
+                    Pane::Memory => Pane::Code,
+                }
+            }
+            KeyCode::Enter if self.pane == Pane::Memory => self.edit = Some(None),
+            code => self.move_cursor(code),
+        }
+    }
+
+    /// Moves the cursor of the active pane: by an instruction or a page of
+    /// code, or by a byte, a row or a page of memory.
+    fn move_cursor(&mut self, code: KeyCode) {
+        let (cursor, line, page) = match self.pane {
+            Pane::Code => (&mut self.code_cursor, 2, 2 * CODE_ROWS as i32),
+            Pane::Memory => (
+                &mut self.mem_cursor,
+                BYTES_PER_ROW as i32,
+                (BYTES_PER_ROW * MEMORY_ROWS) as i32,
+            ),
+        };
+        let delta = match code {
+            KeyCode::Left if self.pane == Pane::Memory => -1,
+            KeyCode::Right if self.pane == Pane::Memory => 1,
+            KeyCode::Up => -line,
+            KeyCode::Down => line,
+            KeyCode::PageUp => -page,
+            KeyCode::PageDown => page,
+            _ => return,
+        };
+        let last = self.dbg.emu().ram().len() as i32 - 1;
+        *cursor = (*cursor as i32 + delta).clamp(0, last) as u16;
+    }
+
+    /// Takes a hex digit while editing memory. The byte is written once both
+    /// nibbles are in, and the cursor moves on to the next one.
+    fn edit_key(&mut self, code: KeyCode, high: Option<u8>) {
+        match code {
+            KeyCode::Char(c) if c.is_ascii_hexdigit() => {
+                let digit = c.to_digit(16).unwrap_or(0) as u8;
+                let Some(high) = high else {
+                    self.edit = Some(Some(digit));
+                    return;
+                };
+                let ram = self.dbg.emu_mut().ram_mut();
+                let addr = self.mem_cursor as usize;
+                ram[addr] = high << 4 | digit;
+                self.mem_cursor = (addr + 1).min(ram.len() - 1) as u16;
+                self.edit = Some(None);
+            }
+            KeyCode::Esc | KeyCode::Enter => self.edit = None,
+            _ => {}
+        }
+    }
+
+    fn toggle_breakpoint(&mut self, addr: u16) {
+        match self.breakpoint_at(addr) {
+            Some(id) => {
+                self.dbg.remove_breakpoint(id);
+            }
+            None => {
+                self.dbg.add_breakpoint(Breakpoint::Pc(addr));
+            }
+        }
+    }
+
+    fn breakpoint_at(&self, addr: u16) -> Option<usize> {
+        self.dbg
+            .breakpoints()
+            .find(|(_, bp)| **bp == Breakpoint::Pc(addr))
+            .map(|(id, _)| id)
+    }
+
+    fn run_to_cursor(&mut self) {
+        if self.breakpoint_at(self.code_cursor).is_none() {
+            self.run_to = Some(self.dbg.add_breakpoint(Breakpoint::Pc(self.code_cursor)));
+        }
+        self.resume();
+    }
+
+    /// Runs a stepping command to completion.
+    fn command(&mut self, step: impl FnOnce(&mut Debugger) -> StopReason) {
+        self.before = Registers::of(self.dbg.emu());
+        let reason = step(&mut self.dbg);
+        self.stop(reason);
+    }
+
+    /// Starts running a frame at a time until something stops execution.
+    fn resume(&mut self) {
+        self.before = Registers::of(self.dbg.emu());
+        self.running = true;
+        self.status = "running".to_string();
+    }
+
+    fn pause(&mut self) {
+        self.stop(StopReason::Step);
+        self.status = "paused".to_string();
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.running = false;
+        if let Some(id) = self.run_to.take() {
+            self.dbg.remove_breakpoint(id);
+        }
+        for key in 0..16 {
+            self.dbg.key_up(key);
+        }
+        self.keypad = Keypad::default();
+        self.code_cursor = self.dbg.emu().pc();
+        self.status = match reason {
+            StopReason::Step => "paused".to_string(),
+            reason => format!("stopped: {}", reason),
+        };
+    }
+
+    /// Runs one 60 Hz frame if running.
+    fn frame(&mut self, now: Instant) {
+        if !self.running {
+            return;
+        }
+        self.keypad.release_expired(&mut self.dbg, now);
+        if let Some(reason) = self.dbg.run_frame() {
+            self.stop(reason);
+        }
+    }
+
+    fn status_line(&self) -> Line {
+        let state = if self.running { "RUNNING" } else { "PAUSED" };
+        vec![
+            (format!(" {} ", state), Style::Title),
+            (format!(" {}", self.status), Style::Plain),
+        ]
+    }
+
+    /// The disassembly from a few instructions before the cursor, decoded
+    /// linearly, with breakpoints marked and the PC line highlighted.
+    fn code_lines(&self) -> Vec<Line> {
+        let emu = self.dbg.emu();
+        let ram = emu.ram();
+        let pc = emu.pc();
+        let back = (CODE_ROWS as u16 / 2).min(self.code_cursor / 2);
+        let mut addr = (self.code_cursor - 2 * back) as usize;
+        let mut lines = Vec::new();
+        while lines.len() < CODE_ROWS && addr < ram.len() {
+            let ins = Instruction::read(&ram[addr..], emu.mode());
+            let size = ins.map_or(2, |ins| ins.size()).min(ram.len() - addr);
+            let bytes: String = ram[addr..addr + size]
+                .iter()
+                .map(|b| format!("{:02X}", b))
+                .collect();
+            let text = format!(
+                "{}{:03X}  {:<8}  {}",
+                if addr as u16 == pc { '>' } else { ' ' },
+                addr,
+                bytes,
+                ins.map_or("???".to_string(), |ins| ins.to_string())
+            );
+            let style = if self.pane == Pane::Code && addr as u16 == self.code_cursor {
+                Style::Cursor
+            } else if addr as u16 == pc {
+                Style::Pc
+            } else {
+                Style::Plain
+            };
+            let marker = if self.breakpoint_at(addr as u16).is_some() {
+                ("●".to_string(), Style::Changed)
+            } else {
+                (" ".to_string(), Style::Plain)
+            };
+            lines.push(vec![marker, (text, style)]);
+            addr += size;
+        }
+        lines
+    }
+
+    /// V0-VF, I, PC, SP and the timers, then the call stack, innermost first.
+    fn register_lines(&self) -> Vec<Line> {
+        let now = Registers::of(self.dbg.emu());
+        let before = self.before;
+        let value = |text: String, changed: bool| {
+            let style = if changed {
+                Style::Changed
+            } else {
+                Style::Plain
+            };
+            (text, style)
+        };
+        let label = |text: &str| (text.to_string(), Style::Plain);
+
+        let mut lines: Vec<Line> = (0..8)
+            .map(|row| {
+                let (a, b) = (row, row + 8);
+                vec![
+                    label(&format!("V{:X} ", a)),
+                    value(format!("{:02X}", now.v[a]), now.v[a] != before.v[a]),
+                    label(&format!("   V{:X} ", b)),
+                    value(format!("{:02X}", now.v[b]), now.v[b] != before.v[b]),
+                ]
+            })
+            .collect();
+        lines.push(vec![
+            label("I  "),
+            value(format!("{:04X}", now.i), now.i != before.i),
+            label(" PC "),
+            value(format!("{:04X}", now.pc), now.pc != before.pc),
+        ]);
+        lines.push(vec![
+            label("SP "),
+            value(format!("{:02X}", now.sp), now.sp != before.sp),
+            label(" DT "),
+            value(format!("{:02X}", now.dt), now.dt != before.dt),
+            label(" ST "),
+            value(format!("{:02X}", now.st), now.st != before.st),
+        ]);
+        lines.push(Vec::new());
+        lines.push(vec![("Stack".to_string(), Style::Title)]);
+
+        let room = CODE_ROWS - lines.len();
+        let stack = self.dbg.emu().stack();
+        for (depth, addr) in stack.iter().rev().enumerate() {
+            if depth + 1 == room && stack.len() > room {
+                lines.push(vec![label(&format!("  +{} more", stack.len() - depth))]);
+                break;
+            }
+            lines.push(vec![label(&format!("{:>2} {:03X}", depth, addr))]);
+        }
+        lines
+    }
+
+    /// Hex and ASCII rows around the memory cursor.
+    fn memory_lines(&self) -> Vec<Line> {
+        let ram = self.dbg.emu().ram();
+        let rows = ram.len() / BYTES_PER_ROW;
+        let cursor_row = self.mem_cursor as usize / BYTES_PER_ROW;
+        let top = cursor_row
+            .saturating_sub(MEMORY_ROWS / 2)
+            .min(rows.saturating_sub(MEMORY_ROWS));
+
+        (top..rows.min(top + MEMORY_ROWS))
+            .map(|row| {
+                let start = row * BYTES_PER_ROW;
+                let bytes = &ram[start..start + BYTES_PER_ROW];
+                let mut line = vec![(format!("{:04X} ", start), Style::Plain)];
+                for (offset, &byte) in bytes.iter().enumerate() {
+                    let addr = start + offset;
+                    line.push((" ".to_string(), Style::Plain));
+                    if self.pane == Pane::Memory && addr == self.mem_cursor as usize {
+                        let text = match self.edit {
+                            Some(Some(high)) => format!("{:X}_", high),
+                            Some(None) => "__".to_string(),
+                            None => format!("{:02X}", byte),
+                        };
+                        line.push((text, Style::Cursor));
+                    } else {
+                        line.push((format!("{:02X}", byte), Style::Plain));
+                    }
+                }
+                let ascii: String = bytes
+                    .iter()
+                    .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+                    .collect();
+                line.push((format!("  {}", ascii), Style::Plain));
+                line
+            })
+            .collect()
+    }
+
+    /// The display, with hires halved so it fits next to the other panes.
+    fn screen_cells(&self, palette: Palette) -> Vec<Vec<(Rgba, Rgba)>> {
+        let emu = self.dbg.emu();
+        let mut pixels: Vec<u8> = emu.pixels().collect();
+        let mut width = emu.screen_width();
+        if width > 64 {
+            pixels = downsample(&pixels, width);
+            width /= 2;
+        }
+        let colors: Vec<Rgba> = pixels.iter().map(|&pixel| palette.color(pixel)).collect();
+        tui::cells(&colors, width)
+    }
+}
+
+/// Halves an image in both directions, ORing each 2x2 block.
+fn downsample(pixels: &[u8], width: usize) -> Vec<u8> {
+    let rows: Vec<&[u8]> = pixels.chunks(width).collect();
+    rows.chunks(2)
+        .flat_map(|pair| {
+            (0..width / 2).map(move |x| {
+                pair.iter()
+                    .map(|row| row[2 * x] | row[2 * x + 1])
+                    .fold(0, |acc, p| acc | p)
+            })
+        })
+        .collect()
+}
+
+/// Debugs `emu` in the terminal until `q` or Ctrl-C.
+pub fn debug(emu: Emu, palette: Palette) -> Result<(), String> {
+    let term = Terminal::enter().map_err(|err| err.to_string())?;
+    let mut view = DebugView::new(emu, term.releases);
+    let result = debug_loop(&mut view, palette);
+    drop(term);
+    result
+}
+
+fn debug_loop(view: &mut DebugView, palette: Palette) -> Result<(), String> {
+    let frame = Duration::from_secs(1) / TIMER_HZ;
+    let mut next_frame = Instant::now();
+    let mut out = io::stdout();
+    let mut dirty = true;
+
+    while !view.quit {
+        while let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+            if !event::poll(wait).map_err(|err| err.to_string())? {
+                break;
+            }
+            match event::read().map_err(|err| err.to_string())? {
+                Event::Key(key) => view.handle_key(key),
+                Event::Resize(..) => {
+                    queue!(out, ResetColor, terminal::Clear(terminal::ClearType::All))
+                        .map_err(|err| err.to_string())?;
+                }
+                _ => {}
+            }
+            dirty = true;
+            if view.quit {
+                return Ok(());
+            }
+        }
+        next_frame += frame;
+        next_frame = next_frame.max(Instant::now());
+
+        if view.running {
+            view.frame(Instant::now());
+            dirty = true;
+        }
+        if dirty {
+            draw(&mut out, view, palette).map_err(|err| err.to_string())?;
+            dirty = false;
+        }
+    }
+    Ok(())
+}
+
+fn draw(out: &mut impl Write, view: &DebugView, palette: Palette) -> io::Result<()> {
+    let (cols, lines) = terminal::size()?;
+    if cols < WIDTH || lines < HEIGHT {
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            ResetColor,
+            terminal::Clear(terminal::ClearType::All),
+            Print(format!("Terminal too small: need {}x{}", WIDTH, HEIGHT))
+        )?;
+        return out.flush();
+    }
+
+    let width = WIDTH as usize;
+    draw_lines(out, (0, 0), (width, 1), &[view.status_line()])?;
+    draw_lines(out, (0, 1), (CODE_WIDTH, CODE_ROWS), &view.code_lines())?;
+    draw_lines(
+        out,
+        (REGS_X, 1),
+        (REGS_WIDTH, CODE_ROWS),
+        &view.register_lines(),
+    )?;
+    tui::draw_cells(out, SCREEN_X, 1, &view.screen_cells(palette))?;
+    draw_lines(
+        out,
+        (0, MEMORY_Y),
+        (width, MEMORY_ROWS),
+        &view.memory_lines(),
+    )?;
+    let help = vec![(HELP.to_string(), Style::Plain)];
+    draw_lines(out, (0, HEIGHT - 1), (width, 1), &[help])?;
+    out.flush()
+}
+
+/// Queues `lines` in the `width` by `height` pane at (x, y), padding them
+/// with spaces so nothing of the previous draw is left.
+fn draw_lines(
+    out: &mut impl Write,
+    (x, y): (u16, u16),
+    (width, height): (usize, usize),
+    lines: &[Line],
+) -> io::Result<()> {
+    let blank = Line::new();
+    for dy in 0..height {
+        let line = lines.get(dy).unwrap_or(&blank);
+        queue!(out, cursor::MoveTo(x, y + dy as u16))?;
+        let mut used = 0;
+        for (text, style) in line {
+            let text: String = text.chars().take(width - used).collect();
+            used += text.chars().count();
+            match style {
+                Style::Plain => queue!(out, ResetColor)?,
+                Style::Title => queue!(out, SetAttribute(Attribute::Reverse))?,
+                Style::Pc => queue!(out, SetForegroundColor(Color::Yellow))?,
+                Style::Cursor => queue!(out, SetAttribute(Attribute::Reverse))?,
+                Style::Changed => queue!(out, SetForegroundColor(Color::Red))?,
+            }
+            queue!(out, Print(text), SetAttribute(Attribute::Reset), ResetColor)?;
+        }
+        queue!(out, Print(" ".repeat(width - used)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(line: &Line) -> String {
+        line.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    /// A view of a small program: `LD V0, 05` / `CALL 208` / `JP 204` /
+    /// `ADD V0, 01` / `RET`.
+    fn view() -> DebugView {
+        let mut emu = Emu::new();
+        emu.load_rom(&[0x60, 0x05, 0x22, 0x06, 0x12, 0x04, 0x70, 0x01, 0x00, 0xEE])
+            .unwrap();
+        DebugView::new(emu, false)
+    }
+
+    fn press(view: &mut DebugView, code: KeyCode) {
+        view.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn test_code_lines() {
+        let mut view = view();
+        press(&mut view, KeyCode::Char('b'));
+        let lines = view.code_lines();
+
+        // Half the pane shows the code before the cursor.
+        assert_eq!(lines.len(), CODE_ROWS);
+        assert!(text(&lines[7]).starts_with("  1FE  0000"));
+        assert_eq!(text(&lines[8]), "●>200  6005      LD V0, 0x05");
+        assert_eq!(lines[8][1].1, Style::Cursor);
+        assert!(text(&lines[9]).starts_with("  202  2206"));
+    }
+
+    #[test]
+    fn test_step_highlights_changes() {
+        let mut view = view();
+        press(&mut view, KeyCode::Char('s'));
+        let lines = view.register_lines();
+
+        assert_eq!(lines[0][1], ("05".to_string(), Style::Changed));
+        assert_eq!(lines[1][1], ("00".to_string(), Style::Plain));
+        assert_eq!(view.code_cursor, 0x202);
+
+        press(&mut view, KeyCode::Char('s'));
+        let lines = view.register_lines();
+        assert_eq!(lines[0][1].1, Style::Plain);
+        assert_eq!(text(&lines[12]), " 0 204");
+    }
+
+    #[test]
+    fn test_run_to_cursor() {
+        let mut view = view();
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Down);
+        press(&mut view, KeyCode::Down);
+        assert_eq!(view.code_cursor, 0x206);
+
+        press(&mut view, KeyCode::Char('g'));
+        view.frame(Instant::now());
+
+        assert!(!view.running);
+        assert_eq!(view.dbg.emu().pc(), 0x206);
+        assert_eq!(view.status, "stopped: breakpoint 1 at 206");
+        assert_eq!(view.dbg.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_memory_edit() {
+        let mut view = view();
+        press(&mut view, KeyCode::Tab);
+        press(&mut view, KeyCode::Enter);
+        view.mem_cursor = 0x300;
+        press(&mut view, KeyCode::Char('a'));
+        assert_eq!(text(&view.memory_lines()[4])[5..16], *" A_ 00 00 0");
+        press(&mut view, KeyCode::Char('B'));
+        press(&mut view, KeyCode::Esc);
+
+        assert_eq!(view.dbg.emu().ram()[0x300], 0xAB);
+        assert_eq!(view.mem_cursor, 0x301);
+        assert_eq!(view.edit, None);
+    }
+
+    #[test]
+    fn test_downsample() {
+        // 4x2 image halves to 2x1, each pixel the OR of a 2x2 block.
+        let pixels = [1, 0, 0, 0, 0, 0, 2, 1];
+
+        assert_eq!(downsample(&pixels, 4), [1, 3]);
+    }
+}
@@ -1,3 +1,30 @@
-fn main() {
-    println!("Hello, world!");
+mod cli;
+mod commands;
+mod debugger;
+mod sha1;
+mod tui;
+
+use std::env;
+use std::process::ExitCode;
+
+use cli::Invocation;
+
+fn main() -> ExitCode {
+    match cli::parse(env::args().skip(1)) {
+        Ok(Invocation::Help) => {
+            print!("{}", cli::USAGE);
+            ExitCode::SUCCESS
+        }
+        Ok(Invocation::Command(command, options)) => match commands::execute(command, &options) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        },
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            ExitCode::from(2)
+        }
+    }
 }
@@ -0,0 +1,413 @@
+//! Plays a ROM in the terminal.
+//!
+//! Each character cell shows two pixels stacked vertically as an upper half
+//! block (`▀`) whose foreground is the top pixel and background the bottom
+//! one, so the 64x32 display needs 64x16 cells and hires mode 128x32.
+//!
+//! With a `Persistence` other than `Off`, pixels fade between the palette's
+//! background and foreground through a `Phosphor` to hide sprite flicker.
+//!
+//! Most terminals only report key presses, so a key counts as held for
+//! `KEY_HOLD` after its last press (or autorepeat) unless the terminal
+//! supports reporting releases.
+
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use chip8_core::{Debugger, Emu, Palette, Persistence, Phosphor, Renderer, Rgba, TIMER_HZ};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+/// How long a key stays down after a press when the terminal sends no release.
+const KEY_HOLD: Duration = Duration::from_millis(200);
+const UPPER_HALF: char = '▀';
+
+fn color([r, g, b, _]: Rgba) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+/// The COSMAC VIP keypad on the left of a QWERTY keyboard:
+///
+/// ```text
+/// 1 2 3 4      1 2 3 C
+/// Q W E R  ->  4 5 6 D
+/// A S D F      7 8 9 E
+/// Z X C V      A 0 B F
+/// ```
+pub fn keypad_key(c: char) -> Option<u8> {
+    let key = match c.to_ascii_lowercase() {
+        '1' => 0x1,
+        '2' => 0x2,
+        '3' => 0x3,
+        '4' => 0xC,
+        'q' => 0x4,
+        'w' => 0x5,
+        'e' => 0x6,
+        'r' => 0xD,
+        'a' => 0x7,
+        's' => 0x8,
+        'd' => 0x9,
+        'f' => 0xE,
+        'z' => 0xA,
+        'x' => 0x0,
+        'c' => 0xB,
+        'v' => 0xF,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Where `Keypad` presses keys: the machine, or a `Debugger` running it,
+/// which must not lose its place at a stop when a key is pressed.
+pub(crate) trait Keys {
+    fn key_down(&mut self, key: u8);
+    fn key_up(&mut self, key: u8);
+}
+
+impl Keys for Emu {
+    fn key_down(&mut self, key: u8) {
+        Emu::key_down(self, key);
+    }
+
+    fn key_up(&mut self, key: u8) {
+        Emu::key_up(self, key);
+    }
+}
+
+impl Keys for Debugger {
+    fn key_down(&mut self, key: u8) {
+        Debugger::key_down(self, key);
+    }
+
+    fn key_up(&mut self, key: u8) {
+        Debugger::key_up(self, key);
+    }
+}
+
+/// Keys currently held and when each one lapses without a fresh press.
+#[derive(Debug, Default)]
+pub(crate) struct Keypad {
+    held: [Option<Instant>; 16],
+}
+
+impl Keypad {
+    /// Passes a keypad key event on to `keys`, returning false for keys
+    /// outside the keypad. `releases` says whether the terminal reports key
+    /// releases, making the hold timer unnecessary.
+    pub(crate) fn handle(&mut self, keys: &mut impl Keys, key: &KeyEvent, releases: bool) -> bool {
+        let KeyCode::Char(c) = key.code else {
+            return false;
+        };
+        let Some(pad) = keypad_key(c) else {
+            return false;
+        };
+        match key.kind {
+            KeyEventKind::Release => {
+                self.release(pad);
+                keys.key_up(pad);
+            }
+            _ => {
+                if releases {
+                    self.release(pad);
+                } else {
+                    self.press(pad, Instant::now());
+                }
+                keys.key_down(pad);
+            }
+        }
+        true
+    }
+
+    /// Lets go of the keys whose hold ran out by `now`.
+    pub(crate) fn release_expired(&mut self, keys: &mut impl Keys, now: Instant) {
+        for key in self.expire(now) {
+            keys.key_up(key);
+        }
+    }
+
+    fn press(&mut self, key: u8, now: Instant) {
+        self.held[key as usize] = Some(now + KEY_HOLD);
+    }
+
+    fn release(&mut self, key: u8) {
+        self.held[key as usize] = None;
+    }
+
+    /// Releases the keys whose hold ran out by `now`, returning them.
+    fn expire(&mut self, now: Instant) -> Vec<u8> {
+        let mut released = Vec::new();
+        for (key, deadline) in self.held.iter_mut().enumerate() {
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                *deadline = None;
+                released.push(key as u8);
+            }
+        }
+        released
+    }
+}
+
+/// Pairs up rows of `pixels` into (top, bottom) cells, one line per two rows.
+pub(crate) fn cells<T: Copy + Default>(pixels: &[T], width: usize) -> Vec<Vec<(T, T)>> {
+    pixels
+        .chunks(width * 2)
+        .map(|rows| {
+            let (top, bottom) = rows.split_at(width.min(rows.len()));
+            (0..top.len())
+                .map(|x| (top[x], bottom.get(x).copied().unwrap_or_default()))
+                .collect()
+        })
+        .collect()
+}
+
+/// Raw mode on the alternate screen, undone on drop even if the game panics.
+pub(crate) struct Terminal {
+    /// Whether the terminal reports key releases.
+    pub(crate) releases: bool,
+}
+
+impl Terminal {
+    pub(crate) fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(Self { releases })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        if self.releases {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            out,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// What was last put on screen, to skip redrawing identical frames.
+#[derive(Default, PartialEq)]
+struct Shown {
+    colors: Vec<Rgba>,
+    beeping: bool,
+    size: (u16, u16),
+}
+
+/// Runs `emu` in the terminal until Esc or Ctrl-C. F5 restarts the ROM.
+pub fn play(mut emu: Emu, palette: Palette, persistence: Persistence) -> Result<(), String> {
+    let term = Terminal::enter().map_err(|err| err.to_string())?;
+    let mut phosphor = Phosphor::new(persistence);
+    let result = game_loop(&mut emu, palette, &mut phosphor, term.releases);
+    drop(term);
+    result
+}
+
+fn game_loop(
+    emu: &mut Emu,
+    palette: Palette,
+    phosphor: &mut Phosphor,
+    releases: bool,
+) -> Result<(), String> {
+    let frame = Duration::from_secs(1) / TIMER_HZ;
+    let mut keypad = Keypad::default();
+    let mut shown = Shown::default();
+    let mut next_frame = Instant::now();
+    let mut out = io::stdout();
+
+    loop {
+        while let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+            if !event::poll(wait).map_err(|err| err.to_string())? {
+                break;
+            }
+            match event::read().map_err(|err| err.to_string())? {
+                Event::Key(key) => {
+                    if quits(&key) {
+                        return Ok(());
+                    }
+                    if key.code == KeyCode::F(5) && key.kind == KeyEventKind::Press {
+                        emu.reset();
+                        phosphor.reset();
+                    } else {
+                        keypad.handle(emu, &key, releases);
+                    }
+                }
+                Event::Resize(..) => shown = Shown::default(),
+                _ => {}
+            }
+        }
+        next_frame += frame;
+        // After a stall (e.g. the terminal was suspended) do not try to catch up.
+        next_frame = next_frame.max(Instant::now());
+
+        keypad.release_expired(emu, Instant::now());
+        emu.run_frame().map_err(|err| err.to_string())?;
+
+        let now_shown = Shown {
+            colors: colors(emu, palette, phosphor),
+            beeping: emu.is_beeping(),
+            size: terminal::size().map_err(|err| err.to_string())?,
+        };
+        if now_shown != shown {
+            let redraw_all = now_shown.size != shown.size;
+            draw(&mut out, emu, &now_shown, palette, redraw_all).map_err(|err| err.to_string())?;
+            shown = now_shown;
+        }
+    }
+}
+
+fn quits(key: &KeyEvent) -> bool {
+    key.kind == KeyEventKind::Press
+        && (key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)))
+}
+
+/// The colour of each pixel, through `phosphor` unless persistence is off.
+fn colors(emu: &Emu, palette: Palette, phosphor: &mut Phosphor) -> Vec<Rgba> {
+    if phosphor.persistence() == Persistence::Off {
+        return emu.pixels().map(|pixel| palette.color(pixel)).collect();
+    }
+    phosphor.update(emu);
+    let image = Renderer::new(palette).render_intensity(phosphor.intensity(), phosphor.width());
+    image
+        .data
+        .chunks_exact(4)
+        .map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]])
+        .collect()
+}
+
+fn draw(
+    out: &mut impl Write,
+    emu: &Emu,
+    shown: &Shown,
+    palette: Palette,
+    clear: bool,
+) -> io::Result<()> {
+    let width = emu.screen_width();
+    let rows = cells(&shown.colors, width);
+    let (cols, lines) = shown.size;
+    if clear {
+        queue!(out, ResetColor, terminal::Clear(terminal::ClearType::All))?;
+    }
+    if (cols as usize) < width || (lines as usize) < rows.len() + 1 {
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            ResetColor,
+            Print(format!(
+                "Terminal too small: need {}x{}",
+                width,
+                rows.len() + 1
+            ))
+        )?;
+        return out.flush();
+    }
+
+    draw_cells(out, 0, 0, &rows)?;
+
+    // The visual bell: the status line lights up while the sound timer runs.
+    let status = format!("{:<1$}", " Esc quit  F5 reset", width.saturating_sub(6));
+    queue!(out, cursor::MoveTo(0, rows.len() as u16))?;
+    if shown.beeping {
+        queue!(
+            out,
+            SetForegroundColor(color(palette.color(0))),
+            SetBackgroundColor(color(palette.color(1))),
+            Print(status),
+            Print(" BEEP ")
+        )?;
+    } else {
+        queue!(out, ResetColor, Print(status), Print("      "))?;
+    }
+    queue!(out, ResetColor)?;
+    out.flush()
+}
+
+/// Queues `rows` of half-block cells with the top left corner at (x, y).
+pub(crate) fn draw_cells(
+    out: &mut impl Write,
+    x: u16,
+    y: u16,
+    rows: &[Vec<(Rgba, Rgba)>],
+) -> io::Result<()> {
+    for (dy, row) in rows.iter().enumerate() {
+        queue!(out, cursor::MoveTo(x, y + dy as u16))?;
+        let mut current = None;
+        for &(top, bottom) in row {
+            if current != Some((top, bottom)) {
+                queue!(
+                    out,
+                    SetForegroundColor(color(top)),
+                    SetBackgroundColor(color(bottom))
+                )?;
+                current = Some((top, bottom));
+            }
+            queue!(out, Print(UPPER_HALF))?;
+        }
+    }
+    queue!(out, ResetColor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keypad_layout() {
+        assert_eq!(keypad_key('1'), Some(0x1));
+        assert_eq!(keypad_key('4'), Some(0xC));
+        assert_eq!(keypad_key('W'), Some(0x5));
+        assert_eq!(keypad_key('x'), Some(0x0));
+        assert_eq!(keypad_key('v'), Some(0xF));
+        assert_eq!(keypad_key('p'), None);
+    }
+
+    #[test]
+    fn test_timed_release() {
+        let start = Instant::now();
+        let mut keypad = Keypad::default();
+        keypad.press(5, start);
+        keypad.press(7, start + KEY_HOLD / 2);
+
+        assert!(keypad.expire(start + KEY_HOLD / 4).is_empty());
+        assert_eq!(keypad.expire(start + KEY_HOLD), [5]);
+        assert_eq!(keypad.expire(start + KEY_HOLD * 2), [7]);
+        assert!(keypad.expire(start + KEY_HOLD * 3).is_empty());
+    }
+
+    #[test]
+    fn test_autorepeat_extends_hold() {
+        let start = Instant::now();
+        let mut keypad = Keypad::default();
+        keypad.press(5, start);
+        keypad.press(5, start + KEY_HOLD / 2);
+
+        assert!(keypad.expire(start + KEY_HOLD).is_empty());
+    }
+
+    #[test]
+    fn test_cells_pair_rows() {
+        // 2x4 image: rows 0 and 1 form the first line, rows 2 and 3 the second.
+        let pixels = [1, 0, 0, 1, 2, 2, 3, 0];
+
+        assert_eq!(
+            cells(&pixels, 2),
+            [vec![(1, 0), (0, 1)], vec![(2, 3), (2, 0)]]
+        );
+    }
+}